@@ -1,7 +1,91 @@
-use nalgebra::{Vector3, Matrix4};
+use std::cell::Cell;
+
+use nalgebra::{Vector3, Matrix3, Matrix4, Rotation3, UnitQuaternion};
 
 use crate::{helper, shape::ShapeBasics};
 
+pub const MOTION_BLUR_STEPS: usize = 16; //transformations per shutter interval (linear interpolated in between)
+
+/*
+some resources:
+
+motion blur:
+https://www.pbr-book.org/3ed-2018/Geometry_and_Transformations/Animating_Transformations
+https://www.pbr-book.org/3ed-2018/Camera_Models/Camera_Model#CameraInterface
+*/
+
+// ******************** sample time ********************
+
+thread_local!
+{
+    static SAMPLE_TIME: Cell<f32> = Cell::new(0.0);
+}
+
+// time of the current pixel sample on this thread (0 = shutter open, 1 = shutter close)
+pub fn set_sample_time(time: f32)
+{
+    SAMPLE_TIME.with(|sample_time| sample_time.set(time));
+}
+
+pub fn get_sample_time() -> f32
+{
+    SAMPLE_TIME.with(|sample_time| sample_time.get())
+}
+
+// transformation at the given time of evenly distributed motion steps
+// (translation and scale are interpolated linearly, the rotation spherically)
+pub fn interpolate_motion(motion: &Vec<Matrix4<f32>>, time: f32) -> Matrix4<f32>
+{
+    if motion.len() == 1
+    {
+        return motion[0];
+    }
+
+    let pos = time.clamp(0.0, 1.0) * (motion.len() - 1) as f32;
+    let index = (pos.floor() as usize).min(motion.len() - 2);
+    let factor = pos - index as f32;
+
+    let (translation_a, rotation_a, scale_a) = decompose_transformation(&motion[index]);
+    let (translation_b, rotation_b, scale_b) = decompose_transformation(&motion[index + 1]);
+
+    let translation = translation_a.lerp(&translation_b, factor);
+    let scale = scale_a.lerp(&scale_b, factor);
+
+    //the rotations are (almost) equal if slerp is not defined
+    let rotation = rotation_a.try_slerp(&rotation_b, factor, f32::EPSILON).unwrap_or(rotation_a);
+
+    Matrix4::new_translation(&translation) * rotation.to_homogeneous() * Matrix4::new_nonuniform_scaling(&scale)
+}
+
+// translation, rotation and scale of an affine transformation (shearing is not supported)
+pub fn decompose_transformation(trans: &Matrix4<f32>) -> (Vector3<f32>, UnitQuaternion<f32>, Vector3<f32>)
+{
+    let translation = Vector3::<f32>::new(trans[(0, 3)], trans[(1, 3)], trans[(2, 3)]);
+
+    let mut linear: Matrix3<f32> = trans.fixed_view::<3, 3>(0, 0).into_owned();
+
+    let mut scale = Vector3::<f32>::new(linear.column(0).norm(), linear.column(1).norm(), linear.column(2).norm());
+
+    //mirroring
+    if linear.determinant() < 0.0
+    {
+        scale.x = -scale.x;
+    }
+
+    for i in 0..3
+    {
+        if scale[i] != 0.0
+        {
+            let column = linear.column(i) / scale[i];
+            linear.set_column(i, &column);
+        }
+    }
+
+    let rotation = UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(linear));
+
+    (translation, UnitQuaternion::new_normalize(*rotation.quaternion()), scale)
+}
+
 
 // ******************** Frame ********************
 
@@ -108,14 +192,20 @@ impl Animation
 
     pub fn get_keyframes_for_frame(&self, frame: u64) -> (&Keyframe, &Keyframe, f64)
     {
-        let timestamp = ((1000.0 / self.fps as f64) * frame as f64).floor() as u64;
+        let timestamp = ((1000.0 / self.fps as f64) * frame as f64).floor();
 
+        self.get_keyframes_for_time(timestamp)
+    }
+
+    // timestamp in ms (does not need to match a frame)
+    pub fn get_keyframes_for_time(&self, timestamp: f64) -> (&Keyframe, &Keyframe, f64)
+    {
         let mut first_keyframe = self.keyframes.first().unwrap();
         let mut last_keyframe = self.keyframes.first().unwrap();
 
         for i in 0..self.keyframes.len()
         {
-            if self.keyframes[i].time <= timestamp
+            if self.keyframes[i].time as f64 <= timestamp
             {
                 first_keyframe = &self.keyframes[i];
 
@@ -130,16 +220,29 @@ impl Animation
             }
         }
 
-        let frame_time_pos = timestamp - first_keyframe.time;
+        let frame_time_pos = timestamp - first_keyframe.time as f64;
         let keyframe_time_diff = last_keyframe.time - first_keyframe.time;
-        let factor = (1.0 / keyframe_time_diff as f64) * frame_time_pos as f64;
+
+        //after the last keyframe
+        let mut factor = 0.0;
+        if keyframe_time_diff > 0
+        {
+            factor = (1.0 / keyframe_time_diff as f64) * frame_time_pos;
+        }
 
         (first_keyframe, last_keyframe, factor)
     }
 
     pub fn get_trans_for_frame(&self, frame: u64, object_name: String) -> Option<Matrix4<f32>>
     {
-        let (first, last, scale) = self.get_keyframes_for_frame(frame);
+        let timestamp = ((1000.0 / self.fps as f64) * frame as f64).floor();
+
+        self.get_trans_for_time(timestamp, &object_name)
+    }
+
+    pub fn get_trans_for_time(&self, timestamp: f64, object_name: &str) -> Option<Matrix4<f32>>
+    {
+        let (first, last, scale) = self.get_keyframes_for_time(timestamp);
 
         let mut first_keyframe = None;
         let mut last_keyframe = None;
@@ -204,4 +307,42 @@ impl Animation
         Some(trans)
     }
 
+    // transformations during the shutter interval (shutter open/close in frames relative to the frame)
+    // empty if the object is not animated or not moving while the shutter is open
+    pub fn get_motion_for_frame(&self, frame: u64, shutter_open: f32, shutter_close: f32, object_name: &str) -> Vec<Matrix4<f32>>
+    {
+        let mut motion = vec![];
+
+        if shutter_close <= shutter_open
+        {
+            return motion;
+        }
+
+        let frame_duration = 1000.0 / self.fps as f64;
+        let frame_timestamp = (frame_duration * frame as f64).floor();
+
+        for step in 0..=MOTION_BLUR_STEPS
+        {
+            let shutter_time = helper::interpolate(shutter_open, shutter_close, step as f32 / MOTION_BLUR_STEPS as f32);
+            let timestamp = frame_timestamp + shutter_time as f64 * frame_duration;
+
+            let trans = self.get_trans_for_time(timestamp, object_name);
+
+            if trans.is_none()
+            {
+                return vec![];
+            }
+
+            motion.push(trans.unwrap());
+        }
+
+        //static while the shutter is open
+        if motion.iter().all(|trans| (trans - motion[0]).amax() < 0.000001)
+        {
+            return vec![];
+        }
+
+        motion
+    }
+
 }
@@ -0,0 +1,1396 @@
+use std::f32::consts::PI;
+
+use nalgebra::{Point3, Vector3};
+use parry3d::query::Ray;
+
+use crate::bsdf::Bsdf;
+use crate::helper::{power_heuristic, max_component, onb, cosine_sample_hemisphere};
+use crate::sampler::{sample_1d, sample_2d};
+use crate::path_tracing::{Scatter, PathLobe, BounceType, BounceCounter};
+use crate::medium::MediumInstance;
+use crate::raytracing::{Raytracing, LightningColorType, RayType, SHADOW_BIAS};
+use crate::scene::{Scene, LightType, AreaShape};
+use crate::spectrum::SpectralSample;
+
+const CONNECTION_SHADOW_EPSILON: f32 = 0.001;
+
+/*
+some resources:
+
+bidirectional path tracing:
+https://www.pbr-book.org/3ed-2018/Light_Transport_III_Bidirectional_Methods/Bidirectional_Path_Tracing
+https://graphics.stanford.edu/papers/veach_thesis/ (chapter 10)
+
+notes:
+ - every pixel is rendered on its own --> light tracing (t = 1) is not used and not part of the MIS weights
+ - participating media are only attenuating the subpaths (use the path tracer for scattering media)
+ - the environment is sampled from the camera subpath only (like in the path tracer)
+ - point and spot lights are falling off with the squared distance (the linear falloff of the other integrators has no light subpath equivalent)
+ - the light subpath is importance transport --> the bsdf of its vertices is corrected for shading normals (Veach, chapter 5.3)
+ - light linking is applied to the direct light of a vertex (s = 0, s = 1 and the first vertex of the light subpath), the segments of the light subpath are indirect rays for the ray visibility
+*/
+
+// ******************** LightRef ********************
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LightRef
+{
+    Light(usize), //index of scene.lights
+    Emitter { item_id: u32, face_id: u32 }
+}
+
+// ******************** VertexType ********************
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VertexType
+{
+    Camera,
+    Light,
+    Surface
+}
+
+// ******************** TransportMode ********************
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransportMode
+{
+    Radiance, //camera subpath
+    Importance //light subpath
+}
+
+// ******************** PathVertex ********************
+pub struct PathVertex
+{
+    pub vertex_type: VertexType,
+
+    pub point: Point3<f32>,
+    pub normal: Vector3<f32>, //geometric normal (zero for point like lights and the camera)
+    pub shading_normal: Vector3<f32>, //facing wo
+    pub wo: Vector3<f32>, //direction to the previous vertex
+    pub mode: TransportMode,
+
+    pub throughput: Vector3<f32>,
+    pub pdf_fwd: f32, //area measure
+    pub pdf_rev: f32, //area measure
+    pub delta: bool,
+
+    pub bsdf: Option<Bsdf>,
+    pub surface_probability: f32, //probability of the (non specular) surface lobe
+    pub ambient_occlusion: f32,
+    pub connectible: bool,
+
+    pub emission: Vector3<f32>,
+    pub light: Option<LightRef>,
+    pub item_id: u32,
+
+    pub medium: Option<MediumInstance> //medium on the side of wo
+}
+
+impl PathVertex
+{
+    pub fn new(vertex_type: VertexType, point: Point3<f32>, throughput: Vector3<f32>) -> PathVertex
+    {
+        PathVertex
+        {
+            vertex_type: vertex_type,
+
+            point: point,
+            normal: Vector3::<f32>::zeros(),
+            shading_normal: Vector3::<f32>::zeros(),
+            wo: Vector3::<f32>::zeros(),
+            mode: TransportMode::Radiance,
+
+            throughput: throughput,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+
+            bsdf: None,
+            surface_probability: 0.0,
+            ambient_occlusion: 1.0,
+            connectible: false,
+
+            emission: Vector3::<f32>::zeros(),
+            light: None,
+            item_id: 0,
+
+            medium: None
+        }
+    }
+
+    // f(wo, wi) of the non specular part
+    pub fn eval(&self, wi: Vector3<f32>) -> Vector3<f32>
+    {
+        if let Some(bsdf) = &self.bsdf
+        {
+            return bsdf.eval(self.shading_normal, self.wo, wi) * (self.surface_probability * self.ambient_occlusion);
+        }
+
+        Vector3::<f32>::zeros()
+    }
+
+    // non symmetric scattering caused by shading normals (importance transport only)
+    pub fn get_shading_correction(&self, wi: &Vector3<f32>) -> f32
+    {
+        if self.mode != TransportMode::Importance || self.vertex_type != VertexType::Surface
+        {
+            return 1.0;
+        }
+
+        let denom = self.wo.dot(&self.normal).abs() * wi.dot(&self.shading_normal).abs();
+        if denom <= 0.0
+        {
+            return 0.0;
+        }
+
+        (self.wo.dot(&self.shading_normal).abs() * wi.dot(&self.normal).abs()) / denom
+    }
+
+    // cosine for the conversion of solid angle densities
+    pub fn get_cos(&self, dir: &Vector3<f32>) -> f32
+    {
+        if self.normal.magnitude_squared() <= 0.0
+        {
+            return 1.0;
+        }
+
+        self.normal.dot(dir).abs()
+    }
+
+    // cosine for the geometry term
+    pub fn get_shading_cos(&self, dir: &Vector3<f32>) -> f32
+    {
+        match self.vertex_type
+        {
+            VertexType::Surface => self.shading_normal.dot(dir).abs(),
+            _ => self.get_cos(dir)
+        }
+    }
+
+    pub fn convert_density(&self, pdf: f32, next: &PathVertex) -> f32
+    {
+        let to_next = next.point - self.point;
+        let dist_sq = to_next.magnitude_squared();
+
+        if dist_sq <= 0.0
+        {
+            return 0.0;
+        }
+
+        pdf / dist_sq * next.get_cos(&(to_next / dist_sq.sqrt()))
+    }
+}
+
+// ******************** LightDistribution ********************
+// lights chosen by their emitted power -- the last entry are the emissive shapes
+pub struct LightDistribution
+{
+    weights: Vec<f32>,
+    total_weight: f32
+}
+
+impl LightDistribution
+{
+    pub fn new() -> LightDistribution
+    {
+        LightDistribution
+        {
+            weights: vec![0.0],
+            total_weight: 0.0
+        }
+    }
+
+    pub fn emitter_index(&self) -> usize
+    {
+        self.weights.len() - 1
+    }
+
+    pub fn pdf(&self, index: usize) -> f32
+    {
+        if self.total_weight <= 0.0
+        {
+            return 0.0;
+        }
+
+        self.weights[index] / self.total_weight
+    }
+
+    pub fn get_light_ref_pdf(&self, light_ref: &LightRef) -> f32
+    {
+        match light_ref
+        {
+            LightRef::Light(index) => self.pdf(*index),
+            LightRef::Emitter { .. } => self.pdf(self.emitter_index())
+        }
+    }
+
+    pub fn sample(&self, u: f32) -> Option<(usize, f32)>
+    {
+        if self.total_weight <= 0.0
+        {
+            return None;
+        }
+
+        let target = u * self.total_weight;
+        let mut sum = 0.0;
+
+        for (index, weight) in self.weights.iter().enumerate()
+        {
+            sum += weight;
+
+            if *weight > 0.0 && sum >= target
+            {
+                return Some((index, self.pdf(index)));
+            }
+        }
+
+        // rounding: last light with a weight
+        let index = self.weights.iter().rposition(|weight| *weight > 0.0)?;
+        Some((index, self.pdf(index)))
+    }
+}
+
+// ******************** LightConnection ********************
+pub struct LightConnection
+{
+    pub vertex: PathVertex,
+    pub dir: Vector3<f32>,
+    pub dist: f32,
+    pub radiance: Vector3<f32>,
+    pub pdf: f32 //solid angle (including the light selection)
+}
+
+// ******************** SubpathInfo ********************
+// AOVs and environment lighting of the camera subpath
+pub struct SubpathInfo
+{
+    pub environment: Vector3<f32>,
+
+    pub depth: f32,
+    pub normal: Vector3<f32>,
+    pub object_id: u32,
+    pub albedo: Vector3<f32>
+}
+
+impl Raytracing
+{
+    pub fn get_color_depth_normal_id_bidirectional(&self, scene: &Scene, ray: Ray, wavelength: &SpectralSample) -> (Vector3<f32>, f32, Vector3<f32>, u32, Vector3<f32>)
+    {
+        let distribution = &self.light_distribution;
+
+        let max_depth = self.config.max_recursion as usize;
+
+        let mut info = SubpathInfo
+        {
+            environment: Vector3::<f32>::zeros(),
+            depth: 0.0,
+            normal: Vector3::<f32>::zeros(),
+            object_id: 0,
+            albedo: Vector3::<f32>::zeros()
+        };
+
+        let camera_vertices = self.generate_camera_subpath(scene, ray, max_depth + 2, wavelength, &mut info);
+        let light_vertices = self.generate_light_subpath(scene, distribution, max_depth + 1, wavelength);
+
+        let mut radiance = info.environment;
+
+        for t in 2..=camera_vertices.len()
+        {
+            for s in 0..=light_vertices.len()
+            {
+                if s + t - 2 > max_depth
+                {
+                    continue;
+                }
+
+                // paths with more than one scattering vertex are indirect
+                radiance += self.clamp_indirect(self.connect_subpaths(scene, distribution, &light_vertices, &camera_vertices, s, t), s + t > 3);
+            }
+        }
+
+        (radiance, info.depth, info.normal, info.object_id, info.albedo)
+    }
+
+    pub fn get_light_distribution(&self, scene: &Scene) -> LightDistribution
+    {
+        let mut weights = vec![];
+        let mut total_weight = 0.0;
+
+        let has_bounds = !scene.items.is_empty() && scene.b_box.mins.x <= scene.b_box.maxs.x;
+
+        for light in &scene.lights
+        {
+            let mut weight = 0.0;
+            if light.is_active() && (light.light_type != LightType::Directional || has_bounds)
+            {
+                weight = max_component(self.get_light_flux(scene, light));
+            }
+
+            total_weight += weight;
+            weights.push(weight);
+        }
+
+        // emissive shapes (two sided)
+        let emitter_weight = scene.emitters.get_total_weight() * 2.0 * PI;
+        total_weight += emitter_weight;
+        weights.push(emitter_weight);
+
+        LightDistribution
+        {
+            weights: weights,
+            total_weight: total_weight
+        }
+    }
+
+    fn get_scene_radius(&self, scene: &Scene) -> f32
+    {
+        scene.b_box.half_extents().magnitude()
+    }
+
+    fn get_emission(&self, scene: &Scene, item_index: usize, point: &Point3<f32>, face_id: u32) -> Vector3<f32>
+    {
+        let item = &scene.items[item_index];
+        let material = item.get_material().read().unwrap();
+
+        let mut uv = None;
+        if material.has_any_texture()
+        {
+            uv = Some(item.get_uv(*point, face_id));
+        }
+
+        self.get_item_color(&material, &uv, LightningColorType::Ambient).xyz()
+    }
+
+    // ********** subpaths **********
+
+    pub fn generate_camera_subpath(&self, scene: &Scene, ray: Ray, max_vertices: usize, wavelength: &SpectralSample, info: &mut SubpathInfo) -> Vec<PathVertex>
+    {
+        let mut vertices = vec![];
+
+        let mut camera = PathVertex::new(VertexType::Camera, ray.origin, Vector3::<f32>::new(1.0, 1.0, 1.0));
+        camera.medium = self.get_global_medium(scene);
+        camera.pdf_fwd = 1.0;
+        vertices.push(camera);
+
+        // the pdf of the camera is not needed (no light tracing strategies)
+        self.random_walk(scene, ray, Vector3::<f32>::new(1.0, 1.0, 1.0), 1.0, max_vertices, TransportMode::Radiance, wavelength, &mut vertices, Some(info));
+
+        vertices
+    }
+
+    pub fn generate_light_subpath(&self, scene: &Scene, distribution: &LightDistribution, max_vertices: usize, wavelength: &SpectralSample) -> Vec<PathVertex>
+    {
+        let mut vertices = vec![];
+
+        let light_choice = distribution.sample(sample_1d());
+        if light_choice.is_none() || max_vertices == 0
+        {
+            return vertices;
+        }
+
+        let (light_index, light_pdf) = light_choice.unwrap();
+
+        let origin;
+        let mut normal = Vector3::<f32>::zeros();
+        let dir;
+        let pdf_pos;
+        let pdf_dir;
+        let emission;
+        let light_ref;
+        let mut directional = false;
+
+        if light_index == distribution.emitter_index()
+        {
+            let (u1, u2) = sample_2d();
+            let emitter_sample = scene.emitters.sample(sample_1d(), u1, u2);
+            if emitter_sample.is_none()
+            {
+                return vertices;
+            }
+
+            let emitter_sample = emitter_sample.unwrap();
+
+            // two sided emission (spheres are emitting outwards only)
+            let mut side = emitter_sample.normal;
+            let mut side_probability = 1.0;
+            if emitter_sample.two_sided
+            {
+                side_probability = 0.5;
+                if sample_1d() < 0.5
+                {
+                    side = -side;
+                }
+            }
+
+            let (u1, u2) = sample_2d();
+            dir = cosine_sample_hemisphere(side, u1, u2);
+
+            origin = emitter_sample.point + (side * SHADOW_BIAS);
+            normal = emitter_sample.normal;
+            pdf_pos = emitter_sample.pdf;
+            pdf_dir = side_probability * dir.dot(&side).max(0.0) / PI;
+            emission = self.get_emission(scene, emitter_sample.item_index, &emitter_sample.point, emitter_sample.face_id);
+            light_ref = LightRef::Emitter { item_id: scene.items[emitter_sample.item_index].get_basic().id, face_id: emitter_sample.face_id };
+        }
+        else
+        {
+            let light = &scene.lights[light_index];
+            light_ref = LightRef::Light(light_index);
+
+            // intensity like in the path tracer (a white lambertian surface facing the light reflects the light intensity)
+            let intensity = light.color * light.intensity * PI;
+
+            match light.light_type
+            {
+                LightType::Point =>
+                {
+                    let (u1, u2) = sample_2d();
+                    let z: f32 = 1.0 - 2.0 * u1;
+                    let r = (1.0 - z * z).max(0.0).sqrt();
+                    let phi = 2.0 * PI * u2;
+
+                    origin = light.pos;
+                    dir = Vector3::<f32>::new(r * phi.cos(), r * phi.sin(), z);
+                    pdf_pos = 1.0;
+                    pdf_dir = 1.0 / (4.0 * PI);
+                    emission = intensity / (4.0 * PI);
+                },
+                LightType::Spot =>
+                {
+                    let light_dir = light.dir.normalize();
+                    let (tangent, bitangent) = onb(light_dir);
+
+                    let cos_max = light.max_angle.cos();
+                    let (u1, u2) = sample_2d();
+                    let z = 1.0 - u1 * (1.0 - cos_max);
+                    let r = (1.0 - z * z).max(0.0).sqrt();
+                    let phi = 2.0 * PI * u2;
+
+                    origin = light.pos;
+                    dir = tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + light_dir * z;
+                    pdf_pos = 1.0;
+                    pdf_dir = 1.0 / (2.0 * PI * (1.0 - cos_max)).max(std::f32::EPSILON);
+                    emission = intensity / (4.0 * PI);
+                },
+                LightType::Directional =>
+                {
+                    // disk covering the bounding sphere of the scene
+                    let light_dir = light.dir.normalize();
+                    let (tangent, bitangent) = onb(light_dir);
+
+                    let radius = self.get_scene_radius(scene);
+                    let (u1, u2) = sample_2d();
+                    let r = radius * u1.sqrt();
+                    let phi = 2.0 * PI * u2;
+
+                    origin = scene.b_box.center() + (tangent * (r * phi.cos())) + (bitangent * (r * phi.sin())) - (light_dir * (radius * 2.0));
+                    dir = light_dir;
+                    pdf_pos = 1.0 / (PI * radius * radius);
+                    pdf_dir = 1.0;
+                    emission = intensity;
+                    directional = true;
+                },
+                LightType::Area =>
+                {
+                    // spheres are sampled on the hemisphere facing a random direction --> uniform over the whole sphere
+                    let mut reference_point = light.pos;
+                    if light.area_shape == AreaShape::Sphere
+                    {
+                        let (u1, u2) = sample_2d();
+                        let z: f32 = 1.0 - 2.0 * u1;
+                        let r = (1.0 - z * z).max(0.0).sqrt();
+                        let phi = 2.0 * PI * u2;
+
+                        reference_point += Vector3::<f32>::new(r * phi.cos(), r * phi.sin(), z);
+                    }
+
+                    let (u1, u2) = sample_2d();
+                    let (point, light_normal, _) = light.sample_area(&reference_point, u1, u2);
+
+                    let (u1, u2) = sample_2d();
+                    dir = cosine_sample_hemisphere(light_normal, u1, u2);
+
+                    origin = point + (light_normal * SHADOW_BIAS);
+                    normal = light_normal;
+                    pdf_pos = 1.0 / light.get_area();
+                    pdf_dir = dir.dot(&light_normal).max(0.0) / PI;
+                    emission = light.get_area_radiance();
+                }
+            }
+        }
+
+        if pdf_pos <= 0.0 || pdf_dir <= 0.0 || max_component(emission) <= 0.0
+        {
+            return vertices;
+        }
+
+        let mut light_vertex = PathVertex::new(VertexType::Light, origin, emission);
+        light_vertex.normal = normal;
+        light_vertex.emission = emission;
+        light_vertex.light = Some(light_ref);
+        light_vertex.medium = self.get_global_medium(scene);
+        if !directional
+        {
+            light_vertex.pdf_fwd = pdf_pos * light_pdf;
+        }
+        vertices.push(light_vertex);
+
+        let mut cos = 1.0;
+        if normal.magnitude_squared() > 0.0
+        {
+            cos = normal.dot(&dir).abs();
+        }
+
+        let throughput = emission * (cos / (light_pdf * pdf_pos * pdf_dir));
+
+        self.random_walk(scene, Ray::new(origin, dir), throughput, pdf_dir, max_vertices, TransportMode::Importance, wavelength, &mut vertices, None);
+
+        // the first vertex of directional lights is sampled by area (perpendicular to the light direction)
+        if directional && vertices.len() > 1
+        {
+            vertices[1].pdf_fwd = pdf_pos * vertices[1].get_cos(&dir);
+        }
+
+        // light linking: the light subpath ends at an object which is not illuminated by the light
+        if vertices.len() > 1 && !self.is_illuminated_by(scene, &Some(light_ref), vertices[1].item_id)
+        {
+            vertices.truncate(1);
+        }
+
+        vertices
+    }
+
+    fn random_walk(&self, scene: &Scene, ray: Ray, throughput: Vector3<f32>, pdf_dir: f32, max_vertices: usize, mode: TransportMode, wavelength: &SpectralSample, vertices: &mut Vec<PathVertex>, mut info: Option<&mut SubpathInfo>)
+    {
+        let mut r = ray;
+        r.dir = r.dir.normalize();
+
+        let mut throughput = throughput;
+        let mut pdf_fwd_dir = pdf_dir;
+
+        let mut medium = self.get_global_medium(scene);
+
+        // environment MIS (camera subpath)
+        let mut specular_bounce = true;
+        let mut last_bsdf_pdf = 0.0;
+
+        let mut aov_pending = true;
+
+        // the bounce limits per path type are applied per subpath
+        let mut bounces = BounceCounter::new();
+
+        // ray type of the current segment (per object ray visibility)
+        let mut ray_type = RayType::Camera;
+        if mode == TransportMode::Importance
+        {
+            ray_type = RayType::Indirect;
+        }
+
+        while vertices.len() < max_vertices
+        {
+            // reflection only objects are visible for light paths
+            let mut depth = vertices.len() as u16;
+            if mode == TransportMode::Importance
+            {
+                depth += 1;
+            }
+
+            let intersection = self.trace(&scene, &r, false, ray_type, depth);
+
+            let mut max_dist = std::f32::MAX;
+            if let Some(intersection) = &intersection
+            {
+                max_dist = intersection.0;
+            }
+
+            // ********** area lights (camera subpath) **********
+            if mode == TransportMode::Radiance
+            {
+                if let Some((light_index, dist, light_normal)) = self.intersect_area_lights(scene, &r, max_dist)
+                {
+                    if let Some(current_medium) = &medium
+                    {
+                        throughput = throughput.component_mul(&current_medium.transmittance(&r.origin, &r.dir, dist, sample_1d()));
+                    }
+
+                    let light = &scene.lights[light_index];
+
+                    let mut vertex = PathVertex::new(VertexType::Light, r.origin + (r.dir * dist), throughput);
+                    vertex.normal = light_normal;
+                    vertex.wo = -r.dir;
+                    vertex.light = Some(LightRef::Light(light_index));
+                    vertex.medium = medium.clone();
+
+                    if light_normal.dot(&(-r.dir)) > 0.0
+                    {
+                        vertex.emission = light.get_area_radiance();
+                    }
+
+                    vertex.pdf_fwd = vertices.last().unwrap().convert_density(pdf_fwd_dir, &vertex);
+
+                    vertices.push(vertex);
+                    break;
+                }
+            }
+
+            if intersection.is_none()
+            {
+                // ********** environment **********
+                if let Some(info) = info.as_mut()
+                {
+                    if scene.environment.is_active()
+                    {
+                        let mut weight = 1.0;
+
+                        if !specular_bounce
+                        {
+                            weight = power_heuristic(last_bsdf_pdf, scene.environment.pdf(&r.dir));
+                        }
+
+                        info.environment += self.clamp_indirect(throughput.component_mul(&scene.environment.eval(&r.dir)) * weight, vertices.len() > 2);
+                    }
+                }
+
+                break;
+            }
+
+            let intersection = intersection.unwrap();
+
+            let hit_dist = intersection.0;
+            let normal = intersection.1;
+            let item = intersection.2;
+            let face_id = intersection.3;
+
+            if let Some(current_medium) = &medium
+            {
+                throughput = throughput.component_mul(&current_medium.transmittance(&r.origin, &r.dir, hit_dist, sample_1d()));
+            }
+
+            let material = item.get_material().read().unwrap();
+
+            let mut surface = self.get_surface_interaction(&r, hit_dist, normal, item, face_id, &material);
+            surface.refraction_index = material.get_refraction_index(wavelength);
+
+            //AOVs (fully transparent surfaces are passing them to the next hit)
+            if let Some(info) = info.as_mut()
+            {
+                if aov_pending
+                {
+                    info.depth = hit_dist;
+                    info.normal = normal;
+                    info.object_id = item.get_basic().id;
+                    info.albedo = surface.base_color;
+
+                    aov_pending = surface.alpha <= 0.0;
+                }
+            }
+
+            let wo = -r.dir;
+
+            let mut n = surface.surface_normal;
+            if n.dot(&wo) < 0.0
+            {
+                n = -n;
+            }
+
+            let mut vertex = PathVertex::new(VertexType::Surface, surface.hit_point, throughput);
+            vertex.normal = normal;
+            vertex.shading_normal = n;
+            vertex.wo = wo;
+            vertex.mode = mode;
+            vertex.surface_probability = surface.alpha.clamp(0.0, 1.0) * (1.0 - surface.reflectivity.clamp(0.0, 1.0));
+            vertex.ambient_occlusion = surface.ambient_occlusion;
+            vertex.connectible = vertex.surface_probability > 0.0 && material.receive_shadow;
+            vertex.item_id = item.get_basic().id;
+            vertex.medium = medium.clone();
+            vertex.pdf_fwd = vertices.last().unwrap().convert_density(pdf_fwd_dir, &vertex);
+
+            if surface.emission.magnitude_squared() > 0.0
+            {
+                vertex.emission = surface.emission;
+
+                if scene.emitters.get_pdf(vertex.item_id, face_id) > 0.0
+                {
+                    vertex.light = Some(LightRef::Emitter { item_id: vertex.item_id, face_id: face_id });
+                }
+            }
+
+            vertex.bsdf = Some(surface.get_bsdf());
+
+            vertices.push(vertex);
+
+            if vertices.len() >= max_vertices
+            {
+                break;
+            }
+
+            // ********** choose lobe **********
+            let lobe = self.choose_lobe(&surface);
+
+            let pdf_rev_dir;
+            let mut delta = true;
+
+            match lobe
+            {
+                PathLobe::Surface =>
+                {
+                    let vertex = vertices.last().unwrap();
+                    let bsdf = vertex.bsdf.as_ref().unwrap();
+
+                    // environment light sampling (camera subpath)
+                    if let Some(info) = info.as_mut()
+                    {
+                        if scene.environment.is_active() && material.receive_shadow
+                        {
+                            let scatter = Scatter::Surface { bsdf: bsdf, n: n, wo: wo };
+                            let environment = self.sample_environment(scene, &scatter, &surface.hit_point, &medium, depth + 1, true);
+                            info.environment += self.clamp_indirect(throughput.component_mul(&environment) * surface.ambient_occlusion, vertices.len() > 2);
+                        }
+                    }
+
+                    let (u1, u2) = sample_2d();
+                    let bsdf_sample = bsdf.sample(n, wo, sample_1d(), u1, u2);
+
+                    if bsdf_sample.is_none()
+                    {
+                        break;
+                    }
+
+                    let bsdf_sample = bsdf_sample.unwrap();
+
+                    let mut bounce_type = BounceType::Diffuse;
+                    ray_type = RayType::Indirect;
+                    if bsdf_sample.glossy
+                    {
+                        bounce_type = BounceType::Glossy;
+                        ray_type = RayType::Reflection;
+                    }
+
+                    if !bounces.add(bounce_type, &self.config)
+                    {
+                        break;
+                    }
+
+                    pdf_fwd_dir = vertex.surface_probability * bsdf_sample.pdf;
+                    pdf_rev_dir = vertex.surface_probability * bsdf.pdf(n, bsdf_sample.dir, wo);
+                    delta = false;
+
+                    throughput = throughput.component_mul(&bsdf_sample.weight) * surface.ambient_occlusion;
+                    last_bsdf_pdf = bsdf_sample.pdf;
+                    specular_bounce = false;
+
+                    r = Ray::new(surface.hit_point + (n * SHADOW_BIAS), bsdf_sample.dir);
+                },
+                PathLobe::Reflection =>
+                {
+                    if !bounces.add(BounceType::Glossy, &self.config)
+                    {
+                        break;
+                    }
+
+                    let mut reflection_ray = self.create_reflection(n, r.dir, surface.hit_point);
+
+                    if surface.roughness > 0.0
+                    {
+                        reflection_ray.dir = self.jitter(reflection_ray.dir, surface.roughness);
+
+                        //do not reflect into the surface
+                        if reflection_ray.dir.dot(&n) <= 0.0
+                        {
+                            break;
+                        }
+                    }
+
+                    pdf_fwd_dir = 0.0;
+                    pdf_rev_dir = 0.0;
+                    specular_bounce = true;
+                    ray_type = RayType::Reflection;
+
+                    r = reflection_ray;
+                },
+                PathLobe::Transmission =>
+                {
+                    let kr = self.fresnel_thin_film(r.dir, surface.surface_normal, surface.refraction_index, &surface.thin_film);
+
+                    let transmission_ray = self.create_transmission(surface.surface_normal, r.dir, surface.hit_point, surface.refraction_index);
+
+                    // total internal reflection or fresnel based reflection (the weight is only colored for thin films)
+                    let mut reflect = true;
+                    if transmission_ray.is_some()
+                    {
+                        let (fresnel_reflection, fresnel_weight) = self.sample_fresnel(kr);
+                        reflect = fresnel_reflection;
+                        throughput = throughput.component_mul(&fresnel_weight);
+                    }
+
+                    let mut bounce_type = BounceType::Transmission;
+                    if reflect
+                    {
+                        bounce_type = BounceType::Glossy;
+                    }
+
+                    if !bounces.add(bounce_type, &self.config)
+                    {
+                        break;
+                    }
+
+                    if reflect
+                    {
+                        r = self.create_reflection(n, r.dir, surface.hit_point);
+                        ray_type = RayType::Reflection;
+                    }
+                    else
+                    {
+                        r = transmission_ray.unwrap();
+                        ray_type = RayType::Refraction;
+
+                        medium = self.get_medium_after_transmission(scene, &medium, item, normal, r.dir);
+                    }
+
+                    pdf_fwd_dir = 0.0;
+                    pdf_rev_dir = 0.0;
+                    specular_bounce = true;
+                }
+            }
+
+            r.dir = r.dir.normalize();
+
+            // shading normal correction of the light subpath
+            throughput *= vertices.last().unwrap().get_shading_correction(&r.dir);
+
+            // reverse density of the previous vertex
+            let len = vertices.len();
+            vertices[len - 1].delta = delta;
+
+            let pdf_rev = vertices[len - 1].convert_density(pdf_rev_dir, &vertices[len - 2]);
+            vertices[len - 2].pdf_rev = pdf_rev;
+
+            if max_component(throughput) <= 0.0
+            {
+                break;
+            }
+        }
+    }
+
+    // light linking of the light at a vertex (emissive shapes are illuminating all objects)
+    fn is_illuminated_by(&self, scene: &Scene, light_ref: &Option<LightRef>, item_id: u32) -> bool
+    {
+        if let Some(LightRef::Light(light_index)) = light_ref
+        {
+            let light = &scene.lights[*light_index];
+            if light.is_linked()
+            {
+                if let Some(item) = scene.get_obj_by_id(item_id)
+                {
+                    return light.illuminates(item.get_basic());
+                }
+            }
+        }
+
+        true
+    }
+
+    // nearest (front facing) area light in front of max_dist
+    pub fn intersect_area_lights(&self, scene: &Scene, ray: &Ray, max_dist: f32) -> Option<(usize, f32, Vector3<f32>)>
+    {
+        let mut nearest: Option<(usize, f32, Vector3<f32>)> = None;
+
+        for (light_index, light) in scene.lights.iter().enumerate()
+        {
+            if !light.is_active() || light.light_type != LightType::Area
+            {
+                continue;
+            }
+
+            if let Some((dist, light_normal)) = light.intersect_area(ray)
+            {
+                if dist < max_dist && (nearest.is_none() || dist < nearest.unwrap().1)
+                {
+                    nearest = Some((light_index, dist, light_normal));
+                }
+            }
+        }
+
+        nearest
+    }
+
+    // ********** densities **********
+
+    // density (area measure at v) of emitting towards v
+    fn pdf_light(&self, scene: &Scene, light_vertex: &PathVertex, v: &PathVertex) -> f32
+    {
+        let to_v = v.point - light_vertex.point;
+        let dist_sq = to_v.magnitude_squared();
+
+        if dist_sq <= 0.0 || light_vertex.light.is_none()
+        {
+            return 0.0;
+        }
+
+        let dir = to_v / dist_sq.sqrt();
+
+        let pdf_dir = match light_vertex.light.unwrap()
+        {
+            LightRef::Light(index) =>
+            {
+                let light = &scene.lights[index];
+
+                match light.light_type
+                {
+                    LightType::Point => 1.0 / (4.0 * PI),
+                    LightType::Spot =>
+                    {
+                        let cos_max = light.max_angle.cos();
+                        if dir.dot(&light.dir.normalize()) < cos_max
+                        {
+                            return 0.0;
+                        }
+
+                        1.0 / (2.0 * PI * (1.0 - cos_max)).max(std::f32::EPSILON)
+                    },
+                    LightType::Directional =>
+                    {
+                        let radius = self.get_scene_radius(scene);
+                        return v.get_cos(&light.dir.normalize()) / (PI * radius * radius);
+                    },
+                    LightType::Area => light_vertex.normal.dot(&dir).max(0.0) / PI
+                }
+            },
+            LightRef::Emitter { item_id, face_id } =>
+            {
+                if scene.emitters.is_two_sided(item_id, face_id)
+                {
+                    light_vertex.normal.dot(&dir).abs() / (2.0 * PI)
+                }
+                else
+                {
+                    light_vertex.normal.dot(&dir).max(0.0) / PI
+                }
+            }
+        };
+
+        pdf_dir / dist_sq * v.get_cos(&dir)
+    }
+
+    // density (area measure including the light selection) of starting a light subpath at the vertex
+    fn pdf_light_origin(&self, scene: &Scene, distribution: &LightDistribution, light_vertex: &PathVertex) -> f32
+    {
+        if light_vertex.light.is_none()
+        {
+            return 0.0;
+        }
+
+        let light_ref = light_vertex.light.unwrap();
+        let light_pdf = distribution.get_light_ref_pdf(&light_ref);
+
+        match light_ref
+        {
+            LightRef::Light(index) =>
+            {
+                let light = &scene.lights[index];
+
+                // delta lights can not be hit
+                if light.light_type != LightType::Area
+                {
+                    return 0.0;
+                }
+
+                light_pdf / light.get_area()
+            },
+            LightRef::Emitter { item_id, face_id } => light_pdf * scene.emitters.get_pdf(item_id, face_id)
+        }
+    }
+
+    // density (area measure at next) of sampling next from vertex (coming from prev)
+    fn pdf_vertex(&self, scene: &Scene, vertex: &PathVertex, prev: Option<&PathVertex>, next: &PathVertex) -> f32
+    {
+        match vertex.vertex_type
+        {
+            VertexType::Light => self.pdf_light(scene, vertex, next),
+            VertexType::Camera => 0.0,
+            VertexType::Surface =>
+            {
+                if vertex.bsdf.is_none() || prev.is_none()
+                {
+                    return 0.0;
+                }
+
+                let wp = (prev.unwrap().point - vertex.point).normalize();
+                let wn = (next.point - vertex.point).normalize();
+
+                let pdf = vertex.surface_probability * vertex.bsdf.as_ref().unwrap().pdf(vertex.shading_normal, wp, wn);
+
+                vertex.convert_density(pdf, next)
+            }
+        }
+    }
+
+    fn is_delta_light(&self, scene: &Scene, light_vertex: &PathVertex) -> bool
+    {
+        match light_vertex.light
+        {
+            Some(LightRef::Light(index)) => scene.lights[index].light_type != LightType::Area,
+            _ => false
+        }
+    }
+
+    // ********** connection **********
+
+    // samples a point on a light for the connection (s = 1)
+    fn sample_light_connection(&self, scene: &Scene, distribution: &LightDistribution, point: &Point3<f32>) -> Option<LightConnection>
+    {
+        let (light_index, light_pdf) = distribution.sample(sample_1d())?;
+
+        let mut vertex;
+        let dir;
+        let dist;
+        let radiance;
+        let pdf;
+
+        if light_index == distribution.emitter_index()
+        {
+            let (u1, u2) = sample_2d();
+            let emitter_sample = scene.emitters.sample(sample_1d(), u1, u2)?;
+
+            let to_light = emitter_sample.point - point;
+            dist = to_light.norm();
+
+            if dist <= SHADOW_BIAS
+            {
+                return None;
+            }
+
+            dir = to_light / dist;
+
+            let cos_light = emitter_sample.normal.dot(&dir).abs();
+            if cos_light <= 0.0
+            {
+                return None;
+            }
+
+            radiance = self.get_emission(scene, emitter_sample.item_index, &emitter_sample.point, emitter_sample.face_id);
+            pdf = light_pdf * emitter_sample.pdf * dist * dist / cos_light;
+
+            vertex = PathVertex::new(VertexType::Light, emitter_sample.point, radiance);
+            vertex.normal = emitter_sample.normal;
+            vertex.light = Some(LightRef::Emitter { item_id: scene.items[emitter_sample.item_index].get_basic().id, face_id: emitter_sample.face_id });
+        }
+        else
+        {
+            let light = &scene.lights[light_index];
+
+            if light.light_type == LightType::Area
+            {
+                let (u1, u2) = sample_2d();
+                let (light_point, light_normal, area_pdf) = light.sample_area(point, u1, u2);
+
+                let to_light = light_point - point;
+                dist = to_light.norm();
+
+                if dist <= SHADOW_BIAS
+                {
+                    return None;
+                }
+
+                dir = to_light / dist;
+
+                let cos_light = light_normal.dot(&(-dir));
+                if cos_light <= 0.0
+                {
+                    return None;
+                }
+
+                radiance = light.get_area_radiance();
+                pdf = light_pdf * area_pdf * dist * dist / cos_light;
+
+                vertex = PathVertex::new(VertexType::Light, light_point, radiance);
+                vertex.normal = light_normal;
+            }
+            else
+            {
+                let (direction_to_light, intensity, light_dist) = light.get_direction_and_intensity(point);
+
+                if intensity <= 0.0
+                {
+                    return None;
+                }
+
+                // squared distance falloff (like the light subpath)
+                let mut falloff = 1.0;
+                if light.light_type != LightType::Directional
+                {
+                    falloff = 1.0 / light_dist.max(SHADOW_BIAS);
+                }
+
+                dir = direction_to_light;
+                dist = light_dist;
+                radiance = light.color * (intensity * PI * falloff);
+                pdf = light_pdf;
+
+                // only the direction matters for directional lights
+                let mut light_point = light.pos;
+                if light.light_type == LightType::Directional
+                {
+                    light_point = point + dir;
+                }
+
+                vertex = PathVertex::new(VertexType::Light, light_point, radiance);
+            }
+
+            vertex.light = Some(LightRef::Light(light_index));
+        }
+
+        vertex.emission = radiance;
+        vertex.pdf_fwd = self.pdf_light_origin(scene, distribution, &vertex);
+
+        Some(LightConnection
+        {
+            vertex: vertex,
+            dir: dir,
+            dist: dist,
+            radiance: radiance,
+            pdf: pdf
+        })
+    }
+
+    fn get_connection_transmittance(&self, scene: &Scene, vertex: &PathVertex, dir: Vector3<f32>, dist: f32) -> Vector3<f32>
+    {
+        let mut offset = vertex.normal * SHADOW_BIAS;
+        if vertex.normal.dot(&dir) < 0.0
+        {
+            offset = -offset;
+        }
+
+        let mut max_dist = dist;
+        if dist < std::f32::MAX
+        {
+            max_dist = dist * (1.0 - CONNECTION_SHADOW_EPSILON) - SHADOW_BIAS;
+        }
+
+        self.get_shadow_transmittance(scene, vertex.point + offset, dir, max_dist, &vertex.medium, 2)
+    }
+
+    fn connect_subpaths(&self, scene: &Scene, distribution: &LightDistribution, light_vertices: &Vec<PathVertex>, camera_vertices: &Vec<PathVertex>, s: usize, t: usize) -> Vector3<f32>
+    {
+        let pt = &camera_vertices[t - 1];
+
+        let mut radiance = Vector3::<f32>::zeros();
+        let mut sampled = None;
+
+        if s == 0
+        {
+            // camera subpath hits a light
+            if pt.vertex_type == VertexType::Camera
+            {
+                return radiance;
+            }
+
+            // light linking (the object before the light hit)
+            if t >= 2 && !self.is_illuminated_by(scene, &pt.light, camera_vertices[t - 2].item_id)
+            {
+                return radiance;
+            }
+
+            radiance = pt.throughput.component_mul(&pt.emission);
+        }
+        else if s == 1
+        {
+            // light sampling
+            if pt.vertex_type != VertexType::Surface || !pt.connectible
+            {
+                return radiance;
+            }
+
+            let connection = self.sample_light_connection(scene, distribution, &pt.point);
+            if connection.is_none()
+            {
+                return radiance;
+            }
+
+            let connection = connection.unwrap();
+
+            // light linking
+            if !self.is_illuminated_by(scene, &connection.vertex.light, pt.item_id)
+            {
+                return radiance;
+            }
+
+            let f = pt.eval(connection.dir) * pt.get_shading_cos(&connection.dir);
+            if max_component(f) <= 0.0 || connection.pdf <= 0.0
+            {
+                return radiance;
+            }
+
+            let transmittance = self.get_connection_transmittance(scene, pt, connection.dir, connection.dist);
+
+            radiance = pt.throughput.component_mul(&f).component_mul(&connection.radiance).component_mul(&transmittance) / connection.pdf;
+
+            sampled = Some(connection.vertex);
+        }
+        else
+        {
+            // connect both subpaths
+            let qs = &light_vertices[s - 1];
+
+            if pt.vertex_type != VertexType::Surface || qs.vertex_type != VertexType::Surface || !pt.connectible || !qs.connectible
+            {
+                return radiance;
+            }
+
+            let to_qs = qs.point - pt.point;
+            let dist = to_qs.norm();
+
+            if dist <= SHADOW_BIAS
+            {
+                return radiance;
+            }
+
+            let dir = to_qs / dist;
+
+            let f = pt.eval(dir).component_mul(&qs.eval(-dir)) * qs.get_shading_correction(&(-dir));
+            if max_component(f) <= 0.0
+            {
+                return radiance;
+            }
+
+            let g = pt.get_shading_cos(&dir) * qs.get_shading_cos(&dir) / (dist * dist);
+
+            let transmittance = self.get_connection_transmittance(scene, pt, dir, dist);
+
+            radiance = pt.throughput.component_mul(&f).component_mul(&qs.throughput).component_mul(&transmittance) * g;
+        }
+
+        if max_component(radiance) <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        radiance * self.get_mis_weight(scene, distribution, light_vertices, camera_vertices, &sampled, s, t)
+    }
+
+    // power heuristic over all strategies creating the same path (based on the ratios of the vertex densities)
+    fn get_mis_weight(&self, scene: &Scene, distribution: &LightDistribution, light_vertices: &Vec<PathVertex>, camera_vertices: &Vec<PathVertex>, sampled: &Option<PathVertex>, s: usize, t: usize) -> f32
+    {
+        let remap = |pdf: f32| -> f32 { if pdf != 0.0 { pdf } else { 1.0 } };
+
+        let pt = &camera_vertices[t - 1];
+        let pt_minus = &camera_vertices[t - 2];
+
+        // light subpath end (the sampled light vertex for s = 1)
+        let mut qs = None;
+        let mut qs_minus = None;
+        if s == 1
+        {
+            qs = sampled.as_ref();
+        }
+        else if s > 1
+        {
+            qs = Some(&light_vertices[s - 1]);
+            qs_minus = Some(&light_vertices[s - 2]);
+        }
+
+        // emission of lights which can not be sampled (for example invisible emitters)
+        if s == 0 && pt.light.is_none()
+        {
+            return 1.0;
+        }
+
+        // reverse densities of the connection
+        let pt_rev;
+        let pt_minus_rev;
+        let mut qs_rev = 0.0;
+        let mut qs_minus_rev = 0.0;
+
+        if let Some(qs) = qs
+        {
+            pt_rev = self.pdf_vertex(scene, qs, qs_minus, pt);
+            pt_minus_rev = self.pdf_vertex(scene, pt, Some(qs), pt_minus);
+            qs_rev = self.pdf_vertex(scene, pt, Some(pt_minus), qs);
+
+            if let Some(qs_minus) = qs_minus
+            {
+                qs_minus_rev = self.pdf_vertex(scene, qs, Some(pt), qs_minus);
+            }
+        }
+        else
+        {
+            pt_rev = self.pdf_light_origin(scene, distribution, pt);
+            pt_minus_rev = self.pdf_light(scene, pt, pt_minus);
+        }
+
+        let mut sum = 0.0;
+
+        // camera subpath (strategies with less camera vertices -- t = 1 is not used)
+        let mut ratio = 1.0;
+        for i in (1..t).rev()
+        {
+            let vertex = &camera_vertices[i];
+
+            let mut pdf_rev = vertex.pdf_rev;
+            let mut delta = vertex.delta;
+            if i == t - 1
+            {
+                pdf_rev = pt_rev;
+                delta = false;
+            }
+            else if i == t - 2
+            {
+                pdf_rev = pt_minus_rev;
+            }
+
+            let prev_delta = camera_vertices[i - 1].delta;
+
+            let r = remap(pdf_rev) / remap(vertex.pdf_fwd);
+            ratio *= r * r;
+
+            if i > 1 && !delta && !prev_delta
+            {
+                sum += ratio;
+            }
+        }
+
+        // light subpath (strategies with less light vertices)
+        let mut ratio = 1.0;
+        for i in (0..s).rev()
+        {
+            let vertex;
+            if s == 1
+            {
+                vertex = qs.unwrap();
+            }
+            else
+            {
+                vertex = &light_vertices[i];
+            }
+
+            let mut pdf_rev = vertex.pdf_rev;
+            let mut delta = vertex.delta;
+            if i == s - 1
+            {
+                pdf_rev = qs_rev;
+                delta = false;
+            }
+            else if i + 2 == s
+            {
+                pdf_rev = qs_minus_rev;
+            }
+
+            let prev_delta;
+            if i > 0
+            {
+                prev_delta = light_vertices[i - 1].delta;
+            }
+            else
+            {
+                prev_delta = self.is_delta_light(scene, vertex);
+            }
+
+            let r = remap(pdf_rev) / remap(vertex.pdf_fwd);
+            ratio *= r * r;
+
+            if !delta && !prev_delta
+            {
+                sum += ratio;
+            }
+        }
+
+        1.0 / (1.0 + sum)
+    }
+}
@@ -0,0 +1,539 @@
+use std::f32::consts::PI;
+
+use nalgebra::Vector3;
+
+use crate::helper::{onb, max_component, interpolate};
+use crate::iridescence::ThinFilm;
+use crate::shape::ShadingModel;
+
+// clamp to prevent a perfectly specular (delta) distribution
+pub const MIN_PBR_ROUGHNESS: f32 = 0.02;
+
+const DIELECTRIC_F0: f32 = 0.04;
+const MIN_SPECULAR_PROBABILITY: f32 = 0.1;
+
+const CLEARCOAT_F0: f32 = 0.04;
+const MIN_CLEARCOAT_PROBABILITY: f32 = 0.1;
+const MAX_CLEARCOAT_PROBABILITY: f32 = 0.9;
+
+const MIN_SHEEN_ROUGHNESS: f32 = 0.07;
+const SHEEN_ALBEDO_SCALING: f32 = 0.25; // approximated directional albedo of the sheen lobe (instead of a lookup table)
+
+/*
+some resources:
+
+microfacet models in general:
+https://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models
+https://learnopengl.com/PBR/Theory
+
+GGX (Trowbridge-Reitz) and smith shadowing:
+https://www.cs.cornell.edu/~srm/publications/EGSR07-btdf.pdf
+
+gltf metallic roughness:
+https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#appendix-b-brdf-implementation
+
+layers (clearcoat, sheen, anisotropy):
+https://github.com/KhronosGroup/glTF/tree/main/extensions/2.0/Khronos/KHR_materials_clearcoat
+https://github.com/KhronosGroup/glTF/tree/main/extensions/2.0/Khronos/KHR_materials_sheen
+https://github.com/KhronosGroup/glTF/tree/main/extensions/2.0/Khronos/KHR_materials_anisotropy
+https://blog.selfshadow.com/publications/s2017-shading-course/imageworks/s2017_pbs_imageworks_sheen.pdf
+https://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models#TheTorrancendashSparrowModel
+
+the layers are only used for pbr materials, the sheen is sampled with the diffuse (cosine) lobe
+*/
+
+// ******************** BsdfSample ********************
+pub struct BsdfSample
+{
+    pub dir: Vector3<f32>,
+    pub weight: Vector3<f32>, // f * cos / pdf
+    pub pdf: f32,
+    pub glossy: bool
+}
+
+// ******************** Bsdf ********************
+pub struct Bsdf
+{
+    pub shading_model: ShadingModel,
+
+    pub base_color: Vector3<f32>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub f0: Vector3<f32>,
+
+    // ********** layers **********
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub clearcoat_normal: Option<Vector3<f32>>, //None = shading normal
+
+    pub sheen_color: Vector3<f32>,
+    pub sheen_roughness: f32,
+
+    pub anisotropy: f32,
+    pub tangent: Option<Vector3<f32>>, //direction of the anisotropy (None = isotropic)
+
+    pub thin_film: ThinFilm
+}
+
+impl Bsdf
+{
+    pub fn new(shading_model: ShadingModel, base_color: Vector3<f32>, metallic: f32, roughness: f32, refraction_index: f32) -> Bsdf
+    {
+        let metallic = metallic.clamp(0.0, 1.0);
+
+        // dielectric reflectance based on the index of refraction (if set)
+        let mut dielectric_f0 = DIELECTRIC_F0;
+        if refraction_index > 1.0
+        {
+            dielectric_f0 = ((refraction_index - 1.0) / (refraction_index + 1.0)).powi(2);
+        }
+
+        let f0 = Vector3::<f32>::new
+        (
+            interpolate(dielectric_f0, base_color.x, metallic),
+            interpolate(dielectric_f0, base_color.y, metallic),
+            interpolate(dielectric_f0, base_color.z, metallic)
+        );
+
+        Bsdf
+        {
+            shading_model: shading_model,
+
+            base_color: base_color,
+            metallic: metallic,
+            roughness: roughness.clamp(MIN_PBR_ROUGHNESS, 1.0),
+            f0: f0,
+
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.0,
+            clearcoat_normal: None,
+
+            sheen_color: Vector3::<f32>::zeros(),
+            sheen_roughness: 0.0,
+
+            anisotropy: 0.0,
+            tangent: None,
+
+            thin_film: ThinFilm::new()
+        }
+    }
+
+    // specular fresnel term of the base layer (modulated by the thin film)
+    pub fn fresnel(&self, cos_theta: f32) -> Vector3<f32>
+    {
+        let fresnel = fresnel_schlick(self.f0, cos_theta);
+
+        self.thin_film.apply(fresnel, cos_theta, self.f0)
+    }
+
+    pub fn alpha(&self) -> f32
+    {
+        self.roughness * self.roughness
+    }
+
+    // alpha along the tangent and the bitangent
+    pub fn anisotropic_alpha(&self) -> (f32, f32)
+    {
+        let alpha = self.alpha();
+        let anisotropy = self.anisotropy.clamp(0.0, 1.0);
+
+        (interpolate(alpha, 1.0, anisotropy * anisotropy), alpha)
+    }
+
+    pub fn clearcoat_alpha(&self) -> f32
+    {
+        let roughness = self.clearcoat_roughness.clamp(MIN_PBR_ROUGHNESS, 1.0);
+
+        roughness * roughness
+    }
+
+    pub fn has_clearcoat(&self) -> bool
+    {
+        self.shading_model == ShadingModel::Pbr && self.clearcoat > 0.0
+    }
+
+    pub fn has_sheen(&self) -> bool
+    {
+        self.shading_model == ShadingModel::Pbr && max_component(self.sheen_color) > 0.0
+    }
+
+    // clearcoat normal on the same side as the shading normal
+    pub fn get_clearcoat_normal(&self, n: Vector3<f32>) -> Vector3<f32>
+    {
+        if let Some(clearcoat_normal) = self.clearcoat_normal
+        {
+            if clearcoat_normal.dot(&n) < 0.0
+            {
+                return -clearcoat_normal;
+            }
+
+            return clearcoat_normal;
+        }
+
+        n
+    }
+
+    // tangent frame of the anisotropic lobe
+    pub fn get_tangent_frame(&self, n: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>)
+    {
+        if let Some(tangent) = self.tangent
+        {
+            let tangent = tangent - n * n.dot(&tangent);
+
+            if tangent.magnitude_squared() > 0.000001
+            {
+                let tangent = tangent.normalize();
+                return (tangent, n.cross(&tangent));
+            }
+        }
+
+        onb(n)
+    }
+
+    // specular lobe of the base layer (anisotropic GGX)
+    fn eval_specular(&self, n: Vector3<f32>, wo: Vector3<f32>, wi: Vector3<f32>, h: Vector3<f32>) -> f32
+    {
+        let (tangent, bitangent) = self.get_tangent_frame(n);
+        let (alpha_t, alpha_b) = self.anisotropic_alpha();
+
+        let d = ggx_d_anisotropic(h.dot(&tangent), h.dot(&bitangent), n.dot(&h).max(0.0), alpha_t, alpha_b);
+        let g = smith_g1_anisotropic(wo.dot(&tangent), wo.dot(&bitangent), n.dot(&wo), alpha_t, alpha_b)
+              * smith_g1_anisotropic(wi.dot(&tangent), wi.dot(&bitangent), n.dot(&wi), alpha_t, alpha_b);
+
+        d * g / (4.0 * n.dot(&wi) * n.dot(&wo))
+    }
+
+    fn specular_pdf(&self, n: Vector3<f32>, wo: Vector3<f32>, h: Vector3<f32>) -> f32
+    {
+        let (tangent, bitangent) = self.get_tangent_frame(n);
+        let (alpha_t, alpha_b) = self.anisotropic_alpha();
+
+        let n_dot_h = n.dot(&h).max(0.0);
+        let v_dot_h = wo.dot(&h).max(0.0001);
+
+        ggx_d_anisotropic(h.dot(&tangent), h.dot(&bitangent), n_dot_h, alpha_t, alpha_b) * n_dot_h / (4.0 * v_dot_h)
+    }
+
+    // (f, fresnel weight of the clearcoat)
+    fn eval_clearcoat(&self, n: Vector3<f32>, wo: Vector3<f32>, wi: Vector3<f32>, h: Vector3<f32>) -> (f32, f32)
+    {
+        let cn = self.get_clearcoat_normal(n);
+
+        let n_dot_l = cn.dot(&wi);
+        let n_dot_v = cn.dot(&wo);
+
+        let f = self.clearcoat * fresnel_schlick_scalar(CLEARCOAT_F0, wo.dot(&h).max(0.0));
+
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0
+        {
+            return (0.0, f);
+        }
+
+        let alpha = self.clearcoat_alpha();
+
+        let d = ggx_d(cn.dot(&h).max(0.0), alpha);
+        let g = smith_g(n_dot_l, n_dot_v, alpha);
+
+        (f * d * g / (4.0 * n_dot_l * n_dot_v), f)
+    }
+
+    // weighted fresnel reflectance of the clearcoat for the view direction
+    pub fn get_clearcoat_fresnel(&self, n: Vector3<f32>, wo: Vector3<f32>) -> f32
+    {
+        if !self.has_clearcoat()
+        {
+            return 0.0;
+        }
+
+        let cn = self.get_clearcoat_normal(n);
+
+        self.clearcoat * fresnel_schlick_scalar(CLEARCOAT_F0, cn.dot(&wo).max(0.0))
+    }
+
+    fn clearcoat_probability(&self, n: Vector3<f32>, wo: Vector3<f32>) -> f32
+    {
+        if !self.has_clearcoat()
+        {
+            return 0.0;
+        }
+
+        self.get_clearcoat_fresnel(n, wo).clamp(MIN_CLEARCOAT_PROBABILITY, MAX_CLEARCOAT_PROBABILITY)
+    }
+
+    fn clearcoat_pdf(&self, n: Vector3<f32>, wo: Vector3<f32>, wi: Vector3<f32>) -> f32
+    {
+        let cn = self.get_clearcoat_normal(n);
+
+        if cn.dot(&wi) <= 0.0 || cn.dot(&wo) <= 0.0
+        {
+            return 0.0;
+        }
+
+        let h = (wo + wi).normalize();
+        let n_dot_h = cn.dot(&h).max(0.0);
+        let v_dot_h = wo.dot(&h).max(0.0001);
+
+        ggx_d(n_dot_h, self.clearcoat_alpha()) * n_dot_h / (4.0 * v_dot_h)
+    }
+
+    fn eval_sheen(&self, n_dot_l: f32, n_dot_v: f32, n_dot_h: f32) -> Vector3<f32>
+    {
+        let roughness = self.sheen_roughness.clamp(MIN_SHEEN_ROUGHNESS, 1.0);
+
+        self.sheen_color * (charlie_d(n_dot_h, roughness * roughness) * neubelt_v(n_dot_l, n_dot_v))
+    }
+
+    // f(wo, wi) -- without the cosine term
+    pub fn eval(&self, n: Vector3<f32>, wo: Vector3<f32>, wi: Vector3<f32>) -> Vector3<f32>
+    {
+        let n_dot_l = n.dot(&wi);
+        let n_dot_v = n.dot(&wo);
+
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        match self.shading_model
+        {
+            ShadingModel::Phong =>
+            {
+                // lambert
+                self.base_color / PI
+            },
+            ShadingModel::Pbr =>
+            {
+                let h = (wo + wi).normalize();
+                let n_dot_h = n.dot(&h).max(0.0);
+                let v_dot_h = wo.dot(&h).max(0.0);
+
+                let f = self.fresnel(v_dot_h);
+
+                let specular = f * self.eval_specular(n, wo, wi, h);
+
+                let k_d = (Vector3::<f32>::new(1.0, 1.0, 1.0) - f) * (1.0 - self.metallic);
+                let diffuse = k_d.component_mul(&self.base_color) / PI;
+
+                let mut res = diffuse + specular;
+
+                // sheen on top of the base layer
+                if self.has_sheen()
+                {
+                    let sheen = self.eval_sheen(n_dot_l, n_dot_v, n_dot_h);
+                    res = res * (1.0 - max_component(self.sheen_color).min(1.0) * SHEEN_ALBEDO_SCALING) + sheen;
+                }
+
+                // clearcoat on top of everything
+                if self.has_clearcoat()
+                {
+                    let (clearcoat, clearcoat_fresnel) = self.eval_clearcoat(n, wo, wi, h);
+                    res = res * (1.0 - clearcoat_fresnel) + Vector3::<f32>::repeat(clearcoat);
+                }
+
+                res
+            }
+        }
+    }
+
+    pub fn specular_probability(&self, n: Vector3<f32>, wo: Vector3<f32>) -> f32
+    {
+        if self.shading_model == ShadingModel::Phong
+        {
+            return 0.0;
+        }
+
+        let specular = max_component(self.fresnel(n.dot(&wo).max(0.0)));
+        let diffuse = (1.0 - self.metallic) * max_component(self.base_color);
+
+        if specular + diffuse <= 0.0
+        {
+            return 0.5;
+        }
+
+        (specular / (specular + diffuse)).clamp(MIN_SPECULAR_PROBABILITY, 1.0)
+    }
+
+    pub fn pdf(&self, n: Vector3<f32>, wo: Vector3<f32>, wi: Vector3<f32>) -> f32
+    {
+        let n_dot_l = n.dot(&wi);
+        let n_dot_v = n.dot(&wo);
+
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0
+        {
+            return 0.0;
+        }
+
+        let diffuse_pdf = n_dot_l / PI;
+
+        if self.shading_model == ShadingModel::Phong
+        {
+            return diffuse_pdf;
+        }
+
+        let h = (wo + wi).normalize();
+
+        let specular_pdf = self.specular_pdf(n, wo, h);
+
+        let p_specular = self.specular_probability(n, wo);
+        let p_clearcoat = self.clearcoat_probability(n, wo);
+
+        let mut pdf = p_specular * specular_pdf + (1.0 - p_specular) * diffuse_pdf;
+
+        if p_clearcoat > 0.0
+        {
+            pdf = p_clearcoat * self.clearcoat_pdf(n, wo, wi) + (1.0 - p_clearcoat) * pdf;
+        }
+
+        pdf
+    }
+
+    pub fn sample(&self, n: Vector3<f32>, wo: Vector3<f32>, u0: f32, u1: f32, u2: f32) -> Option<BsdfSample>
+    {
+        let p_specular = self.specular_probability(n, wo);
+        let p_clearcoat = self.clearcoat_probability(n, wo);
+
+        let wi;
+        let mut glossy = false;
+
+        if u0 < p_clearcoat
+        {
+            // clearcoat reflection
+            let h = sample_ggx(self.get_clearcoat_normal(n), self.clearcoat_alpha(), u1, u2);
+            wi = (2.0 * wo.dot(&h) * h - wo).normalize();
+            glossy = true;
+        }
+        else if (u0 - p_clearcoat) / (1.0 - p_clearcoat) < p_specular
+        {
+            // sample the (anisotropic) GGX distribution of normals and reflect
+            let (tangent, bitangent) = self.get_tangent_frame(n);
+            let (alpha_t, alpha_b) = self.anisotropic_alpha();
+
+            let h = sample_ggx_anisotropic(n, tangent, bitangent, alpha_t, alpha_b, u1, u2);
+            wi = (2.0 * wo.dot(&h) * h - wo).normalize();
+            glossy = true;
+        }
+        else
+        {
+            wi = crate::helper::cosine_sample_hemisphere(n, u1, u2);
+        }
+
+        if n.dot(&wi) <= 0.0
+        {
+            return None;
+        }
+
+        let pdf = self.pdf(n, wo, wi);
+        if pdf <= 0.0
+        {
+            return None;
+        }
+
+        let f = self.eval(n, wo, wi);
+
+        Some(BsdfSample
+        {
+            dir: wi,
+            weight: f * (n.dot(&wi) / pdf),
+            pdf: pdf,
+            glossy: glossy
+        })
+    }
+}
+
+// GGX / Trowbridge-Reitz normal distribution
+pub fn ggx_d(n_dot_h: f32, alpha: f32) -> f32
+{
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+
+    a2 / (PI * d * d)
+}
+
+// smith shadowing/masking for one direction
+pub fn smith_g1(n_dot_v: f32, alpha: f32) -> f32
+{
+    let a2 = alpha * alpha;
+
+    2.0 * n_dot_v / (n_dot_v + (a2 + (1.0 - a2) * n_dot_v * n_dot_v).sqrt())
+}
+
+pub fn smith_g(n_dot_l: f32, n_dot_v: f32, alpha: f32) -> f32
+{
+    smith_g1(n_dot_l, alpha) * smith_g1(n_dot_v, alpha)
+}
+
+// anisotropic GGX (x: h.tangent, y: h.bitangent, z: h.normal)
+pub fn ggx_d_anisotropic(x: f32, y: f32, z: f32, alpha_x: f32, alpha_y: f32) -> f32
+{
+    let d = (x * x) / (alpha_x * alpha_x) + (y * y) / (alpha_y * alpha_y) + z * z;
+
+    1.0 / (PI * alpha_x * alpha_y * d * d)
+}
+
+// x: w.tangent, y: w.bitangent, z: w.normal
+pub fn smith_g1_anisotropic(x: f32, y: f32, z: f32, alpha_x: f32, alpha_y: f32) -> f32
+{
+    if z <= 0.0
+    {
+        return 0.0;
+    }
+
+    let tan2 = (alpha_x * alpha_x * x * x + alpha_y * alpha_y * y * y) / (z * z);
+
+    2.0 / (1.0 + (1.0 + tan2).sqrt())
+}
+
+// "charlie" sheen distribution (Estevez and Kulla)
+pub fn charlie_d(n_dot_h: f32, alpha: f32) -> f32
+{
+    let inv_alpha = 1.0 / alpha;
+    let sin2 = (1.0 - n_dot_h * n_dot_h).max(0.0);
+
+    (2.0 + inv_alpha) * sin2.powf(inv_alpha * 0.5) / (2.0 * PI)
+}
+
+// sheen visibility (Neubelt and Pettineo)
+pub fn neubelt_v(n_dot_l: f32, n_dot_v: f32) -> f32
+{
+    1.0 / (4.0 * (n_dot_l + n_dot_v - n_dot_l * n_dot_v))
+}
+
+pub fn fresnel_schlick_scalar(f0: f32, cos_theta: f32) -> f32
+{
+    let factor = (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+
+    f0 + (1.0 - f0) * factor
+}
+
+pub fn fresnel_schlick(f0: Vector3<f32>, cos_theta: f32) -> Vector3<f32>
+{
+    let factor = (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+
+    f0 + (Vector3::<f32>::new(1.0, 1.0, 1.0) - f0) * factor
+}
+
+// sample a microfacet normal proportional to D(h) * cos(theta_h)
+pub fn sample_ggx(n: Vector3<f32>, alpha: f32, u1: f32, u2: f32) -> Vector3<f32>
+{
+    let a2 = alpha * alpha;
+
+    let phi = 2.0 * PI * u1;
+    let cos_theta = ((1.0 - u2) / (1.0 + (a2 - 1.0) * u2)).max(0.0).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+    let (tangent, bitangent) = onb(n);
+
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + n * cos_theta).normalize()
+}
+
+// stretched slopes: sample a microfacet normal proportional to D(h) * cos(theta_h) of the anisotropic GGX distribution
+pub fn sample_ggx_anisotropic(n: Vector3<f32>, tangent: Vector3<f32>, bitangent: Vector3<f32>, alpha_x: f32, alpha_y: f32, u1: f32, u2: f32) -> Vector3<f32>
+{
+    let phi = 2.0 * PI * u1;
+    let slope = (u2 / (1.0 - u2).max(0.000001)).sqrt();
+
+    let x = alpha_x * slope * phi.cos();
+    let y = alpha_y * slope * phi.sin();
+
+    (tangent * x + bitangent * y + n).normalize()
+}
@@ -1,5 +1,9 @@
+use std::f32::consts::PI;
+
 use nalgebra::{Matrix4, Perspective3, Point3, Isometry3, Vector3};
+use parry3d::query::Ray;
 
+use crate::animation::interpolate_motion;
 use crate::helper::approx_equal;
 
 const DEFAULT_CAM_POS: Point3::<f32> = Point3::<f32>::new(0.0, 0.0, 0.0);
@@ -14,9 +18,73 @@ pub const DEFAULT_FOV: f32 = 90.0f32;
 const DEFAULT_CLIPPING_NEAR: f32 = 0.001;
 const DEFAULT_CLIPPING_FAR: f32 = 1000.0;
 
-#[derive(Debug, Copy, Clone)]
+const DEFAULT_SENSOR_WIDTH: f32 = 36.0; //mm (full frame)
+const DEFAULT_SENSOR_HEIGHT: f32 = 24.0; //mm
+const DEFAULT_F_NUMBER: f32 = 2.8;
+const DEFAULT_FOCUS_DISTANCE: f32 = 1.0;
+const DEFAULT_SHUTTER_SPEED: f32 = 1.0 / 60.0; //seconds
+const DEFAULT_ISO: f32 = 100.0;
+
+const MM_TO_SCENE_UNITS: f32 = 0.001; //scene units are meters (like glTF)
+
+const DEFAULT_ORTHO_SCALE: f32 = 1.0;
+const DEFAULT_FISHEYE_FOV: f32 = 180.0;
+const DEFAULT_STEREO_EYE_DISTANCE: f32 = 0.065; //interpupillary distance in meters
+
+/*
+some resources:
+
+thin lens model:
+https://www.pbr-book.org/3ed-2018/Camera_Models/Projective_Camera_Models#TheThinLensModelandDepthofField
+
+physical exposure (EV100):
+https://seblagarde.files.wordpress.com/2015/07/course_notes_moving_frostbite_to_pbr_v32.pdf
+https://google.github.io/filament/Filament.md.html#imagingpipeline/physicallybasedcamera
+
+fisheye projections:
+https://en.wikipedia.org/wiki/Fisheye_lens#Mapping_function
+*/
+
+// ******************** CameraProjection ********************
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum CameraProjection
+{
+    Perspective,
+    Orthographic,
+    FisheyeEquidistant,
+    FisheyeEquisolid,
+    Equirectangular,
+    Stereo //side by side (left eye on the left half)
+}
+
+impl CameraProjection
+{
+    pub fn from_str(projection: &str) -> Option<CameraProjection>
+    {
+        match projection
+        {
+            "perspective" => Some(CameraProjection::Perspective),
+            "orthographic" | "ortho" => Some(CameraProjection::Orthographic),
+            "fisheye" | "fisheye_equidistant" => Some(CameraProjection::FisheyeEquidistant),
+            "fisheye_equisolid" => Some(CameraProjection::FisheyeEquisolid),
+            "equirectangular" | "panorama" => Some(CameraProjection::Equirectangular),
+            "stereo" => Some(CameraProjection::Stereo),
+            _ => None
+        }
+    }
+}
+
+// exposure value at ISO 100
+pub fn get_ev100(f_number: f32, shutter_speed: f32, iso: f32) -> f32
+{
+    ((f_number * f_number) / shutter_speed * 100.0 / iso).log2()
+}
+
+#[derive(Debug, Clone)]
 pub struct Camera
 {
+    pub name: String,
+
     pub width: u32,
     pub height: u32,
     pub aspect_ratio: f32,
@@ -31,6 +99,26 @@ pub struct Camera
     pub clipping_near: f32,
     pub clipping_far: f32,
 
+    pub projection_type: CameraProjection,
+    pub ortho_scale: f32, //half of the view height (orthographic)
+    pub fisheye_fov: f32, //radians (diagonal of the image circle)
+    pub stereo_eye_distance: f32,
+
+    //physical camera (the focal length is derived from the fov and the sensor height)
+    pub sensor_width: f32, //mm
+    pub sensor_height: f32, //mm
+    pub dof: bool, //thin lens depth of field
+    pub f_number: f32,
+    pub focus_distance: f32, //scene units
+    pub shutter_speed: f32, //seconds
+    pub iso: f32,
+    pub exposure: bool, //scales the radiance relative to the default exposure (f/2.8, 1/60 s, ISO 100)
+
+    //motion blur (shutter interval in frames relative to the rendered frame, e.g. 0.0 - 0.5 for a 180° shutter)
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+    pub motion: Vec<Matrix4<f32>>, //camera movement during the shutter interval (relative to the pose of the frame)
+
     pub projection: Perspective3<f32>,
     pub view: Matrix4<f32>,
 
@@ -44,6 +132,8 @@ impl Camera
     {
         Camera
         {
+            name: String::from("camera"),
+
             width: 0,
             height: 0,
             aspect_ratio: 0.0,
@@ -58,6 +148,24 @@ impl Camera
             clipping_near: DEFAULT_CLIPPING_NEAR,
             clipping_far: DEFAULT_CLIPPING_FAR,
 
+            projection_type: CameraProjection::Perspective,
+            ortho_scale: DEFAULT_ORTHO_SCALE,
+            fisheye_fov: DEFAULT_FISHEYE_FOV.to_radians(),
+            stereo_eye_distance: DEFAULT_STEREO_EYE_DISTANCE,
+
+            sensor_width: DEFAULT_SENSOR_WIDTH,
+            sensor_height: DEFAULT_SENSOR_HEIGHT,
+            dof: false,
+            f_number: DEFAULT_F_NUMBER,
+            focus_distance: DEFAULT_FOCUS_DISTANCE,
+            shutter_speed: DEFAULT_SHUTTER_SPEED,
+            iso: DEFAULT_ISO,
+            exposure: false,
+
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            motion: vec![],
+
             projection: Perspective3::<f32>::new(1.0f32, 0.0f32, DEFAULT_CLIPPING_NEAR, DEFAULT_CLIPPING_FAR),
             view: Matrix4::<f32>::identity(),
 
@@ -122,6 +230,181 @@ impl Camera
         approx_equal(self.clipping_far, DEFAULT_CLIPPING_FAR)
     }
 
+    // focal length in mm
+    pub fn get_focal_length(&self) -> f32
+    {
+        self.sensor_height / (2.0 * (self.fov / 2.0).tan())
+    }
+
+    pub fn set_focal_length(&mut self, focal_length: f32)
+    {
+        self.fov = 2.0 * (self.sensor_height / (2.0 * focal_length.max(0.001))).atan();
+    }
+
+    // radius of the thin lens in scene units (0 means pinhole)
+    pub fn get_lens_radius(&self) -> f32
+    {
+        if !self.dof || self.f_number <= 0.0
+        {
+            return 0.0;
+        }
+
+        (self.get_focal_length() * MM_TO_SCENE_UNITS) / (2.0 * self.f_number)
+    }
+
+    // linear radiance scale based on aperture, shutter speed and iso
+    pub fn get_exposure(&self) -> f32
+    {
+        if !self.exposure || self.f_number <= 0.0 || self.shutter_speed <= 0.0 || self.iso <= 0.0
+        {
+            return 1.0;
+        }
+
+        let reference = get_ev100(DEFAULT_F_NUMBER, DEFAULT_SHUTTER_SPEED, DEFAULT_ISO);
+
+        2.0f32.powf(reference - get_ev100(self.f_number, self.shutter_speed, self.iso))
+    }
+
+    pub fn has_motion_blur(&self) -> bool
+    {
+        self.shutter_close > self.shutter_open
+    }
+
+    // animation transformation of the camera (applied to the default camera looking along -z)
+    pub fn apply_animation_trans(&mut self, trans: &Matrix4<f32>)
+    {
+        self.eye_pos = Point3::<f32>::from_homogeneous(trans * DEFAULT_CAM_POS.to_homogeneous()).unwrap();
+        self.dir = (trans * DEFAULT_CAM_DIR.to_homogeneous()).xyz().normalize();
+        self.up = (trans * DEFAULT_CAM_UP.to_homogeneous()).xyz().normalize();
+
+        //not initialized yet (the matrices are created on init)
+        if self.aspect_ratio > 0.0
+        {
+            self.init_matrices();
+        }
+    }
+
+    // moves the primary ray along the camera motion (time: 0 = shutter open, 1 = shutter close)
+    pub fn apply_motion(&self, ray: Ray, time: f32) -> Ray
+    {
+        if self.motion.is_empty()
+        {
+            return ray;
+        }
+
+        let trans = interpolate_motion(&self.motion, time);
+
+        let origin = Point3::<f32>::from_homogeneous(trans * ray.origin.to_homogeneous()).unwrap();
+        let dir = (trans * ray.dir.to_homogeneous()).xyz().normalize();
+
+        Ray::new(origin, dir)
+    }
+
+    // right, up and forward vector of the camera
+    pub fn get_basis(&self) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>)
+    {
+        let forward = self.dir.normalize();
+        let right = forward.cross(&self.up).normalize();
+        let up = right.cross(&forward);
+
+        (right, up, forward)
+    }
+
+    pub fn is_planar(&self) -> bool
+    {
+        self.projection_type == CameraProjection::Perspective || self.projection_type == CameraProjection::Orthographic || self.projection_type == CameraProjection::Stereo
+    }
+
+    // primary ray of the non perspective projections (sensor_x/y in -1..1, y is up)
+    // None outside of the image circle of fisheye projections
+    pub fn get_projection_ray(&self, sensor_x: f32, sensor_y: f32) -> Option<Ray>
+    {
+        let (right, up, forward) = self.get_basis();
+
+        let mut origin = self.eye_pos;
+        let dir;
+
+        match self.projection_type
+        {
+            CameraProjection::Perspective =>
+            {
+                let tan_half_fov = (self.fov / 2.0).tan();
+                dir = forward + (right * (sensor_x * tan_half_fov * self.aspect_ratio)) + (up * (sensor_y * tan_half_fov));
+            },
+            CameraProjection::Orthographic =>
+            {
+                origin = origin + (right * (sensor_x * self.ortho_scale * self.aspect_ratio)) + (up * (sensor_y * self.ortho_scale));
+                dir = forward;
+            },
+            CameraProjection::FisheyeEquidistant | CameraProjection::FisheyeEquisolid =>
+            {
+                // round image circle fitted to the image height
+                let x = sensor_x * self.aspect_ratio;
+                let y = sensor_y;
+
+                let r = (x * x + y * y).sqrt();
+                if r > 1.0
+                {
+                    return None;
+                }
+
+                let max_theta = (self.fisheye_fov / 2.0).min(PI);
+
+                let theta;
+                if self.projection_type == CameraProjection::FisheyeEquidistant
+                {
+                    // r = f * theta
+                    theta = r * max_theta;
+                }
+                else
+                {
+                    // r = 2 * f * sin(theta / 2)
+                    theta = 2.0 * (r * (max_theta / 2.0).sin()).clamp(-1.0, 1.0).asin();
+                }
+
+                let phi = y.atan2(x);
+
+                dir = (forward * theta.cos()) + (((right * phi.cos()) + (up * phi.sin())) * theta.sin());
+            },
+            CameraProjection::Equirectangular =>
+            {
+                let longitude = sensor_x * PI;
+                let latitude = sensor_y * (PI / 2.0);
+
+                dir = (forward * (latitude.cos() * longitude.cos())) + (right * (latitude.cos() * longitude.sin())) + (up * latitude.sin());
+            },
+            CameraProjection::Stereo =>
+            {
+                // two parallel perspective cameras with half of the aspect ratio each
+                let mut x = sensor_x * 2.0 + 1.0;
+                let mut eye_offset = -self.stereo_eye_distance / 2.0;
+                if sensor_x >= 0.0
+                {
+                    x = sensor_x * 2.0 - 1.0;
+                    eye_offset = -eye_offset;
+                }
+
+                let tan_half_fov = (self.fov / 2.0).tan();
+
+                origin = origin + (right * eye_offset);
+                dir = forward + (right * (x * tan_half_fov * self.aspect_ratio / 2.0)) + (up * (sensor_y * tan_half_fov));
+            }
+        }
+
+        let dir = dir.normalize();
+
+        // start at the near clipping plane (a sphere for the wide angle projections)
+        let mut near_dist = self.clipping_near;
+        if self.is_planar()
+        {
+            near_dist /= dir.dot(&forward).max(0.0001);
+        }
+
+        origin = origin + (dir * near_dist);
+
+        Some(Ray::new(origin, dir))
+    }
+
     pub fn set_cam_position(&mut self, eye_pos: Point3::<f32>, dir: Vector3::<f32>)
     {
         self.eye_pos = eye_pos;
@@ -141,6 +424,7 @@ impl Camera
 
     pub fn print(&self)
     {
+        println!("name: {:?}", self.name);
         println!("width: {:?}", self.width);
         println!("height: {:?}", self.height);
         println!("aspect_ratio: {:?}", self.aspect_ratio);
@@ -155,6 +439,25 @@ impl Camera
         println!("clipping_near: {:?}", self.clipping_near);
         println!("clipping_far: {:?}", self.clipping_far);
 
+        println!("projection_type: {:?}", self.projection_type);
+        println!("ortho_scale: {:?}", self.ortho_scale);
+        println!("fisheye_fov: {:?}", self.fisheye_fov);
+        println!("stereo_eye_distance: {:?}", self.stereo_eye_distance);
+
+        println!("sensor_width: {:?}", self.sensor_width);
+        println!("sensor_height: {:?}", self.sensor_height);
+        println!("focal_length: {:?}", self.get_focal_length());
+        println!("dof: {:?}", self.dof);
+        println!("f_number: {:?}", self.f_number);
+        println!("focus_distance: {:?}", self.focus_distance);
+        println!("shutter_speed: {:?}", self.shutter_speed);
+        println!("iso: {:?}", self.iso);
+        println!("exposure: {:?}", self.exposure);
+
+        println!("shutter_open: {:?}", self.shutter_open);
+        println!("shutter_close: {:?}", self.shutter_close);
+        println!("motion steps: {:?}", self.motion.len());
+
         println!("projection: {:?}", self.projection);
         println!("view: {:?}", self.view);
     }
@@ -0,0 +1,186 @@
+use nalgebra::Vector3;
+use parry3d::query::Ray;
+
+use crate::raytracing::{Raytracing, RayType};
+use crate::scene::Scene;
+
+// counts mapped to the hottest color of the heat maps
+const HEAT_MAP_MAX_BVH_NODES: usize = 64;
+const HEAT_MAP_MAX_INTERSECTION_TESTS: usize = 64;
+
+/*
+some resources:
+
+heat map color ramp:
+https://www.kennethmoreland.com/color-advice/
+
+debug visualizations of a renderer:
+https://www.pbr-book.org/3ed-2018/Shapes/Triangle_Meshes
+https://docs.blender.org/manual/en/latest/render/layers/passes.html
+*/
+
+// ******************** RenderMode ********************
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum RenderMode
+{
+    Shaded, //output of the integrator
+    ShadingNormals,
+    GeometricNormals,
+    Uvs,
+    Barycentrics,
+    Depth,
+    MaterialId,
+    ObjectId,
+    Albedo, //unlit base color (with textures)
+    BvhHeatMap, //visited nodes of the scene bvh
+    IntersectionHeatMap //exact primitive (triangle) intersection tests
+}
+
+impl RenderMode
+{
+    pub fn from_str(render_mode: &str) -> Option<RenderMode>
+    {
+        match render_mode
+        {
+            "shaded" | "default" => Some(RenderMode::Shaded),
+            "shading_normals" | "normals" => Some(RenderMode::ShadingNormals),
+            "geometric_normals" => Some(RenderMode::GeometricNormals),
+            "uvs" | "uv" => Some(RenderMode::Uvs),
+            "barycentrics" => Some(RenderMode::Barycentrics),
+            "depth" => Some(RenderMode::Depth),
+            "material_id" => Some(RenderMode::MaterialId),
+            "object_id" => Some(RenderMode::ObjectId),
+            "albedo" => Some(RenderMode::Albedo),
+            "bvh_heat_map" | "bvh" => Some(RenderMode::BvhHeatMap),
+            "intersection_heat_map" | "intersections" => Some(RenderMode::IntersectionHeatMap),
+            _ => None
+        }
+    }
+}
+
+// ******************** helper ********************
+
+// map a normal from -1..1 to 0..1
+fn normal_to_color(normal: Vector3<f32>) -> Vector3<f32>
+{
+    (normal + Vector3::<f32>::new(1.0, 1.0, 1.0)) * 0.5
+}
+
+// distinct color per id (golden ratio hue steps)
+pub fn get_id_color(id: u32) -> Vector3<f32>
+{
+    let hue = (id as f32 * 0.618034).fract() * 6.0;
+    let x = 1.0 - ((hue % 2.0) - 1.0).abs();
+
+    match hue as u32
+    {
+        0 => Vector3::<f32>::new(1.0, x, 0.0),
+        1 => Vector3::<f32>::new(x, 1.0, 0.0),
+        2 => Vector3::<f32>::new(0.0, 1.0, x),
+        3 => Vector3::<f32>::new(0.0, x, 1.0),
+        4 => Vector3::<f32>::new(x, 0.0, 1.0),
+        _ => Vector3::<f32>::new(1.0, 0.0, x)
+    }
+}
+
+// blue (cold) -> cyan -> green -> yellow -> red (hot)
+pub fn get_heat_map_color(value: f32) -> Vector3<f32>
+{
+    let value = value.clamp(0.0, 1.0) * 4.0;
+    let f = value.fract();
+
+    match value as u32
+    {
+        0 => Vector3::<f32>::new(0.0, f, 1.0),
+        1 => Vector3::<f32>::new(0.0, 1.0, 1.0 - f),
+        2 => Vector3::<f32>::new(f, 1.0, 0.0),
+        3 => Vector3::<f32>::new(1.0, 1.0 - f, 0.0),
+        _ => Vector3::<f32>::new(1.0, 0.0, 0.0)
+    }
+}
+
+impl Raytracing
+{
+    pub fn get_color_depth_normal_id_debug(&self, scene: &Scene, ray: Ray) -> (Vector3<f32>, f32, Vector3<f32>, u32, Vector3<f32>)
+    {
+        let black = Vector3::<f32>::zeros();
+
+        // ********** heat maps (also without a hit) **********
+        let render_mode = self.config.render_mode;
+        if render_mode == RenderMode::BvhHeatMap || render_mode == RenderMode::IntersectionHeatMap
+        {
+            let (bvh_node_visits, intersection_tests) = self.get_trace_counts(scene, &ray);
+
+            let mut heat = bvh_node_visits as f32 / HEAT_MAP_MAX_BVH_NODES as f32;
+            if render_mode == RenderMode::IntersectionHeatMap
+            {
+                heat = intersection_tests as f32 / HEAT_MAP_MAX_INTERSECTION_TESTS as f32;
+            }
+
+            let color = get_heat_map_color(heat);
+            return (color, 0.0, black, 0, color);
+        }
+
+        let intersection = self.trace(scene, &ray, false, RayType::Camera, 1);
+
+        if intersection.is_none()
+        {
+            return (black, 0.0, black, 0, black);
+        }
+
+        let (dist, normal, item, face_id) = intersection.unwrap();
+        let hit_point = ray.origin + (ray.dir * dist);
+
+        let material = item.get_material().read().unwrap();
+        let surface = self.get_surface_interaction(&ray, dist, normal, item, face_id, &material);
+
+        let color = match render_mode
+        {
+            RenderMode::ShadingNormals => normal_to_color(surface.surface_normal),
+            RenderMode::GeometricNormals => normal_to_color(item.get_geometric_normal(hit_point, face_id).unwrap_or(normal)),
+            RenderMode::Uvs =>
+            {
+                let uv = item.get_uv(hit_point, face_id);
+                Vector3::<f32>::new(uv.x.rem_euclid(1.0), uv.y.rem_euclid(1.0), 0.0)
+            },
+            RenderMode::Barycentrics => item.get_barycentrics(hit_point, face_id).unwrap_or(black),
+            RenderMode::Depth =>
+            {
+                // linear depth: white = near, black = the farthest point of the scene
+                let mut max_dist: f32 = 0.0;
+                for vert in &scene.b_box.vertices()
+                {
+                    max_dist = max_dist.max((vert - scene.cam.eye_pos).magnitude());
+                }
+
+                let depth = 1.0 - (dist / max_dist.max(0.0001)).clamp(0.0, 1.0);
+                Vector3::<f32>::new(depth, depth, depth)
+            },
+            RenderMode::MaterialId => get_id_color(material.id),
+            RenderMode::ObjectId => get_id_color(item.get_basic().id),
+            RenderMode::Albedo => surface.base_color,
+            _ => black
+        };
+
+        (color, dist, surface.normal, item.get_basic().id, surface.base_color)
+    }
+
+    // visited nodes of the scene bvh and the exact primitive tests of trace (triangles of the meshes with a bounding box hit)
+    pub fn get_trace_counts(&self, scene: &Scene, ray: &Ray) -> (usize, usize)
+    {
+        //the bvh is always traversed here (trace is only using it for larger scenes)
+        let bvh_node_visits = scene.get_bvh_node_visits(ray);
+
+        let mut intersection_tests = 0;
+        for item in self.get_trace_candidates(scene, ray)
+        {
+            if item.intersect_b_box(ray, false).is_some()
+            {
+                intersection_tests += item.get_primitive_tests(ray);
+            }
+        }
+
+        (bvh_node_visits, intersection_tests)
+    }
+}
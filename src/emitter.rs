@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use std::f32::consts::PI;
+
+use nalgebra::{Point3, Vector3};
+
+use crate::helper::max_component;
+use crate::scene::ScemeItem;
+
+// ******************** EmissiveGeometry ********************
+pub enum EmissiveGeometry
+{
+    Triangle { a: Point3<f32>, b: Point3<f32>, c: Point3<f32>, normal: Vector3<f32> },
+    Sphere { center: Point3<f32>, radius: f32 }
+}
+
+impl EmissiveGeometry
+{
+    // triangles are emitting on both sides, spheres only outwards (they are closed)
+    pub fn is_two_sided(&self) -> bool
+    {
+        match self
+        {
+            EmissiveGeometry::Triangle { .. } => true,
+            EmissiveGeometry::Sphere { .. } => false
+        }
+    }
+}
+
+// ******************** EmissivePrimitive ********************
+pub struct EmissivePrimitive
+{
+    pub item_index: usize,
+    pub item_id: u32,
+    pub face_id: u32,
+
+    pub geometry: EmissiveGeometry,
+    pub area: f32
+}
+
+// ******************** EmitterSample ********************
+pub struct EmitterSample
+{
+    pub item_index: usize,
+    pub face_id: u32,
+
+    pub point: Point3<f32>,
+    pub normal: Vector3<f32>,
+    pub pdf: f32, // area measure (including the primitive selection)
+    pub two_sided: bool
+}
+
+// ******************** Emitters ********************
+pub struct Emitters
+{
+    pub primitives: Vec<EmissivePrimitive>,
+
+    cdf: Vec<f32>,
+    total_weight: f32,
+
+    lookup: HashMap<(u32, u32), usize>,
+    face_amount: HashMap<u32, u32>
+}
+
+impl Emitters
+{
+    pub fn new() -> Emitters
+    {
+        Emitters
+        {
+            primitives: vec![],
+
+            cdf: vec![],
+            total_weight: 0.0,
+
+            lookup: HashMap::new(),
+            face_amount: HashMap::new()
+        }
+    }
+
+    // collects all triangles (and analytic spheres) of emissive shapes -- they are chosen by area * emitted power
+    pub fn build(&mut self, items: &Vec<ScemeItem>)
+    {
+        self.primitives.clear();
+        self.cdf.clear();
+        self.lookup.clear();
+        self.face_amount.clear();
+        self.total_weight = 0.0;
+
+        for (item_index, item) in items.iter().enumerate()
+        {
+            if !item.get_basic().visible
+            {
+                continue;
+            }
+
+            // only explicit emission (the ambient color of legacy materials is no light source)
+            let material = item.get_material_cache_without_textures();
+            let power = max_component(material.ambient_color);
+            if !material.emissive || power <= 0.0
+            {
+                continue;
+            }
+
+            let item_id = item.get_basic().id;
+
+            // analytic spheres (all hits are reported as face 0)
+            if let Some((center, radius)) = item.get_sphere()
+            {
+                let area = 4.0 * PI * radius * radius;
+
+                if area > 0.0
+                {
+                    self.face_amount.insert(item_id, 1);
+                    self.add(item_index, item_id, 0, EmissiveGeometry::Sphere { center: center, radius: radius }, area, power);
+                }
+
+                continue;
+            }
+
+            let triangles = item.get_triangles();
+
+            if triangles.is_empty()
+            {
+                println!("emissive shape {} can not be sampled as light (no triangles)", item.get_basic().name);
+                continue;
+            }
+
+            self.face_amount.insert(item_id, triangles.len() as u32);
+
+            for (face_id, triangle) in triangles.iter().enumerate()
+            {
+                let cross = (triangle[1] - triangle[0]).cross(&(triangle[2] - triangle[0]));
+                let area = cross.norm() * 0.5;
+
+                if area <= 0.0
+                {
+                    continue;
+                }
+
+                let geometry = EmissiveGeometry::Triangle { a: triangle[0], b: triangle[1], c: triangle[2], normal: cross.normalize() };
+                self.add(item_index, item_id, face_id as u32, geometry, area, power);
+            }
+        }
+    }
+
+    fn add(&mut self, item_index: usize, item_id: u32, face_id: u32, geometry: EmissiveGeometry, area: f32, power: f32)
+    {
+        self.total_weight += area * power;
+        self.cdf.push(self.total_weight);
+
+        self.lookup.insert((item_id, face_id), self.primitives.len());
+
+        self.primitives.push(EmissivePrimitive
+        {
+            item_index: item_index,
+            item_id: item_id,
+            face_id: face_id,
+
+            geometry: geometry,
+            area: area
+        });
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.primitives.is_empty()
+    }
+
+    // sum of area * emitted power of all primitives
+    pub fn get_total_weight(&self) -> f32
+    {
+        self.total_weight
+    }
+
+    fn get_selection_probability(&self, index: usize) -> f32
+    {
+        let mut prev = 0.0;
+        if index > 0
+        {
+            prev = self.cdf[index - 1];
+        }
+
+        (self.cdf[index] - prev) / self.total_weight
+    }
+
+    pub fn sample(&self, u0: f32, u1: f32, u2: f32) -> Option<EmitterSample>
+    {
+        if self.is_empty() || self.total_weight <= 0.0
+        {
+            return None;
+        }
+
+        // choose primitive
+        let target = u0 * self.total_weight;
+        let index = self.cdf.partition_point(|weight| *weight < target).min(self.primitives.len() - 1);
+
+        let primitive = &self.primitives[index];
+
+        let point;
+        let normal;
+
+        match primitive.geometry
+        {
+            EmissiveGeometry::Triangle { a, b, c, normal: face_normal } =>
+            {
+                // uniform point on the triangle
+                let su = u1.sqrt();
+                let b0 = 1.0 - su;
+                let b1 = u2 * su;
+
+                point = Point3::<f32>::from(a.coords * b0 + b.coords * b1 + c.coords * (1.0 - b0 - b1));
+                normal = face_normal;
+            },
+            EmissiveGeometry::Sphere { center, radius } =>
+            {
+                // uniform point on the sphere
+                let z = 1.0 - 2.0 * u1;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * u2;
+
+                normal = Vector3::<f32>::new(r * phi.cos(), r * phi.sin(), z);
+                point = center + (normal * radius);
+            }
+        }
+
+        Some(EmitterSample
+        {
+            item_index: primitive.item_index,
+            face_id: primitive.face_id,
+
+            point: point,
+            normal: normal,
+            pdf: self.get_selection_probability(index) / primitive.area,
+            two_sided: primitive.geometry.is_two_sided()
+        })
+    }
+
+    fn get_index(&self, item_id: u32, face_id: u32) -> Option<usize>
+    {
+        let face_amount = self.face_amount.get(&item_id);
+        if face_amount.is_none() || *face_amount.unwrap() == 0
+        {
+            return None;
+        }
+
+        // back faces are reported with an offset
+        let face_id = face_id % face_amount.unwrap();
+
+        self.lookup.get(&(item_id, face_id)).copied()
+    }
+
+    // pdf (area measure) of sampling the given point on an emissive shape
+    pub fn get_pdf(&self, item_id: u32, face_id: u32) -> f32
+    {
+        if let Some(index) = self.get_index(item_id, face_id)
+        {
+            return self.get_selection_probability(index) / self.primitives[index].area;
+        }
+
+        0.0
+    }
+
+    pub fn is_two_sided(&self, item_id: u32, face_id: u32) -> bool
+    {
+        if let Some(index) = self.get_index(item_id, face_id)
+        {
+            return self.primitives[index].geometry.is_two_sided();
+        }
+
+        true
+    }
+}
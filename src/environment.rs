@@ -0,0 +1,306 @@
+use std::f32::consts::PI;
+
+use nalgebra::Vector3;
+
+/*
+some resources:
+
+equirectangular environment maps and importance sampling (piecewise-constant 2D distribution):
+https://www.pbr-book.org/3ed-2018/Light_Sources/Infinite_Area_Lights
+https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/2D_Sampling_with_Multidimensional_Transformations#Piecewise-Constant2DDistributions
+*/
+
+// ******************** EnvironmentSample ********************
+pub struct EnvironmentSample
+{
+    pub dir: Vector3<f32>,
+    pub radiance: Vector3<f32>,
+    pub pdf: f32 // solid angle
+}
+
+// ******************** Environment ********************
+pub struct Environment
+{
+    pub enabled: bool,
+    pub path: String,
+
+    pub intensity: f32,
+    pub rotation: f32, //rotation around the y axis in rad
+
+    width: usize,
+    height: usize,
+    pixels: Vec<Vector3<f32>>,
+
+    // luminance based distribution
+    marginal_cdf: Vec<f32>,    // rows
+    conditional_cdf: Vec<f32>, // columns per row
+    row_weights: Vec<f32>,
+    pixel_weights: Vec<f32>,
+    total_weight: f32
+}
+
+impl Environment
+{
+    pub fn new() -> Environment
+    {
+        Environment
+        {
+            enabled: true,
+            path: String::new(),
+
+            intensity: 1.0,
+            rotation: 0.0,
+
+            width: 0,
+            height: 0,
+            pixels: vec![],
+
+            marginal_cdf: vec![],
+            conditional_cdf: vec![],
+            row_weights: vec![],
+            pixel_weights: vec![],
+            total_weight: 0.0
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> bool
+    {
+        let image = image::open(path);
+
+        if let Err(err) = image
+        {
+            println!("could not load environment map {}: {}", path, err);
+            return false;
+        }
+
+        let image = image.unwrap().into_rgb32f();
+
+        let pixels = image.pixels().map(|pixel| Vector3::<f32>::new(pixel[0], pixel[1], pixel[2])).collect();
+        self.load_from_pixels(path, image.width() as usize, image.height() as usize, pixels);
+
+        true
+    }
+
+    // equirectangular pixel data (row by row -- starting at the top)
+    pub fn load_from_pixels(&mut self, name: &str, width: usize, height: usize, pixels: Vec<Vector3<f32>>)
+    {
+        self.path = name.to_string();
+        self.width = width;
+        self.height = height;
+        self.pixels = pixels;
+
+        self.build_distribution();
+    }
+
+    pub fn unload(&mut self)
+    {
+        self.path = String::new();
+        self.width = 0;
+        self.height = 0;
+        self.pixels.clear();
+
+        self.marginal_cdf.clear();
+        self.conditional_cdf.clear();
+        self.row_weights.clear();
+        self.pixel_weights.clear();
+        self.total_weight = 0.0;
+    }
+
+    pub fn is_loaded(&self) -> bool
+    {
+        self.width > 0 && self.height > 0
+    }
+
+    pub fn is_active(&self) -> bool
+    {
+        self.enabled && self.is_loaded() && self.intensity > 0.0
+    }
+
+    fn build_distribution(&mut self)
+    {
+        self.pixel_weights = vec![0.0; self.width * self.height];
+        self.conditional_cdf = vec![0.0; self.width * self.height];
+        self.row_weights = vec![0.0; self.height];
+        self.marginal_cdf = vec![0.0; self.height];
+
+        let mut total = 0.0;
+
+        for y in 0..self.height
+        {
+            // rows near the poles are covering less solid angle
+            let sin_theta = (PI * (y as f32 + 0.5) / self.height as f32).sin();
+
+            let mut row_sum = 0.0;
+            for x in 0..self.width
+            {
+                let i = y * self.width + x;
+                let pixel = self.pixels[i];
+
+                let luminance = 0.2126 * pixel.x + 0.7152 * pixel.y + 0.0722 * pixel.z;
+                let weight = luminance.max(0.0) * sin_theta;
+
+                self.pixel_weights[i] = weight;
+
+                row_sum += weight;
+                self.conditional_cdf[i] = row_sum;
+            }
+
+            self.row_weights[y] = row_sum;
+
+            total += row_sum;
+            self.marginal_cdf[y] = total;
+        }
+
+        self.total_weight = total;
+    }
+
+    fn dir_to_uv(&self, dir: &Vector3<f32>) -> (f32, f32)
+    {
+        let dir = dir.normalize();
+
+        let phi = dir.x.atan2(-dir.z) - self.rotation;
+        let theta = dir.y.clamp(-1.0, 1.0).acos();
+
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = theta / PI;
+
+        (u, v)
+    }
+
+    fn uv_to_dir(&self, u: f32, v: f32) -> Vector3<f32>
+    {
+        let phi = (u - 0.5) * 2.0 * PI + self.rotation;
+        let theta = v * PI;
+
+        Vector3::<f32>::new(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos())
+    }
+
+    fn get_pixel_index(&self, u: f32, v: f32) -> usize
+    {
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+
+        y * self.width + x
+    }
+
+    // radiance from the given direction
+    pub fn eval(&self, dir: &Vector3<f32>) -> Vector3<f32>
+    {
+        if !self.is_active()
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        let (u, v) = self.dir_to_uv(dir);
+
+        self.pixels[self.get_pixel_index(u, v)] * self.intensity
+    }
+
+    // pdf (solid angle) of sampling the given direction
+    pub fn pdf(&self, dir: &Vector3<f32>) -> f32
+    {
+        if !self.is_active()
+        {
+            return 0.0;
+        }
+
+        if self.total_weight <= 0.0
+        {
+            return 1.0 / (4.0 * PI);
+        }
+
+        let (u, v) = self.dir_to_uv(dir);
+
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0
+        {
+            return 0.0;
+        }
+
+        let pdf_uv = self.pixel_weights[self.get_pixel_index(u, v)] * (self.width * self.height) as f32 / self.total_weight;
+
+        pdf_uv / (2.0 * PI * PI * sin_theta)
+    }
+
+    pub fn sample(&self, u1: f32, u2: f32) -> Option<EnvironmentSample>
+    {
+        if !self.is_active()
+        {
+            return None;
+        }
+
+        // uniform sphere sampling as fallback for completely black maps
+        if self.total_weight <= 0.0
+        {
+            let z = 1.0 - 2.0 * u1;
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let phi = 2.0 * PI * u2;
+
+            let dir = Vector3::<f32>::new(r * phi.cos(), z, r * phi.sin());
+
+            return Some(EnvironmentSample
+            {
+                dir: dir,
+                radiance: self.eval(&dir),
+                pdf: 1.0 / (4.0 * PI)
+            });
+        }
+
+        // row
+        let target = u1 * self.total_weight;
+        let y = self.marginal_cdf.partition_point(|weight| *weight < target).min(self.height - 1);
+
+        // column
+        let row_start = y * self.width;
+        let row = &self.conditional_cdf[row_start..row_start + self.width];
+
+        let row_weight = self.row_weights[y];
+        if row_weight <= 0.0
+        {
+            return None;
+        }
+
+        let target = u2 * row_weight;
+        let x = row.partition_point(|weight| *weight < target).min(self.width - 1);
+
+        // continuous position inside of the pixel
+        let mut prev_row = 0.0;
+        if y > 0
+        {
+            prev_row = self.marginal_cdf[y - 1];
+        }
+
+        let mut prev_col = 0.0;
+        if x > 0
+        {
+            prev_col = row[x - 1];
+        }
+
+        let row_offset = ((u1 * self.total_weight - prev_row) / row_weight).clamp(0.0, 1.0);
+
+        let pixel_weight = self.pixel_weights[row_start + x];
+        let mut col_offset = 0.5;
+        if pixel_weight > 0.0
+        {
+            col_offset = ((u2 * row_weight - prev_col) / pixel_weight).clamp(0.0, 1.0);
+        }
+
+        let u = (x as f32 + col_offset) / self.width as f32;
+        let v = (y as f32 + row_offset) / self.height as f32;
+
+        let dir = self.uv_to_dir(u, v);
+        let pdf = self.pdf(&dir);
+
+        if pdf <= 0.0
+        {
+            return None;
+        }
+
+        Some(EnvironmentSample
+        {
+            dir: dir,
+            radiance: self.eval(&dir),
+            pdf: pdf
+        })
+    }
+}
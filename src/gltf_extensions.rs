@@ -0,0 +1,220 @@
+use std::fs;
+use std::path::Path;
+use std::collections::HashMap;
+use std::cell::{OnceCell, RefCell};
+
+use image::{DynamicImage, RgbaImage, ImageBuffer, Rgba};
+use serde_json::Value;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_HEADER_SIZE: usize = 12;
+const GLB_CHUNK_HEADER_SIZE: usize = 8;
+const GLB_CHUNK_TYPE_JSON: u32 = 0x4E4F534A;
+
+/*
+easy_gltf does not expose material extensions --> read them from the raw gltf json
+textures of extensions are loaded via the gltf crate (only if needed -- only the referenced images are decoded)
+
+https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#glb-file-format-specification
+https://github.com/KhronosGroup/glTF/tree/main/extensions/2.0/Khronos
+*/
+
+// ******************** GltfExtensions ********************
+pub struct GltfExtensions
+{
+    path: String,
+    json: Value,
+    document: OnceCell<Option<(gltf::Document, Vec<gltf::buffer::Data>)>>,
+    images: RefCell<HashMap<usize, Option<DynamicImage>>>
+}
+
+impl GltfExtensions
+{
+    pub fn load(path: &str) -> GltfExtensions
+    {
+        let json = GltfExtensions::read_json(path).unwrap_or(Value::Null);
+
+        GltfExtensions
+        {
+            path: path.to_string(),
+            json: json,
+            document: OnceCell::new(),
+            images: RefCell::new(HashMap::new())
+        }
+    }
+
+    fn read_json(path: &str) -> Option<Value>
+    {
+        let data = fs::read(path).ok()?;
+
+        // binary gltf: the first chunk contains the json
+        if data.len() >= GLB_HEADER_SIZE + GLB_CHUNK_HEADER_SIZE && &data[0..4] == GLB_MAGIC
+        {
+            let chunk_length = u32::from_le_bytes(data[12..16].try_into().ok()?) as usize;
+            let chunk_type = u32::from_le_bytes(data[16..20].try_into().ok()?);
+
+            let start = GLB_HEADER_SIZE + GLB_CHUNK_HEADER_SIZE;
+
+            if chunk_type != GLB_CHUNK_TYPE_JSON || data.len() < start + chunk_length
+            {
+                println!("invalid glb json chunk: {}", path);
+                return None;
+            }
+
+            return serde_json::from_slice(&data[start..start + chunk_length]).ok();
+        }
+
+        serde_json::from_slice(&data).ok()
+    }
+
+    // materials are matched by name (easy_gltf does not provide the material index)
+    pub fn get_material(&self, material_name: &Option<String>) -> Option<&Value>
+    {
+        let materials = self.json["materials"].as_array()?;
+
+        if let Some(material_name) = material_name
+        {
+            for material in materials
+            {
+                if material["name"].as_str() == Some(material_name.as_str())
+                {
+                    return Some(material);
+                }
+            }
+        }
+        else if materials.len() == 1
+        {
+            return materials.first();
+        }
+
+        None
+    }
+
+    pub fn get_material_extension(&self, material_name: &Option<String>, extension: &str) -> Option<&Value>
+    {
+        let material = self.get_material(material_name)?;
+        let extension = &material["extensions"][extension];
+
+        if extension.is_null()
+        {
+            return None;
+        }
+
+        Some(extension)
+    }
+
+    // cameras are matched by name like the materials
+    pub fn get_camera_extras(&self, camera_name: &Option<String>) -> Option<&Value>
+    {
+        let cameras = self.json["cameras"].as_array()?;
+        let camera_name = camera_name.as_ref()?;
+
+        for camera in cameras
+        {
+            if camera["name"].as_str() == Some(camera_name.as_str()) && camera["extras"].is_object()
+            {
+                return Some(&camera["extras"]);
+            }
+        }
+
+        None
+    }
+
+    // texture_info: gltf textureInfo object ({"index": ...}) of an extension
+    pub fn load_texture(&self, texture_info: &Value) -> Option<DynamicImage>
+    {
+        let texture_index = texture_info["index"].as_u64()? as usize;
+        let image_index = self.json["textures"][texture_index]["source"].as_u64()? as usize;
+
+        // images can be shared by multiple textures
+        if let Some(img) = self.images.borrow().get(&image_index)
+        {
+            return img.clone();
+        }
+
+        let img = self.decode_image(image_index);
+        self.images.borrow_mut().insert(image_index, img.clone());
+
+        img
+    }
+
+    // document and buffers (without decoding the images)
+    fn get_document(&self) -> Option<&(gltf::Document, Vec<gltf::buffer::Data>)>
+    {
+        self.document.get_or_init(||
+        {
+            let base = Path::new(&self.path).parent();
+
+            let gltf = match gltf::Gltf::open(&self.path)
+            {
+                Ok(gltf) => gltf,
+                Err(err) =>
+                {
+                    println!("error loading gltf document: {}", err);
+                    return None;
+                }
+            };
+
+            match gltf::import_buffers(&gltf.document, base, gltf.blob)
+            {
+                Ok(buffers) => Some((gltf.document, buffers)),
+                Err(err) =>
+                {
+                    println!("error loading gltf buffers: {}", err);
+                    None
+                }
+            }
+        }).as_ref()
+    }
+
+    fn decode_image(&self, image_index: usize) -> Option<DynamicImage>
+    {
+        let (document, buffers) = self.get_document()?;
+        let image = document.images().nth(image_index)?;
+
+        let data = match gltf::image::Data::from_source(image.source(), Path::new(&self.path).parent(), buffers)
+        {
+            Ok(data) => data,
+            Err(err) =>
+            {
+                println!("error loading gltf image {}: {}", image_index, err);
+                return None;
+            }
+        };
+
+        let channels = match data.format
+        {
+            gltf::image::Format::R8 => 1,
+            gltf::image::Format::R8G8 => 2,
+            gltf::image::Format::R8G8B8 => 3,
+            gltf::image::Format::R8G8B8A8 => 4,
+            _ =>
+            {
+                println!("unsupported gltf image format: {:?}", data.format);
+                return None;
+            }
+        };
+
+        let mut img: RgbaImage = ImageBuffer::new(data.width, data.height);
+        for y in 0..data.height
+        {
+            for x in 0..data.width
+            {
+                let i = ((y * data.width + x) as usize) * channels;
+                let pixel = &data.pixels[i..i + channels];
+
+                let rgba = match channels
+                {
+                    1 => [pixel[0], pixel[0], pixel[0], 255],
+                    2 => [pixel[0], pixel[1], 0, 255],
+                    3 => [pixel[0], pixel[1], pixel[2], 255],
+                    _ => [pixel[0], pixel[1], pixel[2], pixel[3]]
+                };
+
+                img.put_pixel(x, y, Rgba(rgba));
+            }
+        }
+
+        Some(DynamicImage::ImageRgba8(img))
+    }
+}
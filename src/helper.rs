@@ -1,3 +1,5 @@
+use std::f32::consts::PI;
+
 use nalgebra::{Vector3, Vector4};
 use rand::Rng;
 
@@ -58,4 +60,74 @@ pub fn plane_based_vector_angle(a1: Vector3<f32>, a2: Vector3<f32>) -> f32
     let det = a1.x*a2.y*n.z + a2.x*n.y*a1.z + n.x*a1.y*a2.z - a1.z*a2.y*n.x - a2.z*n.y*a1.x - n.z*a1.y*a2.x;
 
     det.atan2(dot)
-}
\ No newline at end of file
+}
+
+// https://graphics.pixar.com/library/OrthonormalB/paper.pdf
+pub fn onb(n: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>)
+{
+    let sign = 1.0f32.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+
+    let tangent = Vector3::<f32>::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+    let bitangent = Vector3::<f32>::new(b, sign + n.y * n.y * a, -n.y);
+
+    (tangent, bitangent)
+}
+
+// https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/2D_Sampling_with_Multidimensional_Transformations#Cosine-WeightedHemisphereSampling
+pub fn cosine_sample_hemisphere(normal: Vector3<f32>, u1: f32, u2: f32) -> Vector3<f32>
+{
+    let r = u1.sqrt();
+    let theta = 2.0 * PI * u2;
+
+    let x = r * theta.cos();
+    let y = r * theta.sin();
+    let z = (1.0 - u1).max(0.0).sqrt();
+
+    let (tangent, bitangent) = onb(normal);
+
+    (tangent * x + bitangent * y + normal * z).normalize()
+}
+
+// https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/2D_Sampling_with_Multidimensional_Transformations#SamplingaUnitDisk
+pub fn concentric_sample_disk(u1: f32, u2: f32) -> (f32, f32)
+{
+    let x = 2.0 * u1 - 1.0;
+    let y = 2.0 * u2 - 1.0;
+
+    if x == 0.0 && y == 0.0
+    {
+        return (0.0, 0.0);
+    }
+
+    let (r, theta) = if x.abs() > y.abs()
+    {
+        (x, (PI / 4.0) * (y / x))
+    }
+    else
+    {
+        (y, (PI / 2.0) - (PI / 4.0) * (x / y))
+    };
+
+    (r * theta.cos(), r * theta.sin())
+}
+
+// https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/Importance_Sampling#MultipleImportanceSampling
+pub fn power_heuristic(f_pdf: f32, g_pdf: f32) -> f32
+{
+    let f = f_pdf * f_pdf;
+    let g = g_pdf * g_pdf;
+
+    if f + g <= 0.0
+    {
+        return 0.0;
+    }
+
+    f / (f + g)
+}
+
+pub fn max_component(v: Vector3<f32>) -> f32
+{
+    v.x.max(v.y).max(v.z)
+}
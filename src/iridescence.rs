@@ -0,0 +1,169 @@
+use std::f32::consts::PI;
+
+use nalgebra::Vector3;
+
+use crate::spectrum::xyz_to_rgb;
+
+const OUTSIDE_IOR: f32 = 1.0;
+const MIN_THICKNESS: f32 = 0.03; //nm (the film fades out below)
+const MAX_BASE_F0: f32 = 0.9999;
+
+/*
+some resources:
+
+thin-film interference (Belcour and Barla 2017):
+https://belcour.github.io/blog/research/publication/2017/05/01/brdf-thin-film.html
+
+gltf iridescence and the reference implementation:
+https://github.com/KhronosGroup/glTF/tree/main/extensions/2.0/Khronos/KHR_materials_iridescence
+https://github.com/KhronosGroup/glTF-Sample-Viewer/blob/main/source/Renderer/shaders/iridescence.glsl
+*/
+
+// ******************** ThinFilm ********************
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThinFilm
+{
+    pub weight: f32, //0 = off
+    pub ior: f32,
+    pub thickness: f32 //nm
+}
+
+impl ThinFilm
+{
+    pub fn new() -> ThinFilm
+    {
+        ThinFilm
+        {
+            weight: 0.0,
+            ior: 1.3,
+            thickness: 0.0
+        }
+    }
+
+    pub fn is_active(&self) -> bool
+    {
+        self.weight > 0.0
+    }
+
+    // blends the fresnel term of the surface with the interference of the film
+    pub fn apply(&self, fresnel: Vector3<f32>, cos_theta: f32, base_f0: Vector3<f32>) -> Vector3<f32>
+    {
+        if !self.is_active()
+        {
+            return fresnel;
+        }
+
+        let iridescence = fresnel_iridescence(OUTSIDE_IOR, self.ior, cos_theta, self.thickness, base_f0);
+        let weight = self.weight.clamp(0.0, 1.0);
+
+        fresnel * (1.0 - weight) + iridescence * weight
+    }
+}
+
+fn ior_to_f0(transmitted_ior: f32, incident_ior: f32) -> f32
+{
+    ((transmitted_ior - incident_ior) / (transmitted_ior + incident_ior)).powi(2)
+}
+
+fn f0_to_ior(f0: f32) -> f32
+{
+    let sqrt_f0 = f0.clamp(0.0, MAX_BASE_F0).sqrt();
+
+    (1.0 + sqrt_f0) / (1.0 - sqrt_f0)
+}
+
+fn schlick(f0: f32, cos_theta: f32) -> f32
+{
+    f0 + (1.0 - f0) * (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+// fourier transform of the CIE color matching functions (opd in nm)
+fn eval_sensitivity(opd: f32, shift: Vector3<f32>) -> Vector3<f32>
+{
+    let phase = 2.0 * PI * opd * 1.0e-9;
+
+    let val = Vector3::<f32>::new(5.4856e-13, 4.4201e-13, 5.2481e-13);
+    let pos = Vector3::<f32>::new(1.6810e+06, 1.7953e+06, 2.2084e+06);
+    let var = Vector3::<f32>::new(4.3278e+09, 9.3046e+09, 6.6121e+09);
+
+    let mut xyz = Vector3::<f32>::zeros();
+    for i in 0..3
+    {
+        xyz[i] = val[i] * (2.0 * PI * var[i]).sqrt() * (pos[i] * phase + shift[i]).cos() * (-phase * phase * var[i]).exp();
+    }
+
+    xyz.x += 9.7470e-14 * (2.0 * PI * 4.5282e+09f32).sqrt() * (2.2399e+06 * phase + shift.x).cos() * (-4.5282e+09 * phase * phase).exp();
+    xyz /= 1.0685e-7;
+
+    xyz_to_rgb(&xyz)
+}
+
+// rgb fresnel reflectance of a thin film (thickness in nm) on top of a base with the reflectance base_f0
+pub fn fresnel_iridescence(outside_ior: f32, film_ior: f32, cos_theta1: f32, thickness: f32, base_f0: Vector3<f32>) -> Vector3<f32>
+{
+    // the film ior fades to the outside ior for a vanishing film
+    let t = (thickness / MIN_THICKNESS).clamp(0.0, 1.0);
+    let film_ior = outside_ior + (film_ior - outside_ior) * (t * t * (3.0 - 2.0 * t));
+
+    // angle inside of the film
+    let sin_theta2_sq = (outside_ior / film_ior).powi(2) * (1.0 - cos_theta1 * cos_theta1);
+    let cos_theta2_sq = 1.0 - sin_theta2_sq;
+
+    // total internal reflection
+    if cos_theta2_sq < 0.0
+    {
+        return Vector3::<f32>::new(1.0, 1.0, 1.0);
+    }
+
+    let cos_theta2 = cos_theta2_sq.sqrt();
+
+    // first interface
+    let r12 = schlick(ior_to_f0(film_ior, outside_ior), cos_theta1);
+    let t121 = 1.0 - r12;
+
+    let mut phi12 = 0.0;
+    if film_ior < outside_ior
+    {
+        phi12 = PI;
+    }
+    let phi21 = PI - phi12;
+
+    // second interface
+    let mut r23 = Vector3::<f32>::zeros();
+    let mut phi23 = Vector3::<f32>::zeros();
+    for i in 0..3
+    {
+        let base_ior = f0_to_ior(base_f0[i]);
+
+        r23[i] = schlick(ior_to_f0(base_ior, film_ior), cos_theta2);
+
+        if base_ior < film_ior
+        {
+            phi23[i] = PI;
+        }
+    }
+
+    // phase shift
+    let opd = 2.0 * film_ior * thickness * cos_theta2;
+    let phi = phi23.add_scalar(phi21);
+
+    // compound terms
+    let r123 = (r23 * r12).map(|r| r.clamp(1e-5, 0.9999));
+    let r123_sqrt = r123.map(|r| r.sqrt());
+    let rs = (r23 * (t121 * t121)).component_div(&r123.map(|r| 1.0 - r));
+
+    // reflectance term for m = 0 (dc term amplitude)
+    let mut intensity = rs.add_scalar(r12);
+
+    // reflectance terms for m > 0 (pairs of diracs)
+    let mut cm = rs.add_scalar(-t121);
+    for m in 1..=2
+    {
+        cm = cm.component_mul(&r123_sqrt);
+        let sm = eval_sensitivity(m as f32 * opd, phi * (m as f32)) * 2.0;
+        intensity += cm.component_mul(&sm);
+    }
+
+    // out of gamut colors
+    intensity.map(|c| c.max(0.0))
+}
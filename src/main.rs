@@ -1,12 +1,29 @@
 use egui::{Style, Visuals};
 use regex::Regex;
 use run::Run;
+use raytracing::IntegratorType;
+use debug_render::RenderMode;
+use sampler::SamplerType;
 
 pub mod helper;
 pub mod shape;
 
 pub mod renderer;
 pub mod raytracing;
+pub mod sampler;
+pub mod path_tracing;
+pub mod bidirectional;
+pub mod debug_render;
+pub mod bsdf;
+pub mod iridescence;
+pub mod emitter;
+pub mod gltf_extensions;
+pub mod environment;
+pub mod sky;
+pub mod medium;
+pub mod subsurface;
+pub mod spectrum;
+pub mod photon_map;
 pub mod scene;
 pub mod camera;
 pub mod animation;
@@ -23,7 +40,17 @@ fn main()
     let mut width = 0;
     let mut height = 0;
     let mut monte_carlo = None;
+    let mut spectral = None;
+    let mut photons = None;
+    let mut integrator = None;
+    let mut render_mode = None;
     let mut samples = None;
+    let mut sampler = None;
+    let mut adaptive_threshold = None;
+    let mut progressive = None;
+    let mut time_limit = None;
+    let mut denoise = None;
+    let mut camera = None;
     let mut start = false;
 
     let res_regex = Regex::new(r"^\d+x\d+$").unwrap(); // example: 800x600
@@ -45,6 +72,44 @@ fn main()
 
             monte_carlo = Some(splits_arr[1] == "1" || splits_arr[1] == "true");
         }
+        else if arg.starts_with("spectral=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            spectral = Some(splits_arr[1] == "1" || splits_arr[1] == "true");
+        }
+        else if arg.starts_with("photons=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            photons = Some(splits_arr[1].parse().unwrap());
+        }
+        else if arg.starts_with("integrator=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            integrator = IntegratorType::from_str(splits_arr[1]);
+
+            if integrator.is_none()
+            {
+                println!("unknown integrator: {}", splits_arr[1]);
+            }
+        }
+        else if arg.starts_with("render_mode=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            render_mode = RenderMode::from_str(splits_arr[1]);
+
+            if render_mode.is_none()
+            {
+                println!("unknown render mode: {}", splits_arr[1]);
+            }
+        }
         else if arg.ends_with(".json") || arg.ends_with(".gltf") || arg.ends_with(".glb") || arg.ends_with(".obj")
         {
             scenes.push(arg);
@@ -64,6 +129,53 @@ fn main()
 
             samples = Some(splits_arr[1].parse().unwrap());
         }
+        else if arg.starts_with("sampler=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            sampler = SamplerType::from_str(splits_arr[1]);
+
+            if sampler.is_none()
+            {
+                println!("unknown sampler: {}", splits_arr[1]);
+            }
+        }
+        else if arg.starts_with("adaptive=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            adaptive_threshold = Some(splits_arr[1].parse().unwrap());
+        }
+        else if arg.starts_with("progressive=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            progressive = Some(splits_arr[1] == "1" || splits_arr[1] == "true");
+        }
+        else if arg.starts_with("time_limit=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            time_limit = Some(splits_arr[1].parse().unwrap());
+        }
+        else if arg.starts_with("denoise=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            denoise = Some(splits_arr[1] == "1" || splits_arr[1] == "true");
+        }
+        else if arg.starts_with("camera=")
+        {
+            let splits: Vec<&str> = arg.split("=").collect();
+            let splits_arr = splits.as_slice();
+
+            camera = Some(splits_arr[1].to_string());
+        }
         else if arg.starts_with("start=")
         {
             let splits: Vec<&str> = arg.split("=").collect();
@@ -74,12 +186,22 @@ fn main()
     }
 
     let mut runner = Run::new(width, height, window, scenes, animation, start);
+    runner.set_camera_selection(camera);
 
     //apply cmd settings
     {
         let rt = runner.raytracing.write().unwrap();
+        if let Some(integrator) = integrator { rt.scene.write().unwrap().raytracing_config.integrator = integrator; }
+        if let Some(render_mode) = render_mode { rt.scene.write().unwrap().raytracing_config.render_mode = render_mode; }
         if let Some(monte_carlo) = monte_carlo { rt.scene.write().unwrap().raytracing_config.monte_carlo = monte_carlo; }
+        if let Some(spectral) = spectral { rt.scene.write().unwrap().raytracing_config.spectral = spectral; }
         if let Some(samples) = samples { rt.scene.write().unwrap().raytracing_config.samples = samples; }
+        if let Some(sampler) = sampler { rt.scene.write().unwrap().raytracing_config.sampler = sampler; }
+        if let Some(adaptive_threshold) = adaptive_threshold { rt.scene.write().unwrap().raytracing_config.adaptive_threshold = adaptive_threshold; }
+        if let Some(progressive) = progressive { rt.scene.write().unwrap().raytracing_config.progressive = progressive; }
+        if let Some(time_limit) = time_limit { rt.scene.write().unwrap().raytracing_config.time_limit = time_limit; }
+        if let Some(photons) = photons { rt.scene.write().unwrap().raytracing_config.photon_count = photons; }
+        if let Some(denoise) = denoise { rt.scene.write().unwrap().post_processing.denoise = denoise; }
     }
 
     runner.init();
@@ -0,0 +1,592 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::sync::Arc;
+
+use nalgebra::{Matrix4, Point3, Vector3};
+use parry3d::bounding_volume::Aabb;
+
+use crate::helper::onb;
+
+const MAX_MARCH_STEPS: usize = 1024;
+const MIN_STEP_SIZE: f32 = 0.001;
+
+const HG_ISOTROPIC_EPSILON: f32 = 0.001;
+const MAX_ANISOTROPY: f32 = 0.99;
+
+/*
+some resources:
+
+volume scattering and the henyey-greenstein phase function:
+https://www.pbr-book.org/3ed-2018/Volume_Scattering/Phase_Functions
+
+distance sampling (free flight) and transmittance:
+https://www.pbr-book.org/3ed-2018/Light_Transport_II_Volume_Rendering/Sampling_Volume_Scattering
+
+chromatic media: the distance is sampled based on the averaged extinction, the rgb difference is part of the sample weight
+*/
+
+// ******************** DensityType ********************
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DensityType
+{
+    Homogeneous,
+    Grid,
+    Noise
+}
+
+impl DensityType
+{
+    pub fn from_str(name: &str) -> Option<DensityType>
+    {
+        match name.to_lowercase().as_str()
+        {
+            "homogeneous" | "constant" => Some(DensityType::Homogeneous),
+            "grid" | "voxel" => Some(DensityType::Grid),
+            "noise" => Some(DensityType::Noise),
+            _ => None
+        }
+    }
+}
+
+// ******************** DensityGrid ********************
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityGrid
+{
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+
+    pub data: Arc<Vec<f32>> // x first, then y, then z
+}
+
+impl DensityGrid
+{
+    pub fn new(width: usize, height: usize, depth: usize, data: Vec<f32>) -> Option<DensityGrid>
+    {
+        if width == 0 || height == 0 || depth == 0 || data.len() != width * height * depth
+        {
+            println!("invalid density grid: {}x{}x{} with {} values", width, height, depth, data.len());
+            return None;
+        }
+
+        Some(DensityGrid
+        {
+            width: width,
+            height: height,
+            depth: depth,
+
+            data: Arc::new(data)
+        })
+    }
+
+    // raw little endian f32 values
+    pub fn load(path: &str, width: usize, height: usize, depth: usize) -> Option<DensityGrid>
+    {
+        let bytes = fs::read(path);
+
+        if let Err(err) = bytes
+        {
+            println!("could not load density grid {}: {}", path, err);
+            return None;
+        }
+
+        let data = bytes.unwrap().chunks_exact(4).map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])).collect();
+
+        DensityGrid::new(width, height, depth, data)
+    }
+
+    fn get(&self, x: usize, y: usize, z: usize) -> f32
+    {
+        self.data[(z * self.height + y) * self.width + x]
+    }
+
+    // trilinear interpolation -- uvw in the range of 0..1
+    pub fn eval(&self, u: f32, v: f32, w: f32) -> f32
+    {
+        if u < 0.0 || v < 0.0 || w < 0.0 || u > 1.0 || v > 1.0 || w > 1.0
+        {
+            return 0.0;
+        }
+
+        let x = (u * self.width as f32 - 0.5).clamp(0.0, (self.width - 1) as f32);
+        let y = (v * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let z = (w * self.depth as f32 - 0.5).clamp(0.0, (self.depth - 1) as f32);
+
+        let x0 = x as usize;
+        let y0 = y as usize;
+        let z0 = z as usize;
+
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let z1 = (z0 + 1).min(self.depth - 1);
+
+        let fx = x - x0 as f32;
+        let fy = y - y0 as f32;
+        let fz = z - z0 as f32;
+
+        let c00 = self.get(x0, y0, z0) * (1.0 - fx) + self.get(x1, y0, z0) * fx;
+        let c10 = self.get(x0, y1, z0) * (1.0 - fx) + self.get(x1, y1, z0) * fx;
+        let c01 = self.get(x0, y0, z1) * (1.0 - fx) + self.get(x1, y0, z1) * fx;
+        let c11 = self.get(x0, y1, z1) * (1.0 - fx) + self.get(x1, y1, z1) * fx;
+
+        let c0 = c00 * (1.0 - fy) + c10 * fy;
+        let c1 = c01 * (1.0 - fy) + c11 * fy;
+
+        c0 * (1.0 - fz) + c1 * fz
+    }
+}
+
+// ******************** Medium ********************
+#[derive(Clone, Debug, PartialEq)]
+pub struct Medium
+{
+    pub enabled: bool,
+
+    // coefficients per unit length
+    pub absorption: Vector3<f32>,
+    pub scattering: Vector3<f32>,
+
+    pub anisotropy: f32, // henyey-greenstein g (-1 = backward, 0 = isotropic, 1 = forward)
+    pub density: f32,
+
+    // heterogeneous density
+    pub density_type: DensityType,
+    pub noise_scale: f32,
+    pub noise_octaves: u32,
+    pub grid: Option<DensityGrid>, // spans the (local) bounding box of the object
+    pub step_size: f32
+}
+
+impl Medium
+{
+    pub fn new() -> Medium
+    {
+        Medium
+        {
+            enabled: false,
+
+            absorption: Vector3::<f32>::new(0.05, 0.05, 0.05),
+            scattering: Vector3::<f32>::new(0.5, 0.5, 0.5),
+
+            anisotropy: 0.0,
+            density: 1.0,
+
+            density_type: DensityType::Homogeneous,
+            noise_scale: 1.0,
+            noise_octaves: 4,
+            grid: None,
+            step_size: 0.05
+        }
+    }
+
+    pub fn new_homogeneous(absorption: Vector3<f32>, scattering: Vector3<f32>, anisotropy: f32) -> Medium
+    {
+        let mut medium = Medium::new();
+        medium.enabled = true;
+        medium.absorption = absorption;
+        medium.scattering = scattering;
+        medium.anisotropy = anisotropy;
+
+        medium
+    }
+
+    pub fn is_homogeneous(&self) -> bool
+    {
+        self.density_type == DensityType::Homogeneous || (self.density_type == DensityType::Grid && self.grid.is_none())
+    }
+
+    pub fn is_active(&self) -> bool
+    {
+        self.enabled && self.density > 0.0 && (self.absorption + self.scattering).max() > 0.0
+    }
+
+    pub fn print(&self)
+    {
+        println!("medium enabled: {:?}", self.enabled);
+        println!("medium absorption: {:?}", self.absorption);
+        println!("medium scattering: {:?}", self.scattering);
+        println!("medium anisotropy: {:?}", self.anisotropy);
+        println!("medium density: {:?}", self.density);
+        println!("medium density_type: {:?}", self.density_type);
+        println!("medium noise_scale: {:?}", self.noise_scale);
+        println!("medium noise_octaves: {:?}", self.noise_octaves);
+        println!("medium grid: {:?}", self.grid.is_some());
+        println!("medium step_size: {:?}", self.step_size);
+    }
+}
+
+// ******************** MediumSample ********************
+pub struct MediumSample
+{
+    pub scattered: bool,
+    pub dist: f32,
+    pub weight: Vector3<f32> // transmittance (and scattering coefficient) divided by the pdf
+}
+
+// ******************** MediumInstance ********************
+// a medium placed into the scene: the global medium or the interior of an object
+#[derive(Clone, Debug)]
+pub struct MediumInstance
+{
+    pub medium: Medium,
+    pub item_id: Option<u32>,
+
+    to_local: Matrix4<f32>,
+    b_box: Aabb
+}
+
+impl MediumInstance
+{
+    // the global medium fills the scene bounds (otherwise distant lights and the environment would be fully absorbed)
+    pub fn new_global(medium: Medium, scene_b_box: Aabb) -> MediumInstance
+    {
+        MediumInstance
+        {
+            medium: medium,
+            item_id: None,
+
+            to_local: Matrix4::<f32>::identity(),
+            b_box: scene_b_box
+        }
+    }
+
+    pub fn new_interior(medium: Medium, item_id: u32, to_local: Matrix4<f32>, b_box: Aabb) -> MediumInstance
+    {
+        MediumInstance
+        {
+            medium: medium,
+            item_id: Some(item_id),
+
+            to_local: to_local,
+            b_box: b_box
+        }
+    }
+
+    pub fn get_density(&self, point: &Point3<f32>) -> f32
+    {
+        let medium = &self.medium;
+
+        if medium.is_homogeneous()
+        {
+            return medium.density;
+        }
+
+        let local = Point3::from_homogeneous(self.to_local * point.to_homogeneous()).unwrap();
+
+        let density = match medium.density_type
+        {
+            DensityType::Noise => fbm_noise(&(local.coords * medium.noise_scale), medium.noise_octaves),
+            DensityType::Grid =>
+            {
+                let size = self.b_box.maxs - self.b_box.mins;
+                let uvw = (local - self.b_box.mins).component_div(&size);
+
+                medium.grid.as_ref().unwrap().eval(uvw.x, uvw.y, uvw.z)
+            },
+            DensityType::Homogeneous => 1.0
+        };
+
+        density.max(0.0) * medium.density
+    }
+
+    // limits the segment to the scene bounds (only for the global medium)
+    fn get_max_dist(&self, origin: &Point3<f32>, dir: &Vector3<f32>, max_dist: f32) -> f32
+    {
+        if self.item_id.is_some() || self.b_box.mins.x > self.b_box.maxs.x
+        {
+            return max_dist;
+        }
+
+        let mut exit_dist = max_dist;
+
+        for i in 0..3
+        {
+            if dir[i] == 0.0
+            {
+                continue;
+            }
+
+            let t1 = (self.b_box.mins[i] - origin[i]) / dir[i];
+            let t2 = (self.b_box.maxs[i] - origin[i]) / dir[i];
+
+            exit_dist = exit_dist.min(t1.max(t2));
+        }
+
+        exit_dist.max(0.0)
+    }
+
+    fn get_extinction(&self, density: f32) -> Vector3<f32>
+    {
+        (self.medium.absorption + self.medium.scattering) * density
+    }
+
+    fn get_step_size(&self) -> f32
+    {
+        self.medium.step_size.max(MIN_STEP_SIZE)
+    }
+
+    // transmittance along the ray up to max_dist (jitter: 0..1 offset of the ray marching steps)
+    pub fn transmittance(&self, origin: &Point3<f32>, dir: &Vector3<f32>, max_dist: f32, jitter: f32) -> Vector3<f32>
+    {
+        let max_dist = self.get_max_dist(origin, dir, max_dist);
+
+        if self.medium.is_homogeneous()
+        {
+            let sigma_t = self.get_extinction(self.medium.density);
+
+            return sigma_t.map(|sigma| (-sigma * max_dist).exp());
+        }
+
+        let mut optical_depth = Vector3::<f32>::zeros();
+
+        self.march(origin, dir, max_dist, jitter, |start, end, density|
+        {
+            optical_depth += self.get_extinction(density) * (end - start);
+            true
+        });
+
+        optical_depth.map(|tau| (-tau).exp())
+    }
+
+    // free flight distance sampling -- scatters inside the segment or passes it
+    pub fn sample(&self, origin: &Point3<f32>, dir: &Vector3<f32>, max_dist: f32, u: f32, jitter: f32) -> MediumSample
+    {
+        // pure absorbers (beer-lambert) are resolved analytically
+        if self.medium.scattering.max() <= 0.0
+        {
+            return MediumSample { scattered: false, dist: max_dist, weight: self.transmittance(origin, dir, max_dist, jitter) };
+        }
+
+        let segment_dist = self.get_max_dist(origin, dir, max_dist);
+
+        // sampled optical depth (based on the averaged extinction)
+        let target = -(1.0 - u).max(std::f32::MIN_POSITIVE).ln();
+
+        if self.medium.is_homogeneous()
+        {
+            let sigma_t = self.get_extinction(self.medium.density);
+            let sigma_avg = sigma_t.mean();
+
+            if sigma_avg <= 0.0
+            {
+                return MediumSample { scattered: false, dist: max_dist, weight: Vector3::<f32>::new(1.0, 1.0, 1.0) };
+            }
+
+            let dist = target / sigma_avg;
+
+            if dist < segment_dist
+            {
+                // sigma_s * T(dist) / (sigma_avg * exp(-sigma_avg * dist))
+                let weight = (self.medium.scattering * self.medium.density).component_mul(&(sigma_t - Vector3::<f32>::repeat(sigma_avg)).map(|sigma| (-sigma * dist).exp())) / sigma_avg;
+
+                return MediumSample { scattered: true, dist: dist, weight: weight };
+            }
+
+            let weight = (sigma_t - Vector3::<f32>::repeat(sigma_avg)).map(|sigma| (-sigma * segment_dist).exp());
+
+            return MediumSample { scattered: false, dist: max_dist, weight: weight };
+        }
+
+        let mut optical_depth = Vector3::<f32>::zeros();
+        let mut optical_depth_avg = 0.0;
+        let mut result = None;
+
+        self.march(origin, dir, segment_dist, jitter, |start, end, density|
+        {
+            let sigma_t = self.get_extinction(density);
+            let sigma_avg = sigma_t.mean();
+
+            if sigma_avg > 0.0 && optical_depth_avg + sigma_avg * (end - start) >= target
+            {
+                let dist = start + (target - optical_depth_avg) / sigma_avg;
+                optical_depth += sigma_t * (dist - start);
+
+                let transmittance = optical_depth.map(|tau| (target - tau).exp());
+                let weight = (self.medium.scattering * density).component_mul(&transmittance) / sigma_avg;
+
+                result = Some(MediumSample { scattered: true, dist: dist, weight: weight });
+                return false;
+            }
+
+            optical_depth += sigma_t * (end - start);
+            optical_depth_avg += sigma_avg * (end - start);
+            true
+        });
+
+        if let Some(result) = result
+        {
+            return result;
+        }
+
+        let weight = optical_depth.map(|tau| (optical_depth_avg - tau).exp());
+
+        MediumSample { scattered: false, dist: max_dist, weight: weight }
+    }
+
+    // one scattering point inside of the segment (used for single scattering)
+    // the weight contains the transmittance up to the point and the scattering coefficient divided by the pdf
+    pub fn sample_scattering(&self, origin: &Point3<f32>, dir: &Vector3<f32>, max_dist: f32, u: f32, jitter: f32) -> Option<(f32, Vector3<f32>)>
+    {
+        if self.medium.scattering.max() <= 0.0
+        {
+            return None;
+        }
+
+        if self.medium.is_homogeneous()
+        {
+            let max_dist = self.get_max_dist(origin, dir, max_dist);
+
+            let sigma_t = self.get_extinction(self.medium.density);
+            let sigma_avg = sigma_t.mean();
+
+            if sigma_avg <= 0.0
+            {
+                return None;
+            }
+
+            // truncated exponential distribution --> the point is always inside of the segment
+            let segment_probability = 1.0 - (-sigma_avg * max_dist).exp();
+            if segment_probability <= 0.0
+            {
+                return None;
+            }
+
+            let dist = -(1.0 - u * segment_probability).ln() / sigma_avg;
+
+            let weight = (self.medium.scattering * self.medium.density).component_mul(&(sigma_t - Vector3::<f32>::repeat(sigma_avg)).map(|sigma| (-sigma * dist).exp())) * (segment_probability / sigma_avg);
+
+            return Some((dist, weight));
+        }
+
+        let medium_sample = self.sample(origin, dir, max_dist, u, jitter);
+
+        if !medium_sample.scattered
+        {
+            return None;
+        }
+
+        Some((medium_sample.dist, medium_sample.weight))
+    }
+
+    // ray marching with (jittered) fixed steps -- the density is evaluated in the middle of each step
+    fn march<F>(&self, origin: &Point3<f32>, dir: &Vector3<f32>, max_dist: f32, jitter: f32, mut step: F) where F: FnMut(f32, f32, f32) -> bool
+    {
+        //long segments are marched with larger steps instead of being cut off
+        let step_size = self.get_step_size().max(max_dist / MAX_MARCH_STEPS as f32);
+
+        let mut start = 0.0;
+        let mut end = step_size * jitter.clamp(0.01, 1.0);
+
+        while start < max_dist
+        {
+            end = end.min(max_dist);
+
+            let point = origin + dir * ((start + end) * 0.5);
+            let density = self.get_density(&point);
+
+            if !step(start, end, density)
+            {
+                return;
+            }
+
+            start = end;
+            end += step_size;
+        }
+    }
+}
+
+// ******************** phase function ********************
+
+// cos_theta: angle between the ray direction and the scattered direction
+pub fn henyey_greenstein(cos_theta: f32, g: f32) -> f32
+{
+    let g = g.clamp(-MAX_ANISOTROPY, MAX_ANISOTROPY);
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+
+    (1.0 - g * g) / (4.0 * PI * denom * denom.max(0.0).sqrt())
+}
+
+pub fn sample_henyey_greenstein(dir: Vector3<f32>, g: f32, u1: f32, u2: f32) -> Vector3<f32>
+{
+    let g = g.clamp(-MAX_ANISOTROPY, MAX_ANISOTROPY);
+
+    let cos_theta;
+    if g.abs() < HG_ISOTROPIC_EPSILON
+    {
+        cos_theta = 1.0 - 2.0 * u1;
+    }
+    else
+    {
+        let sqr_term = (1.0 - g * g) / (1.0 - g + 2.0 * g * u1);
+        cos_theta = ((1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)).clamp(-1.0, 1.0);
+    }
+
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+
+    let (tangent, bitangent) = onb(dir);
+
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + dir * cos_theta).normalize()
+}
+
+// ******************** noise ********************
+
+fn hash(x: i32, y: i32, z: i32) -> f32
+{
+    let mut h = (x as u32).wrapping_mul(0x8da6b343) ^ (y as u32).wrapping_mul(0xd8163841) ^ (z as u32).wrapping_mul(0xcb1ab31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1e995);
+    h ^= h >> 15;
+
+    (h & 0x00ffffff) as f32 / 0x00ffffff as f32
+}
+
+fn smoothstep(t: f32) -> f32
+{
+    t * t * (3.0 - 2.0 * t)
+}
+
+// value noise in the range of 0..1
+pub fn value_noise(p: &Vector3<f32>) -> f32
+{
+    let base = p.map(|v| v.floor());
+    let f = p - base;
+
+    let x = base.x as i32;
+    let y = base.y as i32;
+    let z = base.z as i32;
+
+    let u = smoothstep(f.x);
+    let v = smoothstep(f.y);
+    let w = smoothstep(f.z);
+
+    let c00 = hash(x, y, z) * (1.0 - u) + hash(x + 1, y, z) * u;
+    let c10 = hash(x, y + 1, z) * (1.0 - u) + hash(x + 1, y + 1, z) * u;
+    let c01 = hash(x, y, z + 1) * (1.0 - u) + hash(x + 1, y, z + 1) * u;
+    let c11 = hash(x, y + 1, z + 1) * (1.0 - u) + hash(x + 1, y + 1, z + 1) * u;
+
+    let c0 = c00 * (1.0 - v) + c10 * v;
+    let c1 = c01 * (1.0 - v) + c11 * v;
+
+    c0 * (1.0 - w) + c1 * w
+}
+
+// fractal brownian motion in the range of 0..1
+pub fn fbm_noise(p: &Vector3<f32>, octaves: u32) -> f32
+{
+    let mut value = 0.0;
+    let mut amplitude = 0.5;
+    let mut total_amplitude = 0.0;
+    let mut frequency = 1.0;
+
+    for _ in 0..octaves.max(1)
+    {
+        value += value_noise(&(p * frequency)) * amplitude;
+        total_amplitude += amplitude;
+
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    value / total_amplitude
+}
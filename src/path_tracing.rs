@@ -0,0 +1,941 @@
+use std::f32::consts::PI;
+
+use nalgebra::{Point3, Vector3};
+use parry3d::query::Ray;
+
+use crate::bsdf::Bsdf;
+use crate::helper::{power_heuristic, max_component};
+use crate::medium::{MediumInstance, henyey_greenstein, sample_henyey_greenstein};
+use crate::raytracing::{Raytracing, RaytracingConfig, SurfaceInteraction, LightningColorType, RayType, SHADOW_BIAS};
+use crate::scene::{Scene, Light, LightType};
+use crate::shape::Shape;
+use crate::subsurface::get_subsurface_exit_bsdf;
+use crate::sampler::{sample_1d, sample_2d};
+use crate::spectrum::SpectralSample;
+
+const RUSSIAN_ROULETTE_MIN_BOUNCES: u16 = 3;
+const RUSSIAN_ROULETTE_MIN_PROBABILITY: f32 = 0.05;
+const RUSSIAN_ROULETTE_MAX_PROBABILITY: f32 = 0.95;
+
+const MAX_SHADOW_TRANSMISSIONS: u16 = 8;
+
+const EMITTER_SHADOW_EPSILON: f32 = 0.001;
+
+/*
+some resources:
+
+path tracing in general:
+https://www.pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Path_Tracing
+
+next event estimation and multiple importance sampling:
+https://www.pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Direct_Lighting
+https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/Importance_Sampling#MultipleImportanceSampling
+
+russian roulette:
+https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/Russian_Roulette_and_Splitting
+
+volumetric path tracing:
+https://www.pbr-book.org/3ed-2018/Light_Transport_II_Volume_Rendering/Volumetric_Light_Transport
+
+subsurface scattering: see subsurface.rs
+*/
+
+// ******************** LightSample ********************
+pub struct LightSample
+{
+    pub dir: Vector3<f32>,
+    pub dist: f32,
+    pub radiance: Vector3<f32>,
+    pub pdf: f32,
+    pub is_delta: bool
+}
+
+// ******************** Scatter ********************
+// surface bsdf or phase function of a medium (used for light sampling)
+pub enum Scatter<'a>
+{
+    Surface { bsdf: &'a Bsdf, n: Vector3<f32>, wo: Vector3<f32> },
+    Medium { anisotropy: f32, dir: Vector3<f32> }
+}
+
+impl<'a> Scatter<'a>
+{
+    // including the cosine term for surfaces
+    pub fn eval(&self, wi: Vector3<f32>) -> Vector3<f32>
+    {
+        match self
+        {
+            Scatter::Surface { bsdf, n, wo } =>
+            {
+                let cos = n.dot(&wi);
+                if cos <= 0.0
+                {
+                    return Vector3::<f32>::zeros();
+                }
+
+                bsdf.eval(*n, *wo, wi) * cos
+            },
+            Scatter::Medium { anisotropy, dir } =>
+            {
+                Vector3::<f32>::repeat(henyey_greenstein(dir.dot(&wi), *anisotropy))
+            }
+        }
+    }
+
+    pub fn pdf(&self, wi: Vector3<f32>) -> f32
+    {
+        match self
+        {
+            Scatter::Surface { bsdf, n, wo } => bsdf.pdf(*n, *wo, wi),
+            Scatter::Medium { anisotropy, dir } => henyey_greenstein(dir.dot(&wi), *anisotropy)
+        }
+    }
+
+    // shadow ray origin
+    pub fn get_origin(&self, point: &Point3<f32>) -> Point3<f32>
+    {
+        match self
+        {
+            Scatter::Surface { n, .. } => point + (n * SHADOW_BIAS),
+            Scatter::Medium { .. } => *point
+        }
+    }
+}
+
+// ******************** PathLobe ********************
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum PathLobe
+{
+    Surface,
+    Reflection,
+    Transmission
+}
+
+// ******************** BounceType ********************
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum BounceType
+{
+    Diffuse,
+    Glossy,
+    Transmission
+}
+
+// ******************** BounceCounter ********************
+// bounces per path type (the total is limited by max_recursion)
+pub struct BounceCounter
+{
+    pub diffuse: u16,
+    pub glossy: u16,
+    pub transmission: u16
+}
+
+impl BounceCounter
+{
+    pub fn new() -> BounceCounter
+    {
+        BounceCounter
+        {
+            diffuse: 0,
+            glossy: 0,
+            transmission: 0
+        }
+    }
+
+    // returns false if the limit of the bounce type is exceeded
+    pub fn add(&mut self, bounce_type: BounceType, config: &RaytracingConfig) -> bool
+    {
+        match bounce_type
+        {
+            BounceType::Diffuse =>
+            {
+                self.diffuse += 1;
+                self.diffuse <= config.max_diffuse_bounces
+            },
+            BounceType::Glossy =>
+            {
+                self.glossy += 1;
+                self.glossy <= config.max_glossy_bounces
+            },
+            BounceType::Transmission =>
+            {
+                self.transmission += 1;
+                self.transmission <= config.max_transmission_bounces
+            }
+        }
+    }
+}
+
+impl Raytracing
+{
+    pub fn get_color_depth_normal_id_path_tracing(&self, scene: &Scene, ray: Ray, wavelength: &SpectralSample) -> (Vector3<f32>, f32, Vector3<f32>, u32, Vector3<f32>)
+    {
+        let mut r = ray;
+        r.dir = r.dir.normalize();
+
+        let mut out_depth: f32 = 0.0;
+        let mut out_normal = Vector3::zeros();
+        let mut out_id: u32 = 0;
+        let mut out_albedo = Vector3::zeros();
+        let mut aov_pending = true;
+
+        let mut radiance = Vector3::<f32>::zeros();
+        let mut throughput = Vector3::<f32>::new(1.0, 1.0, 1.0);
+
+        // camera rays and specular bounces can not be importance sampled by the light --> no MIS for them
+        let mut specular_bounce = true;
+        let mut last_bsdf_pdf = 0.0;
+
+        // caustic paths (diffuse --> specular --> light) are covered by the photon map
+        let use_photon_map = !self.photon_map.is_empty();
+        let mut after_diffuse = false;
+
+        // the medium the ray is currently travelling through
+        let mut medium = self.get_global_medium(scene);
+
+        let mut bounce: u16 = 0;
+        let mut bounces = BounceCounter::new();
+
+        // ray type of the current path segment (per object ray visibility)
+        let mut ray_type = RayType::Camera;
+
+        // the object the current path segment starts from (light linking of area light hits)
+        let mut last_item: Option<&dyn Shape> = None;
+
+        loop
+        {
+            let intersection = self.trace(&scene, &r, false, ray_type, bounce + 1);
+
+            let mut max_dist = std::f32::MAX;
+            if let Some(intersection) = &intersection
+            {
+                max_dist = intersection.0;
+            }
+
+            // ********** participating media **********
+            if let Some(current_medium) = &medium
+            {
+                let (u1, u2) = sample_2d();
+                let medium_sample = current_medium.sample(&r.origin, &r.dir, max_dist, u1, u2);
+                throughput = throughput.component_mul(&medium_sample.weight);
+
+                if medium_sample.scattered
+                {
+                    if bounce >= self.config.max_recursion
+                    {
+                        break;
+                    }
+
+                    let scatter_point = r.origin + (r.dir * medium_sample.dist);
+                    let anisotropy = current_medium.medium.anisotropy;
+
+                    // next event estimation
+                    let scatter = Scatter::Medium { anisotropy: anisotropy, dir: r.dir };
+                    let direct = self.sample_direct_lighting(scene, &scatter, &scatter_point, None, &medium, bounce + 1);
+                    radiance += self.clamp_indirect(throughput.component_mul(&direct), bounce >= 1);
+
+                    // volume scattering is limited like diffuse bounces
+                    if !bounces.add(BounceType::Diffuse, &self.config)
+                    {
+                        break;
+                    }
+
+                    // phase function sampling (the sample weight is exactly one)
+                    let (u1, u2) = sample_2d();
+                    let dir = sample_henyey_greenstein(r.dir, anisotropy, u1, u2);
+
+                    last_bsdf_pdf = henyey_greenstein(r.dir.dot(&dir), anisotropy);
+                    specular_bounce = false;
+                    after_diffuse = false;
+                    ray_type = RayType::Indirect;
+                    last_item = None;
+
+                    r = Ray::new(scatter_point, dir);
+                    bounce += 1;
+
+                    if !self.apply_russian_roulette(&mut throughput, bounce)
+                    {
+                        break;
+                    }
+
+                    continue;
+                }
+            }
+
+            // ********** area lights **********
+            // (they are not part of the scene geometry --> check them separately)
+
+            if !(use_photon_map && after_diffuse && specular_bounce)
+            {
+                let area_light_emission = self.get_area_light_emission(scene, &r, max_dist, last_item, specular_bounce, last_bsdf_pdf);
+                radiance += self.clamp_indirect(throughput.component_mul(&area_light_emission), bounce >= 2);
+            }
+
+            if intersection.is_none()
+            {
+                // ********** environment **********
+                if scene.environment.is_active()
+                {
+                    let mut weight = 1.0;
+
+                    if !specular_bounce
+                    {
+                        weight = power_heuristic(last_bsdf_pdf, scene.environment.pdf(&r.dir));
+                    }
+
+                    radiance += self.clamp_indirect(throughput.component_mul(&scene.environment.eval(&r.dir)) * weight, bounce >= 2);
+                }
+
+                break;
+            }
+
+            let intersection = intersection.unwrap();
+
+            let hit_dist = intersection.0;
+            let normal = intersection.1;
+            let item = intersection.2;
+            let face_id = intersection.3;
+            let material = item.get_material().read().unwrap();
+
+            let mut surface = self.get_surface_interaction(&r, hit_dist, normal, item, face_id, &material);
+            surface.refraction_index = material.get_refraction_index(wavelength);
+
+            //AOVs (fully transparent surfaces are passing them to the next hit)
+            if aov_pending
+            {
+                out_depth = hit_dist;
+                out_normal = normal;
+                out_id = item.get_basic().id;
+                out_albedo = surface.base_color;
+
+                aov_pending = surface.alpha <= 0.0;
+            }
+
+            // ********** emission **********
+            if surface.emission.magnitude_squared() > 0.0
+            {
+                let mut weight = 1.0;
+
+                if !specular_bounce
+                {
+                    let light_pdf = self.get_emitter_pdf(scene, &r, hit_dist, normal, item.get_basic().id, face_id);
+                    weight = power_heuristic(last_bsdf_pdf, light_pdf);
+                }
+
+                radiance += self.clamp_indirect(throughput.component_mul(&surface.emission) * weight, bounce >= 2);
+            }
+
+            if bounce >= self.config.max_recursion
+            {
+                break;
+            }
+
+            // ********** choose lobe **********
+            let lobe = self.choose_lobe(&surface);
+
+            let wo = -r.dir;
+
+            match lobe
+            {
+                PathLobe::Surface =>
+                {
+                    // shading normal on the side of the viewer
+                    let mut n = surface.surface_normal;
+                    if n.dot(&wo) < 0.0
+                    {
+                        n = -n;
+                    }
+
+                    // lambert for phong materials, GGX microfacet model for pbr materials
+                    let mut bsdf = surface.get_bsdf();
+                    let mut hit_point = surface.hit_point;
+                    let mut wo = wo;
+
+                    // subsurface scattering (blended with the surface bsdf): the path continues at the exit point of the random walk
+                    if material.subsurface > 0.0 && sample_1d() < material.subsurface
+                    {
+                        let mut entry_normal = normal;
+                        if entry_normal.dot(&wo) < 0.0
+                        {
+                            entry_normal = -entry_normal;
+                        }
+
+                        let exit = self.subsurface_random_walk(item, &material, &surface.hit_point, entry_normal);
+
+                        if exit.is_none()
+                        {
+                            break;
+                        }
+
+                        let exit = exit.unwrap();
+
+                        throughput = throughput.component_mul(&exit.weight);
+                        hit_point = exit.point;
+                        n = exit.normal;
+                        wo = exit.normal;
+                        bsdf = get_subsurface_exit_bsdf();
+                    }
+
+                    // next event estimation
+                    if material.receive_shadow
+                    {
+                        let scatter = Scatter::Surface { bsdf: &bsdf, n: n, wo: wo };
+                        let direct = self.sample_direct_lighting(scene, &scatter, &hit_point, Some(item), &medium, bounce + 1);
+                        radiance += self.clamp_indirect(throughput.component_mul(&direct) * surface.ambient_occlusion, bounce >= 1);
+
+                        // caustics
+                        if use_photon_map
+                        {
+                            let caustics = self.get_caustic_radiance(&bsdf, n, wo, &hit_point);
+                            radiance += self.clamp_indirect(throughput.component_mul(&caustics) * surface.ambient_occlusion, bounce >= 1);
+                        }
+                    }
+
+                    let (u1, u2) = sample_2d();
+                    let bsdf_sample = bsdf.sample(n, wo, sample_1d(), u1, u2);
+
+                    if bsdf_sample.is_none()
+                    {
+                        break;
+                    }
+
+                    let bsdf_sample = bsdf_sample.unwrap();
+
+                    let mut bounce_type = BounceType::Diffuse;
+                    ray_type = RayType::Indirect;
+                    if bsdf_sample.glossy
+                    {
+                        bounce_type = BounceType::Glossy;
+                        ray_type = RayType::Reflection;
+                    }
+
+                    if !bounces.add(bounce_type, &self.config)
+                    {
+                        break;
+                    }
+
+                    throughput = throughput.component_mul(&bsdf_sample.weight) * surface.ambient_occlusion;
+                    last_bsdf_pdf = bsdf_sample.pdf;
+                    specular_bounce = false;
+                    after_diffuse = true;
+
+                    r = Ray::new(hit_point + (n * SHADOW_BIAS), bsdf_sample.dir);
+                },
+                PathLobe::Reflection =>
+                {
+                    if !bounces.add(BounceType::Glossy, &self.config)
+                    {
+                        break;
+                    }
+
+                    let mut n = surface.surface_normal;
+                    if n.dot(&wo) < 0.0
+                    {
+                        n = -n;
+                    }
+
+                    let mut reflection_ray = self.create_reflection(n, r.dir, surface.hit_point);
+
+                    if surface.roughness > 0.0
+                    {
+                        reflection_ray.dir = self.jitter(reflection_ray.dir, surface.roughness);
+
+                        //do not reflect into the surface
+                        if reflection_ray.dir.dot(&n) <= 0.0
+                        {
+                            break;
+                        }
+                    }
+
+                    specular_bounce = true;
+                    ray_type = RayType::Reflection;
+                    r = reflection_ray;
+                },
+                PathLobe::Transmission =>
+                {
+                    let kr = self.fresnel_thin_film(r.dir, surface.surface_normal, surface.refraction_index, &surface.thin_film);
+
+                    let transmission_ray = self.create_transmission(surface.surface_normal, r.dir, surface.hit_point, surface.refraction_index);
+
+                    // total internal reflection or fresnel based reflection (the weight is only colored for thin films)
+                    let mut reflect = true;
+                    if transmission_ray.is_some()
+                    {
+                        let (fresnel_reflection, fresnel_weight) = self.sample_fresnel(kr);
+                        reflect = fresnel_reflection;
+                        throughput = throughput.component_mul(&fresnel_weight);
+                    }
+
+                    let mut bounce_type = BounceType::Transmission;
+                    if reflect
+                    {
+                        bounce_type = BounceType::Glossy;
+                    }
+
+                    if !bounces.add(bounce_type, &self.config)
+                    {
+                        break;
+                    }
+
+                    if reflect
+                    {
+                        let mut n = surface.surface_normal;
+                        if n.dot(&wo) < 0.0
+                        {
+                            n = -n;
+                        }
+
+                        r = self.create_reflection(n, r.dir, surface.hit_point);
+                        ray_type = RayType::Reflection;
+                    }
+                    else
+                    {
+                        r = transmission_ray.unwrap();
+                        ray_type = RayType::Refraction;
+
+                        medium = self.get_medium_after_transmission(scene, &medium, item, normal, r.dir);
+                    }
+
+                    specular_bounce = true;
+                }
+            }
+
+            r.dir = r.dir.normalize();
+            bounce += 1;
+            last_item = Some(item);
+
+            if !self.apply_russian_roulette(&mut throughput, bounce)
+            {
+                break;
+            }
+        }
+
+        (radiance, out_depth, out_normal, out_id, out_albedo)
+    }
+
+    // firefly suppression: scales indirect radiance samples down to the max component indirect_clamp
+    pub fn clamp_indirect(&self, radiance: Vector3<f32>, indirect: bool) -> Vector3<f32>
+    {
+        if !indirect || self.config.indirect_clamp <= 0.0
+        {
+            return radiance;
+        }
+
+        let max = max_component(radiance);
+        if max > self.config.indirect_clamp
+        {
+            return radiance * (self.config.indirect_clamp / max);
+        }
+
+        radiance
+    }
+
+    // returns false if the path should be terminated
+    pub fn apply_russian_roulette(&self, throughput: &mut Vector3<f32>, bounce: u16) -> bool
+    {
+        if bounce >= RUSSIAN_ROULETTE_MIN_BOUNCES
+        {
+            let probability = max_component(*throughput).clamp(RUSSIAN_ROULETTE_MIN_PROBABILITY, RUSSIAN_ROULETTE_MAX_PROBABILITY);
+
+            if sample_1d() > probability
+            {
+                return false;
+            }
+
+            *throughput /= probability;
+        }
+
+        max_component(*throughput) > 0.0
+    }
+
+    pub fn choose_lobe(&self, surface: &SurfaceInteraction) -> PathLobe
+    {
+        // the lobes are mixed like in the whitted tracer: alpha blends between transmission and the surface,
+        // reflectivity blends between mirror reflection and the surface bsdf
+        let transmission_probability = 1.0 - surface.alpha.clamp(0.0, 1.0);
+        let reflection_probability = surface.alpha.clamp(0.0, 1.0) * surface.reflectivity.clamp(0.0, 1.0);
+
+        let u = sample_1d();
+
+        if u < transmission_probability
+        {
+            PathLobe::Transmission
+        }
+        else if u < transmission_probability + reflection_probability
+        {
+            PathLobe::Reflection
+        }
+        else
+        {
+            PathLobe::Surface
+        }
+    }
+
+    pub fn sample_light(&self, light: &Light, hit_point: &Point3<f32>) -> Option<LightSample>
+    {
+        if !light.is_active()
+        {
+            return None;
+        }
+
+        if light.light_type == LightType::Area
+        {
+            return self.sample_area_light(light, hit_point);
+        }
+
+        let (dir, intensity, dist) = light.get_direction_and_intensity(hit_point);
+
+        if intensity <= 0.0
+        {
+            return None;
+        }
+
+        // light intensities are defined like in the whitted tracer:
+        // a white lambertian surface facing the light reflects exactly the light intensity --> scale by PI
+        Some(LightSample
+        {
+            dir: dir,
+            dist: dist,
+            radiance: light.color * intensity * PI,
+            pdf: 1.0,
+            is_delta: true
+        })
+    }
+
+    pub fn sample_area_light(&self, light: &Light, hit_point: &Point3<f32>) -> Option<LightSample>
+    {
+        let (u1, u2) = sample_2d();
+        let (light_point, light_normal, area_pdf) = light.sample_area(hit_point, u1, u2);
+
+        let to_light = light_point - hit_point;
+        let dist = to_light.norm();
+
+        if dist <= 0.0
+        {
+            return None;
+        }
+
+        let dir = to_light / dist;
+
+        // one sided emitter
+        let cos_light = light_normal.dot(&(-dir));
+        if cos_light <= 0.0
+        {
+            return None;
+        }
+
+        // area measure to solid angle
+        let pdf = area_pdf * dist * dist / cos_light;
+
+        Some(LightSample
+        {
+            dir: dir,
+            dist: dist - SHADOW_BIAS,
+            radiance: light.get_area_radiance(),
+            pdf: pdf,
+            is_delta: false
+        })
+    }
+
+    // receiver: the object the ray starts from (None for camera rays and volume scattering)
+    pub fn get_area_light_emission(&self, scene: &Scene, ray: &Ray, max_dist: f32, receiver: Option<&dyn Shape>, specular_bounce: bool, bsdf_pdf: f32) -> Vector3<f32>
+    {
+        let mut emission = Vector3::<f32>::zeros();
+
+        for light in &scene.lights
+        {
+            if !light.is_active() || light.light_type != LightType::Area
+            {
+                continue;
+            }
+
+            if let Some(receiver) = receiver
+            {
+                if !light.illuminates(receiver.get_basic())
+                {
+                    continue;
+                }
+            }
+
+            if let Some((dist, light_normal)) = light.intersect_area(ray)
+            {
+                if dist >= max_dist
+                {
+                    continue;
+                }
+
+                let cos_light = light_normal.dot(&(-ray.dir));
+                if cos_light <= 0.0
+                {
+                    continue;
+                }
+
+                let mut weight = 1.0;
+
+                if !specular_bounce
+                {
+                    let light_pdf = light.get_area_pdf() * dist * dist / cos_light;
+                    weight = power_heuristic(bsdf_pdf, light_pdf);
+                }
+
+                emission += light.get_area_radiance() * weight;
+            }
+        }
+
+        emission
+    }
+
+    // item: the shaded object (used for light linking, None for volume scattering)
+    pub fn sample_direct_lighting(&self, scene: &Scene, scatter: &Scatter, point: &Point3<f32>, item: Option<&dyn Shape>, medium: &Option<MediumInstance>, depth: u16) -> Vector3<f32>
+    {
+        let mut direct = Vector3::<f32>::zeros();
+
+        let origin = scatter.get_origin(point);
+
+        for light in &scene.lights
+        {
+            if let Some(item) = item
+            {
+                if !light.illuminates(item.get_basic())
+                {
+                    continue;
+                }
+            }
+
+            let light_sample = self.sample_light(light, point);
+
+            if light_sample.is_none()
+            {
+                continue;
+            }
+
+            let light_sample = light_sample.unwrap();
+
+            let f = scatter.eval(light_sample.dir);
+            if max_component(f) <= 0.0
+            {
+                continue;
+            }
+
+            let transmittance = self.get_shadow_transmittance(scene, origin, light_sample.dir, light_sample.dist, medium, depth);
+            if max_component(transmittance) <= 0.0
+            {
+                continue;
+            }
+
+            let mut weight = 1.0;
+            if !light_sample.is_delta
+            {
+                weight = power_heuristic(light_sample.pdf, scatter.pdf(light_sample.dir));
+            }
+
+            direct += f.component_mul(&light_sample.radiance).component_mul(&transmittance) * (weight / light_sample.pdf);
+        }
+
+        direct += self.sample_emissive_shapes(scene, scatter, point, medium, depth, true);
+        direct += self.sample_environment(scene, scatter, point, medium, depth, true);
+
+        direct
+    }
+
+    // one sample of an emissive shape (chosen by area and emitted power)
+    pub fn sample_emissive_shapes(&self, scene: &Scene, scatter: &Scatter, point: &Point3<f32>, medium: &Option<MediumInstance>, depth: u16, mis: bool) -> Vector3<f32>
+    {
+        let (u1, u2) = sample_2d();
+        let emitter_sample = scene.emitters.sample(sample_1d(), u1, u2);
+
+        if emitter_sample.is_none()
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        let emitter_sample = emitter_sample.unwrap();
+
+        let to_emitter = emitter_sample.point - point;
+        let dist = to_emitter.norm();
+
+        if dist <= SHADOW_BIAS
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        let dir = to_emitter / dist;
+
+        let cos_light = emitter_sample.normal.dot(&dir).abs();
+        if cos_light <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        let f = scatter.eval(dir);
+        if max_component(f) <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        // emitted radiance at the sampled point
+        let item = &scene.items[emitter_sample.item_index];
+        let material = item.get_material().read().unwrap();
+
+        let mut uv = None;
+        if material.has_any_texture()
+        {
+            uv = Some(item.get_uv(emitter_sample.point, emitter_sample.face_id));
+        }
+
+        let emission = self.get_item_color(&material, &uv, LightningColorType::Ambient).xyz();
+        if max_component(emission) <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        // the shadow ray must not hit the emitter itself (measured from the offset origin for close emitters)
+        let origin = scatter.get_origin(point);
+        let shadow_dist = (emitter_sample.point - origin).norm() * (1.0 - EMITTER_SHADOW_EPSILON);
+
+        let transmittance = self.get_shadow_transmittance(scene, origin, (emitter_sample.point - origin).normalize(), shadow_dist, medium, depth);
+        if max_component(transmittance) <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        // area measure to solid angle
+        let pdf = emitter_sample.pdf * dist * dist / cos_light;
+
+        let mut weight = 1.0;
+        if mis
+        {
+            weight = power_heuristic(pdf, scatter.pdf(dir));
+        }
+
+        f.component_mul(&emission).component_mul(&transmittance) * (weight / pdf)
+    }
+
+    // one importance sampled direction of the environment map
+    pub fn sample_environment(&self, scene: &Scene, scatter: &Scatter, point: &Point3<f32>, medium: &Option<MediumInstance>, depth: u16, mis: bool) -> Vector3<f32>
+    {
+        let (u1, u2) = sample_2d();
+        let environment_sample = scene.environment.sample(u1, u2);
+
+        if environment_sample.is_none()
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        let environment_sample = environment_sample.unwrap();
+
+        if max_component(environment_sample.radiance) <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        let f = scatter.eval(environment_sample.dir);
+        if max_component(f) <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        let transmittance = self.get_shadow_transmittance(scene, scatter.get_origin(point), environment_sample.dir, std::f32::MAX, medium, depth);
+        if max_component(transmittance) <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        let mut weight = 1.0;
+        if mis
+        {
+            weight = power_heuristic(environment_sample.pdf, scatter.pdf(environment_sample.dir));
+        }
+
+        f.component_mul(&environment_sample.radiance).component_mul(&transmittance) * (weight / environment_sample.pdf)
+    }
+
+    // pdf (solid angle) of hitting an emissive shape by light sampling
+    pub fn get_emitter_pdf(&self, scene: &Scene, ray: &Ray, hit_dist: f32, normal: Vector3<f32>, item_id: u32, face_id: u32) -> f32
+    {
+        let area_pdf = scene.emitters.get_pdf(item_id, face_id);
+
+        if area_pdf <= 0.0
+        {
+            return 0.0;
+        }
+
+        let cos_light = normal.dot(&ray.dir).abs();
+        if cos_light <= 0.0
+        {
+            return 0.0;
+        }
+
+        area_pdf * hit_dist * hit_dist / cos_light
+    }
+
+    // shadow rays are passing through (alpha based) transparent objects and participating media
+    // refractive objects are opaque if caustics are rendered by the photon map (the light through them is part of the caustics)
+    pub fn get_shadow_transmittance(&self, scene: &Scene, origin: Point3<f32>, dir: Vector3<f32>, max_dist: f32, medium: &Option<MediumInstance>, depth: u16) -> Vector3<f32>
+    {
+        let mut transmittance = Vector3::<f32>::new(1.0, 1.0, 1.0);
+        let mut ray = Ray::new(origin, dir);
+        let mut dist_left = max_dist;
+        let mut medium = medium.clone();
+
+        for _ in 0..MAX_SHADOW_TRANSMISSIONS
+        {
+            let shadow_intersection = self.trace(&scene, &ray, false, RayType::Shadow, depth);
+
+            let mut segment_dist = dist_left;
+            if let Some(shadow_intersection) = &shadow_intersection
+            {
+                segment_dist = shadow_intersection.0.min(dist_left);
+            }
+
+            if let Some(current_medium) = &medium
+            {
+                transmittance = transmittance.component_mul(&current_medium.transmittance(&ray.origin, &dir, segment_dist, sample_1d()));
+            }
+
+            if shadow_intersection.is_none()
+            {
+                return transmittance;
+            }
+
+            let shadow_intersection = shadow_intersection.unwrap();
+            let shadow_dist = shadow_intersection.0;
+
+            if shadow_dist >= dist_left
+            {
+                return transmittance;
+            }
+
+            let shadow_obj = shadow_intersection.2;
+            let shadow_obj_material = shadow_obj.get_material().read().unwrap();
+
+            let shadow_hit_point = ray.origin + (ray.dir * shadow_dist);
+
+            let mut shadow_uv = None;
+            if shadow_obj_material.has_any_texture()
+            {
+                shadow_uv = Some(shadow_obj.get_uv(shadow_hit_point, shadow_intersection.3));
+            }
+
+            if !self.photon_map.is_empty() && shadow_obj_material.is_refractive()
+            {
+                return Vector3::<f32>::zeros();
+            }
+
+            let alpha = self.get_alpha(&shadow_obj_material, &shadow_uv);
+
+            transmittance *= 1.0 - alpha.clamp(0.0, 1.0);
+
+            if max_component(transmittance) <= 0.0
+            {
+                return Vector3::<f32>::zeros();
+            }
+
+            medium = self.get_medium_after_transmission(scene, &medium, shadow_obj, shadow_intersection.1, dir);
+
+            dist_left -= shadow_dist + SHADOW_BIAS;
+            ray = Ray::new(shadow_hit_point + (dir * SHADOW_BIAS), dir);
+        }
+
+        transmittance
+    }
+}
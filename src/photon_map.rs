@@ -0,0 +1,531 @@
+use std::f32::consts::PI;
+
+use nalgebra::{Point3, Vector3};
+use parry3d::query::Ray;
+
+use crate::animation::set_sample_time;
+use crate::bsdf::Bsdf;
+use crate::helper::{rand, max_component, onb};
+use crate::path_tracing::PathLobe;
+use crate::raytracing::{Raytracing, RayType, SHADOW_BIAS};
+use crate::scene::{Scene, Light, LightType, AreaShape};
+use crate::spectrum::SpectralSample;
+
+const MAX_PHOTON_EMIT_THREADS: usize = 16;
+
+/*
+some resources:
+
+photon mapping and caustics:
+https://graphics.stanford.edu/~henrik/papers/book/
+https://www.pbr-book.org/3ed-2018/Light_Transport_III_Bidirectional_Methods/Stochastic_Progressive_Photon_Mapping
+
+kd-tree (balanced, median split):
+https://en.wikipedia.org/wiki/K-d_tree
+
+only caustic photons are stored (light --> specular --> ... --> diffuse): all other light paths are handled by the integrators
+*/
+
+// ******************** Photon ********************
+#[derive(Clone, Copy, Debug)]
+pub struct Photon
+{
+    pub pos: Point3<f32>,
+    pub dir: Vector3<f32>, //direction of travel
+    pub normal: Vector3<f32>, //surface normal at the hit (facing the incoming photon)
+    pub power: Vector3<f32>
+}
+
+// ******************** PhotonMap ********************
+pub struct PhotonMap
+{
+    photons: Vec<Photon>,
+    split_axis: Vec<u8>
+}
+
+impl PhotonMap
+{
+    pub fn new() -> PhotonMap
+    {
+        PhotonMap
+        {
+            photons: vec![],
+            split_axis: vec![]
+        }
+    }
+
+    pub fn build(photons: Vec<Photon>) -> PhotonMap
+    {
+        let mut map = PhotonMap
+        {
+            split_axis: vec![0; photons.len()],
+            photons: photons
+        };
+
+        let len = map.photons.len();
+        map.build_node(0, len);
+
+        map
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.photons.is_empty()
+    }
+
+    // the median of every range is the node -- split along the axis with the largest extent
+    fn build_node(&mut self, start: usize, end: usize)
+    {
+        if end <= start
+        {
+            return;
+        }
+
+        let mut min = Vector3::<f32>::repeat(std::f32::MAX);
+        let mut max = Vector3::<f32>::repeat(std::f32::MIN);
+        for photon in &self.photons[start..end]
+        {
+            min = min.inf(&photon.pos.coords);
+            max = max.sup(&photon.pos.coords);
+        }
+
+        let extent = max - min;
+        let mut axis = 0;
+        if extent.y > extent[axis] { axis = 1; }
+        if extent.z > extent[axis] { axis = 2; }
+
+        let mid = (start + end) / 2;
+        self.photons[start..end].select_nth_unstable_by(mid - start, |a, b| a.pos[axis].partial_cmp(&b.pos[axis]).unwrap());
+        self.split_axis[mid] = axis as u8;
+
+        self.build_node(start, mid);
+        self.build_node(mid + 1, end);
+    }
+
+    // calls the callback for every photon within the radius
+    pub fn for_each_in_radius<F: FnMut(&Photon)>(&self, point: &Point3<f32>, radius: f32, callback: &mut F)
+    {
+        self.find_in_node(0, self.photons.len(), point, radius * radius, callback);
+    }
+
+    fn find_in_node<F: FnMut(&Photon)>(&self, start: usize, end: usize, point: &Point3<f32>, radius_sq: f32, callback: &mut F)
+    {
+        if end <= start
+        {
+            return;
+        }
+
+        let mid = (start + end) / 2;
+        let photon = &self.photons[mid];
+        let axis = self.split_axis[mid] as usize;
+
+        let diff = point[axis] - photon.pos[axis];
+
+        // nearer side first, the other side only if the split plane is within the radius
+        if diff < 0.0
+        {
+            self.find_in_node(start, mid, point, radius_sq, callback);
+            if diff * diff <= radius_sq
+            {
+                self.find_in_node(mid + 1, end, point, radius_sq, callback);
+            }
+        }
+        else
+        {
+            self.find_in_node(mid + 1, end, point, radius_sq, callback);
+            if diff * diff <= radius_sq
+            {
+                self.find_in_node(start, mid, point, radius_sq, callback);
+            }
+        }
+
+        if (photon.pos - point).magnitude_squared() <= radius_sq
+        {
+            callback(photon);
+        }
+    }
+}
+
+// ******************** photon tracing ********************
+impl Raytracing
+{
+    // emitted flux of the light (in the units of the path tracer -- see sample_light)
+    pub fn get_light_flux(&self, scene: &Scene, light: &Light) -> Vector3<f32>
+    {
+        let color = light.color * light.intensity * PI;
+
+        match light.light_type
+        {
+            LightType::Point => color,
+            LightType::Spot => color * ((1.0 - light.max_angle.cos()) / 2.0),
+            LightType::Directional =>
+            {
+                let radius = scene.b_box.half_extents().magnitude();
+                color * (PI * radius * radius)
+            },
+            LightType::Area => color
+        }
+    }
+
+    pub fn build_photon_map(&mut self)
+    {
+        if self.config.photon_count == 0
+        {
+            self.photon_map = PhotonMap::new();
+            return;
+        }
+
+        let scene_arc = self.scene.clone();
+        let scene = scene_arc.read().unwrap();
+
+        let photons = self.emit_photons(&scene);
+
+        self.photon_map = PhotonMap::build(photons);
+
+        println!("photon map: {} caustic photons stored", self.photon_map.len());
+    }
+
+    fn emit_photons(&self, scene: &Scene) -> Vec<Photon>
+    {
+        let mut photons = vec![];
+
+        if scene.items.is_empty() || scene.b_box.mins.x > scene.b_box.maxs.x
+        {
+            return photons;
+        }
+
+        // lights are chosen proportional to their flux
+        let mut light_weights = vec![];
+        let mut total_weight = 0.0;
+        for light in &scene.lights
+        {
+            let mut weight = 0.0;
+            if light.is_active()
+            {
+                weight = max_component(self.get_light_flux(scene, light));
+            }
+
+            total_weight += weight;
+            light_weights.push(weight);
+        }
+
+        if total_weight <= 0.0
+        {
+            return photons;
+        }
+
+        let photon_count = self.config.photon_count as usize;
+        let thread_amount = num_cpus::get().clamp(1, MAX_PHOTON_EMIT_THREADS);
+        let per_thread = (photon_count + thread_amount - 1) / thread_amount;
+
+        std::thread::scope(|s|
+        {
+            let mut handles = vec![];
+
+            for thread_id in 0..thread_amount
+            {
+                let count = per_thread.min(photon_count.saturating_sub(thread_id * per_thread));
+                let light_weights = &light_weights;
+
+                handles.push(s.spawn(move ||
+                {
+                    let mut thread_photons = vec![];
+
+                    for _ in 0..count
+                    {
+                        // choose light
+                        let target = rand(0.0, 1.0) * total_weight;
+                        let mut light_index = 0;
+                        let mut sum = 0.0;
+                        for (i, weight) in light_weights.iter().enumerate()
+                        {
+                            sum += weight;
+                            light_index = i;
+
+                            if *weight > 0.0 && sum >= target
+                            {
+                                break;
+                            }
+                        }
+
+                        let light = &scene.lights[light_index];
+                        let probability = light_weights[light_index] / total_weight;
+                        if probability <= 0.0
+                        {
+                            continue;
+                        }
+
+                        let power = self.get_light_flux(scene, light) / (photon_count as f32 * probability);
+
+                        //motion blur: the photons are distributed over the shutter interval
+                        if scene.cam.has_motion_blur()
+                        {
+                            set_sample_time(rand(0.0, 1.0));
+                        }
+
+                        if let Some(ray) = self.emit_photon_ray(scene, light)
+                        {
+                            self.trace_photon(scene, ray, power, light, &mut thread_photons);
+                        }
+                    }
+
+                    thread_photons
+                }));
+            }
+
+            for handle in handles
+            {
+                photons.append(&mut handle.join().unwrap());
+            }
+        });
+
+        photons
+    }
+
+    fn emit_photon_ray(&self, scene: &Scene, light: &Light) -> Option<Ray>
+    {
+        match light.light_type
+        {
+            LightType::Point =>
+            {
+                let z: f32 = 1.0 - 2.0 * rand(0.0, 1.0);
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * rand(0.0, 1.0);
+
+                Some(Ray::new(light.pos, Vector3::<f32>::new(r * phi.cos(), r * phi.sin(), z)))
+            },
+            LightType::Spot =>
+            {
+                // uniform within the cone
+                let light_dir = light.dir.normalize();
+                let (tangent, bitangent) = onb(light_dir);
+
+                let z = 1.0 - rand(0.0, 1.0) * (1.0 - light.max_angle.cos());
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * rand(0.0, 1.0);
+
+                Some(Ray::new(light.pos, tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + light_dir * z))
+            },
+            LightType::Directional =>
+            {
+                // disk covering the bounding sphere of the scene
+                let light_dir = light.dir.normalize();
+                let (tangent, bitangent) = onb(light_dir);
+
+                let center = scene.b_box.center();
+                let radius = scene.b_box.half_extents().magnitude();
+
+                let r = radius * rand(0.0, 1.0f32).sqrt();
+                let phi = 2.0 * PI * rand(0.0, 1.0);
+
+                let origin = center + (tangent * (r * phi.cos())) + (bitangent * (r * phi.sin())) - (light_dir * (radius * 2.0));
+
+                Some(Ray::new(origin, light_dir))
+            },
+            LightType::Area =>
+            {
+                // spheres are sampled on the hemisphere facing a random direction --> uniform over the whole sphere
+                let mut reference_point = light.pos;
+                if light.area_shape == AreaShape::Sphere
+                {
+                    let z: f32 = 1.0 - 2.0 * rand(0.0, 1.0);
+                    let r = (1.0 - z * z).max(0.0).sqrt();
+                    let phi = 2.0 * PI * rand(0.0, 1.0);
+
+                    reference_point += Vector3::<f32>::new(r * phi.cos(), r * phi.sin(), z);
+                }
+
+                let (point, normal, _) = light.sample_area(&reference_point, rand(0.0, 1.0), rand(0.0, 1.0));
+
+                // cosine weighted emission
+                let u1: f32 = rand(0.0, 1.0);
+                let (tangent, bitangent) = onb(normal);
+                let r = u1.sqrt();
+                let phi = 2.0 * PI * rand(0.0, 1.0);
+                let dir = tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1.0 - u1).max(0.0).sqrt();
+
+                Some(Ray::new(point + (normal * SHADOW_BIAS), dir))
+            }
+        }
+    }
+
+    fn trace_photon(&self, scene: &Scene, ray: Ray, power: Vector3<f32>, light: &Light, photons: &mut Vec<Photon>)
+    {
+        let mut r = ray;
+        r.dir = r.dir.normalize();
+
+        let mut power = power;
+
+        // spectral mode: one wavelength per photon (dispersion of caustics -- only sampled if a dispersive material is hit)
+        let mut wavelength_u = None;
+        if self.config.spectral
+        {
+            wavelength_u = Some(rand(0.0, 1.0));
+        }
+        let spectral = SpectralSample::new(wavelength_u);
+
+        let mut medium = self.get_global_medium(scene);
+
+        // point and spot lights are falling off linearly in this renderer (see get_direction_and_intensity)
+        let distance_falloff = light.light_type == LightType::Point || light.light_type == LightType::Spot;
+        let mut travelled = 0.0;
+
+        let mut specular_bounces = 0;
+        let mut bounce: u16 = 0;
+
+        let mut ray_type = RayType::Indirect;
+
+        while bounce <= self.config.max_recursion
+        {
+            let intersection = self.trace(&scene, &r, false, ray_type, bounce + 2);
+
+            if intersection.is_none()
+            {
+                break;
+            }
+
+            let intersection = intersection.unwrap();
+
+            let hit_dist = intersection.0;
+            let normal = intersection.1;
+            let item = intersection.2;
+            let face_id = intersection.3;
+
+            if let Some(current_medium) = &medium
+            {
+                power = power.component_mul(&current_medium.transmittance(&r.origin, &r.dir, hit_dist, rand(0.0, 1.0)));
+            }
+
+            travelled += hit_dist;
+
+            let material = item.get_material().read().unwrap();
+
+            let mut surface = self.get_surface_interaction(&r, hit_dist, normal, item, face_id, &material);
+            surface.refraction_index = material.get_refraction_index(&spectral);
+
+            let lobe = self.choose_lobe(&surface);
+
+            // passing through non refracting (alpha) transparency does not create a caustic
+            let mut specular = true;
+
+            match lobe
+            {
+                PathLobe::Surface =>
+                {
+                    // store caustic photons only (on objects linked to the light)
+                    if specular_bounces > 0 && material.receive_shadow && light.illuminates(item.get_basic())
+                    {
+                        let mut photon_power = power.component_mul(&spectral.get_rgb_weight());
+                        if distance_falloff
+                        {
+                            photon_power *= travelled;
+                        }
+
+                        let mut n = normal;
+                        if n.dot(&r.dir) > 0.0
+                        {
+                            n = -n;
+                        }
+
+                        photons.push(Photon
+                        {
+                            pos: surface.hit_point,
+                            dir: r.dir,
+                            normal: n,
+                            power: photon_power
+                        });
+                    }
+
+                    break;
+                },
+                PathLobe::Reflection =>
+                {
+                    let mut n = surface.surface_normal;
+                    if n.dot(&r.dir) > 0.0
+                    {
+                        n = -n;
+                    }
+
+                    r = self.create_reflection(n, r.dir, surface.hit_point);
+                    ray_type = RayType::Reflection;
+                },
+                PathLobe::Transmission =>
+                {
+                    let kr = self.fresnel_thin_film(r.dir, surface.surface_normal, surface.refraction_index, &surface.thin_film);
+
+                    let transmission_ray = self.create_transmission(surface.surface_normal, r.dir, surface.hit_point, surface.refraction_index);
+
+                    // total internal reflection or fresnel based reflection (the weight is only colored for thin films)
+                    let mut reflect = true;
+                    if transmission_ray.is_some()
+                    {
+                        let (fresnel_reflection, fresnel_weight) = self.sample_fresnel(kr);
+                        reflect = fresnel_reflection;
+                        power = power.component_mul(&fresnel_weight);
+                    }
+
+                    if reflect
+                    {
+                        let mut n = surface.surface_normal;
+                        if n.dot(&r.dir) > 0.0
+                        {
+                            n = -n;
+                        }
+
+                        r = self.create_reflection(n, r.dir, surface.hit_point);
+                        ray_type = RayType::Reflection;
+                    }
+                    else
+                    {
+                        r = transmission_ray.unwrap();
+                        ray_type = RayType::Refraction;
+                        specular = material.is_refractive();
+
+                        medium = self.get_medium_after_transmission(scene, &medium, item, normal, r.dir);
+                    }
+                }
+            }
+
+            r.dir = r.dir.normalize();
+            if specular
+            {
+                specular_bounces += 1;
+            }
+            bounce += 1;
+
+            if max_component(power) <= 0.0
+            {
+                break;
+            }
+        }
+    }
+
+    // caustic radiance towards wo (density estimation with a constant kernel)
+    pub fn get_caustic_radiance(&self, bsdf: &Bsdf, n: Vector3<f32>, wo: Vector3<f32>, point: &Point3<f32>) -> Vector3<f32>
+    {
+        let mut radiance = Vector3::<f32>::zeros();
+
+        if self.photon_map.is_empty() || self.config.photon_radius <= 0.0
+        {
+            return radiance;
+        }
+
+        self.photon_map.for_each_in_radius(point, self.config.photon_radius, &mut |photon: &Photon|
+        {
+            // photons from the other side of thin surfaces
+            if photon.normal.dot(&n) <= 0.0
+            {
+                return;
+            }
+
+            radiance += bsdf.eval(n, wo, -photon.dir).component_mul(&photon.power);
+        });
+
+        radiance / (PI * self.config.photon_radius * self.config.photon_radius)
+    }
+}
@@ -3,11 +3,30 @@ use nalgebra::{Vector3, Vector2, Matrix4, Vector4};
 
 use crate::camera::Camera;
 
+const DENOISE_KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0]; //B3 spline
+const DENOISE_COLOR_SIGMA: f32 = 0.5;
+const DENOISE_NORMAL_POWER: f32 = 64.0;
+const DENOISE_DEPTH_SIGMA: f32 = 0.02; //relative to the depth (per pixel step)
+const DENOISE_MIN_ALBEDO: f32 = 0.01;
+
+/*
+some resources:
+
+edge-avoiding a-trous wavelet filter (Dammertz et al. 2010):
+https://jo.dreggn.org/home/2010_atrous.pdf
+
+normal and depth edge stopping functions (SVGF):
+https://research.nvidia.com/publication/2017-07_spatiotemporal-variance-guided-filtering-real-time-reconstruction-path-traced
+*/
+
 #[derive(Debug, Copy, Clone)]
 pub struct PostProcessingConfig
 {
     pub cavity: bool,
     pub outline: bool,
+
+    pub denoise: bool,
+    pub denoise_iterations: u32, //filter passes (the kernel footprint doubles per pass)
 }
 
 impl PostProcessingConfig
@@ -18,6 +37,9 @@ impl PostProcessingConfig
         {
             cavity: false,
             outline: false,
+
+            denoise: false,
+            denoise_iterations: 5,
         }
     }
 }
@@ -120,6 +142,114 @@ pub fn calculate_outline(texel: Vector2<i32>, object_ids: &Vec<u32>, width: i32)
     outline_opacity
 }
 
+// edge-avoiding a-trous filter guided by normals, depth, object ids and albedo
+// works on the linear colors -- the albedo is divided out before filtering so that textures are kept sharp
+pub fn denoise(colors: &Vec<Vector3<f32>>, width: u32, height: u32, normals: &Vec<Vector3<f32>>, depth: &Vec<f32>, object_ids: &Vec<u32>, albedo: &Vec<Vector3<f32>>, iterations: u32) -> Vec<Vector3<f32>>
+{
+    let width = width as i32;
+    let height = height as i32;
+    let pixels = (width * height) as usize;
+
+    if colors.len() < pixels || normals.len() < pixels || depth.len() < pixels || object_ids.len() < pixels || albedo.len() < pixels
+    {
+        println!("denoising skipped: AOV buffers do not match the image size");
+        return colors.clone();
+    }
+
+    // channels without (enough) albedo are filtered as they are
+    let albedo: Vec<Vector3<f32>> = albedo.iter().map(|a| a.map(|c| if c < DENOISE_MIN_ALBEDO { 1.0 } else { c })).collect();
+
+    // demodulate
+    let mut color: Vec<Vector3<f32>> = colors.iter().zip(albedo.iter()).map(|(c, a)| c.component_div(a)).collect();
+
+    let mut color_sigma = DENOISE_COLOR_SIGMA;
+
+    for iteration in 0..iterations
+    {
+        let step = 1 << iteration;
+        let mut filtered = vec![Vector3::<f32>::zeros(); pixels];
+
+        for y in 0..height
+        {
+            for x in 0..width
+            {
+                let index = (y * width + x) as usize;
+
+                let center_color = color[index];
+                let center_normal = normals[index];
+                let center_depth = depth[index];
+                let center_id = object_ids[index];
+
+                let mut sum = Vector3::<f32>::zeros();
+                let mut weight_sum = 0.0;
+
+                for (j, kernel_y) in DENOISE_KERNEL.iter().enumerate()
+                {
+                    let sample_y = y + (j as i32 - 2) * step;
+                    if sample_y < 0 || sample_y >= height
+                    {
+                        continue;
+                    }
+
+                    for (i, kernel_x) in DENOISE_KERNEL.iter().enumerate()
+                    {
+                        let sample_x = x + (i as i32 - 2) * step;
+                        if sample_x < 0 || sample_x >= width
+                        {
+                            continue;
+                        }
+
+                        let sample_index = (sample_y * width + sample_x) as usize;
+
+                        // different objects are never mixed
+                        if object_ids[sample_index] != center_id
+                        {
+                            continue;
+                        }
+
+                        let color_diff = (color[sample_index] - center_color).magnitude_squared();
+                        let color_weight = (-color_diff / (color_sigma * color_sigma)).exp();
+
+                        // background pixels have no normal
+                        let mut normal_weight = 1.0;
+                        if center_normal.magnitude_squared() > 0.0
+                        {
+                            normal_weight = center_normal.dot(&normals[sample_index]).max(0.0).powf(DENOISE_NORMAL_POWER);
+                        }
+
+                        let pixel_dist = (((i as i32 - 2) * (i as i32 - 2) + (j as i32 - 2) * (j as i32 - 2)) as f32).sqrt() * step as f32;
+                        let depth_diff = (depth[sample_index] - center_depth).abs();
+                        let depth_weight = (-depth_diff / (DENOISE_DEPTH_SIGMA * center_depth.abs() * pixel_dist + 0.0001)).exp();
+
+                        let weight = kernel_x * kernel_y * color_weight * normal_weight * depth_weight;
+
+                        sum += color[sample_index] * weight;
+                        weight_sum += weight;
+                    }
+                }
+
+                if weight_sum > 0.0
+                {
+                    filtered[index] = sum / weight_sum;
+                }
+                else
+                {
+                    filtered[index] = center_color;
+                }
+            }
+        }
+
+        color = filtered;
+
+        // finer details are kept in the later (wider) passes
+        color_sigma *= 0.5;
+    }
+
+    // remodulate
+    color.iter().zip(albedo.iter()).map(|(c, a)| c.component_mul(a)).collect()
+}
+
+// the image is expected to be denoised already (see denoise)
 pub fn run_post_processing(config: PostProcessingConfig, image: &RgbaImage, normals: &Vec<Vector3<f32>>, _depth: &Vec<f32>, object_ids: &Vec<u32>, _cam: &Camera) -> RgbaImage
 {
     let width = image.width();
@@ -178,4 +308,38 @@ pub fn run_post_processing(config: PostProcessingConfig, image: &RgbaImage, norm
     }
 
     processed_image
-}
\ No newline at end of file
+}
+
+// heat map of the samples used per pixel (blue = few, red = max samples)
+pub fn get_sample_count_image(sample_counts: &Vec<u32>, width: u32, height: u32, max_samples: u32) -> RgbaImage
+{
+    let mut image: RgbaImage = ImageBuffer::new(width, height);
+
+    let max_samples = max_samples.max(1) as f32;
+
+    for x in 0..width
+    {
+        for y in 0..height
+        {
+            let index = (y * width + x) as usize;
+
+            let mut samples = 0;
+            if index < sample_counts.len()
+            {
+                samples = sample_counts[index];
+            }
+
+            let t = (samples as f32 / max_samples).clamp(0.0, 1.0);
+
+            // blue --> green --> red
+            let r = ((t - 0.5) * 2.0).clamp(0.0, 1.0);
+            let g = 1.0 - ((t - 0.5) * 2.0).abs();
+            let b = ((0.5 - t) * 2.0).clamp(0.0, 1.0);
+
+            let color = Rgba([(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255]);
+            image.put_pixel(x, y, color);
+        }
+    }
+
+    image
+}
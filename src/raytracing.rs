@@ -2,26 +2,37 @@ use std::f32::consts::PI;
 use std::sync::{RwLock, Arc};
 
 use crate::post_processing::PostProcessingConfig;
-use crate::shape::{Shape, TextureType, Material};
-
-use crate::scene::{Scene, LightType};
-use crate::helper::approx_equal;
-
-use nalgebra::{Point3, Vector3, Matrix3, Vector4, Point2};
+use crate::shape::{Shape, TextureType, Material, ShadingModel};
+use crate::bsdf::Bsdf;
+use crate::iridescence::ThinFilm;
+
+use crate::scene::{Scene, LightType, ScemeItem};
+use crate::camera::CameraProjection;
+use crate::debug_render::RenderMode;
+use crate::animation::set_sample_time;
+use crate::helper::{approx_equal, max_component, concentric_sample_disk};
+use crate::medium::{Medium, MediumInstance};
+use crate::path_tracing::Scatter;
+use crate::photon_map::PhotonMap;
+use crate::bidirectional::LightDistribution;
+use crate::spectrum::SpectralSample;
+use crate::sampler::{SamplerType, start_pixel_sample, sample_1d, sample_2d};
+
+use nalgebra::{Point3, Vector3, Matrix3, Vector4, Point2, Vector2};
 use parry3d::query::{Ray};
 
-use rand::{Rng, SeedableRng};
-use rand::rngs::StdRng;
-use rand::seq::SliceRandom;
-
-const SHADOW_BIAS: f32 = 0.001;
-const APERTURE_BASE_RESOLUTION: f32 = 800.0;
+pub const SHADOW_BIAS: f32 = 0.001;
 
 const CAM_CLIPPING_PLANE_DIST: f32 = 1.0;
 const DEFAULT_VIEW_POS: Vector4::<f32> = Vector4::<f32>::new(0.0, 0.0, 0.0, 1.0);
 
 const BVH_MIN_ITEMS: usize = 50;
 
+const PBR_MIN_REFLECTION_WEIGHT: f32 = 0.01;
+
+pub const ADAPTIVE_MIN_SAMPLES: u32 = 16; //adaptive sampling is only used with more samples (variance estimate)
+const ADAPTIVE_MIN_LUMINANCE: f32 = 0.0001;
+
 /*
 some resources:
 
@@ -51,6 +62,9 @@ https://www.gamedev.net/forums/topic/683176-finding-a-random-point-on-a-sphere-w
 
 pbr shading
 https://gist.github.com/galek/53557375251e1a942dfa
+
+adaptive sampling (running variance):
+https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance#Welford's_online_algorithm
 */
 
 // ******************** PixelData ********************
@@ -60,16 +74,90 @@ pub struct PixelData
     pub g: u8,
     pub b: u8,
 
+    pub color: Vector3<f32>, //linear color (denoising input)
+
     pub normal: Vector3<f32>,
     pub depth: f32,
+    pub albedo: Vector3<f32>, //base color of the first visible surface (denoising guide)
 
     pub object_id: u32,
 
+    pub samples: u32, //samples used for this pixel (adaptive sampling)
+
     pub x: i32,
     pub y: i32
 }
 
 
+// ******************** PixelAccumulation ********************
+#[derive(Debug, Copy, Clone)]
+pub struct PixelAccumulation
+{
+    pub color: Vector3<f32>, //sums over all samples
+    pub depth: f32,
+    pub normal: Vector3<f32>,
+    pub albedo: Vector3<f32>,
+
+    pub object_id: u32,
+    pub samples: u32,
+
+    //running luminance mean and variance (welford) for adaptive sampling
+    luminance_mean: f32,
+    luminance_m2: f32
+}
+
+impl PixelAccumulation
+{
+    pub fn new() -> PixelAccumulation
+    {
+        PixelAccumulation
+        {
+            color: Vector3::<f32>::zeros(),
+            depth: 0.0,
+            normal: Vector3::<f32>::zeros(),
+            albedo: Vector3::<f32>::zeros(),
+
+            object_id: 0,
+            samples: 0,
+
+            luminance_mean: 0.0,
+            luminance_m2: 0.0
+        }
+    }
+
+    pub fn add_sample(&mut self, color: Vector3<f32>, depth: f32, normal: Vector3<f32>, object_id: u32, albedo: Vector3<f32>)
+    {
+        self.color += color;
+        self.depth += depth;
+        self.normal += normal;
+        self.albedo += albedo;
+        self.object_id = object_id;
+
+        self.samples += 1;
+
+        let luminance = 0.2126 * color.x.min(1.0) + 0.7152 * color.y.min(1.0) + 0.0722 * color.z.min(1.0);
+
+        let delta = luminance - self.luminance_mean;
+        self.luminance_mean += delta / self.samples as f32;
+        self.luminance_m2 += delta * (luminance - self.luminance_mean);
+    }
+
+    // the standard error of the mean is below the threshold (relative to the square root of the brightness)
+    pub fn is_converged(&self, threshold: f32) -> bool
+    {
+        if self.samples < ADAPTIVE_MIN_SAMPLES
+        {
+            return false;
+        }
+
+        let variance = self.luminance_m2 / (self.samples - 1) as f32;
+        let standard_error = (variance / self.samples as f32).sqrt();
+        let error = standard_error / self.luminance_mean.max(ADAPTIVE_MIN_LUMINANCE).sqrt();
+
+        error < threshold
+    }
+}
+
 // ******************** HitResult ********************
 pub struct HitResult<'a>
 {
@@ -77,6 +165,18 @@ pub struct HitResult<'a>
     dist: f32,
 }
 
+// ******************** RayType ********************
+// used for the per object ray visibility
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum RayType
+{
+    Camera,
+    Shadow,
+    Reflection, //specular and glossy reflections
+    Refraction,
+    Indirect //diffuse bounces, volume scattering and photons
+}
+
 
 // ******************** LightningColorType ********************
 pub enum LightningColorType
@@ -86,22 +186,112 @@ pub enum LightningColorType
     Specular
 }
 
+// ******************** SurfaceInteraction ********************
+pub struct SurfaceInteraction
+{
+    pub hit_point: Point3<f32>,
+    pub normal: Vector3<f32>,
+    pub surface_normal: Vector3<f32>,
+    pub uv: Option<Point2<f32>>,
+
+    pub base_color: Vector3<f32>,
+    pub emission: Vector3<f32>,
+    pub alpha: f32,
+    pub reflectivity: f32,
+    pub roughness: f32,
+    pub refraction_index: f32,
+    pub ambient_occlusion: f32,
+
+    pub shading_model: ShadingModel,
+    pub metallic: f32,
+    pub pbr_roughness: f32,
+
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub clearcoat_normal: Option<Vector3<f32>>,
+    pub sheen_color: Vector3<f32>,
+    pub sheen_roughness: f32,
+    pub anisotropy: f32,
+    pub tangent: Option<Vector3<f32>>,
+
+    pub thin_film: ThinFilm,
+}
+
+impl SurfaceInteraction
+{
+    pub fn get_bsdf(&self) -> Bsdf
+    {
+        let mut bsdf = Bsdf::new(self.shading_model, self.base_color, self.metallic, self.pbr_roughness, self.refraction_index);
+
+        bsdf.clearcoat = self.clearcoat;
+        bsdf.clearcoat_roughness = self.clearcoat_roughness;
+        bsdf.clearcoat_normal = self.clearcoat_normal;
+        bsdf.sheen_color = self.sheen_color;
+        bsdf.sheen_roughness = self.sheen_roughness;
+        bsdf.anisotropy = self.anisotropy;
+        bsdf.tangent = self.tangent;
+        bsdf.thin_film = self.thin_film;
+
+        bsdf
+    }
+}
+
+// ******************** IntegratorType ********************
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum IntegratorType
+{
+    Whitted,
+    PathTracing,
+    Bidirectional
+}
+
+impl IntegratorType
+{
+    pub fn from_str(integrator: &str) -> Option<IntegratorType>
+    {
+        match integrator
+        {
+            "whitted" => Some(IntegratorType::Whitted),
+            "path_tracing" | "path" => Some(IntegratorType::PathTracing),
+            "bidirectional" | "bdpt" => Some(IntegratorType::Bidirectional),
+            _ => None
+        }
+    }
+}
+
 // ******************** RaytracingConfig ********************
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct RaytracingConfig
 {
+    pub integrator: IntegratorType,
+    pub render_mode: RenderMode, //debug visualizations (shaded = integrator output)
     pub monte_carlo: bool,
+    pub spectral: bool, //one wavelength per sample (dispersion)
+
+    pub samples: u16, //this includes anti aliasing (max samples for adaptive sampling)
+    pub sampler: SamplerType,
 
-    pub samples: u16, //this includes anti aliasing
+    pub adaptive_threshold: f32, //relative standard error to stop sampling a pixel (0 means off, needs more than ADAPTIVE_MIN_SAMPLES samples)
+    pub sample_count_image: bool, //saves the samples used per pixel as additional image
 
-    pub focal_length: f32, //8.0
-    pub aperture_size: f32, //64.0 (1 means off)
+    pub progressive: bool, //renders passes of one sample per pixel until samples, noise or time limit is reached
+    pub time_limit: f32, //seconds (0 means off)
 
-    pub fog_density: f32,
-    pub fog_color: Vector3<f32>,
+    pub fog_density: f32, //extinction of the global medium (per unit length)
+    pub fog_color: Vector3<f32>, //scattering albedo of the global medium
+    pub fog_anisotropy: f32, //henyey-greenstein g of the global medium
+
+    pub photon_count: u32, //emitted caustic photons (0 means off)
+    pub photon_radius: f32, //gather radius of the caustic photons
+
+    pub max_recursion: u16, //total bounce limit
+    pub max_diffuse_bounces: u16, //bounce limits per path type (path tracing and bidirectional)
+    pub max_glossy_bounces: u16,
+    pub max_transmission_bounces: u16,
+    pub indirect_clamp: f32, //max component of indirect radiance samples (0 means off)
 
-    pub max_recursion: u16,
     pub gamma_correction: bool
 }
 
@@ -111,17 +301,33 @@ impl RaytracingConfig
     {
         RaytracingConfig
         {
+            integrator: IntegratorType::Whitted,
+            render_mode: RenderMode::Shaded,
             monte_carlo: false,
+            spectral: false,
 
             samples: 1,
+            sampler: SamplerType::Sobol,
+
+            adaptive_threshold: 0.0,
+            sample_count_image: false,
 
-            focal_length: 1.0,
-            aperture_size: 1.0,
+            progressive: false,
+            time_limit: 0.0,
 
             fog_density: 0.0,
             fog_color: Vector3::<f32>::new(0.4, 0.4, 0.4),
+            fog_anisotropy: 0.0,
+
+            photon_count: 0,
+            photon_radius: 0.1,
 
             max_recursion: 6,
+            max_diffuse_bounces: 64,
+            max_glossy_bounces: 64,
+            max_transmission_bounces: 64,
+            indirect_clamp: 0.0,
+
             gamma_correction: false
         }
     }
@@ -130,28 +336,64 @@ impl RaytracingConfig
     {
         let default_config = RaytracingConfig::new();
 
+        // integrator
+        if default_config.integrator != new_config.integrator
+        {
+            self.integrator = new_config.integrator;
+        }
+
+        // render mode
+        if default_config.render_mode != new_config.render_mode
+        {
+            self.render_mode = new_config.render_mode;
+        }
+
         // monte_carlo
         if default_config.monte_carlo != new_config.monte_carlo
         {
             self.monte_carlo = new_config.monte_carlo.clone();
         }
 
+        // spectral
+        if default_config.spectral != new_config.spectral
+        {
+            self.spectral = new_config.spectral;
+        }
+
         // samples
         if default_config.samples != new_config.samples
         {
             self.samples = new_config.samples.clone();
         }
 
-        // focal_length
-        if !approx_equal(default_config.focal_length, new_config.focal_length)
+        // sampler
+        if default_config.sampler != new_config.sampler
         {
-            self.focal_length = new_config.focal_length.clone();
+            self.sampler = new_config.sampler;
         }
 
-        // aperture_size
-        if !approx_equal(default_config.aperture_size, new_config.aperture_size)
+        // adaptive_threshold
+        if !approx_equal(default_config.adaptive_threshold, new_config.adaptive_threshold)
         {
-            self.aperture_size = new_config.aperture_size.clone();
+            self.adaptive_threshold = new_config.adaptive_threshold;
+        }
+
+        // sample_count_image
+        if default_config.sample_count_image != new_config.sample_count_image
+        {
+            self.sample_count_image = new_config.sample_count_image;
+        }
+
+        // progressive
+        if default_config.progressive != new_config.progressive
+        {
+            self.progressive = new_config.progressive;
+        }
+
+        // time_limit
+        if !approx_equal(default_config.time_limit, new_config.time_limit)
+        {
+            self.time_limit = new_config.time_limit;
         }
 
         // fog_density
@@ -171,12 +413,54 @@ impl RaytracingConfig
             self.fog_color = new_config.fog_color;
         }
 
+        // fog_anisotropy
+        if !approx_equal(default_config.fog_anisotropy, new_config.fog_anisotropy)
+        {
+            self.fog_anisotropy = new_config.fog_anisotropy.clone();
+        }
+
+        // photon_count
+        if default_config.photon_count != new_config.photon_count
+        {
+            self.photon_count = new_config.photon_count;
+        }
+
+        // photon_radius
+        if !approx_equal(default_config.photon_radius, new_config.photon_radius)
+        {
+            self.photon_radius = new_config.photon_radius;
+        }
+
         // max_recursion
         if default_config.max_recursion != new_config.max_recursion
         {
             self.max_recursion = new_config.max_recursion.clone();
         }
 
+        // max_diffuse_bounces
+        if default_config.max_diffuse_bounces != new_config.max_diffuse_bounces
+        {
+            self.max_diffuse_bounces = new_config.max_diffuse_bounces;
+        }
+
+        // max_glossy_bounces
+        if default_config.max_glossy_bounces != new_config.max_glossy_bounces
+        {
+            self.max_glossy_bounces = new_config.max_glossy_bounces;
+        }
+
+        // max_transmission_bounces
+        if default_config.max_transmission_bounces != new_config.max_transmission_bounces
+        {
+            self.max_transmission_bounces = new_config.max_transmission_bounces;
+        }
+
+        // indirect_clamp
+        if !approx_equal(default_config.indirect_clamp, new_config.indirect_clamp)
+        {
+            self.indirect_clamp = new_config.indirect_clamp;
+        }
+
         // gamma_correction
         if default_config.gamma_correction != new_config.gamma_correction
         {
@@ -186,16 +470,29 @@ impl RaytracingConfig
 
     pub fn print(&self)
     {
+        println!("integrator: {:?}", self.integrator);
+        println!("render_mode: {:?}", self.render_mode);
         println!("monte_carlo: {:?}", self.monte_carlo);
+        println!("spectral: {:?}", self.spectral);
         println!("samples: {:?}", self.samples);
-
-        println!("focal_length: {:?}", self.focal_length);
-        println!("aperture_size: {:?}", self.aperture_size);
+        println!("sampler: {:?}", self.sampler);
+        println!("adaptive_threshold: {:?}", self.adaptive_threshold);
+        println!("sample_count_image: {:?}", self.sample_count_image);
+        println!("progressive: {:?}", self.progressive);
+        println!("time_limit: {:?}", self.time_limit);
 
         println!("fog_density: {:?}", self.fog_density);
         println!("fog_color: {:?}", self.fog_color);
+        println!("fog_anisotropy: {:?}", self.fog_anisotropy);
+
+        println!("photon_count: {:?}", self.photon_count);
+        println!("photon_radius: {:?}", self.photon_radius);
 
         println!("max_recursion: {:?}", self.max_recursion);
+        println!("max_diffuse_bounces: {:?}", self.max_diffuse_bounces);
+        println!("max_glossy_bounces: {:?}", self.max_glossy_bounces);
+        println!("max_transmission_bounces: {:?}", self.max_transmission_bounces);
+        println!("indirect_clamp: {:?}", self.indirect_clamp);
         println!("gamma_correction: {:?}", self.gamma_correction);
     }
 }
@@ -206,7 +503,11 @@ pub struct Raytracing
 {
     pub scene: Arc<RwLock<Scene>>,
     pub config: RaytracingConfig,
-    pub post_processing: PostProcessingConfig
+    pub post_processing: PostProcessingConfig,
+
+    pub photon_map: PhotonMap,
+    pub light_distribution: LightDistribution, //light selection of the bidirectional path tracer
+    pub integrator_fallback: Option<IntegratorType> //used instead of the configured integrator if the scene needs it
 }
 
 impl Raytracing
@@ -219,7 +520,40 @@ impl Raytracing
 
             config: RaytracingConfig::new(),
 
-            post_processing: PostProcessingConfig::new()
+            post_processing: PostProcessingConfig::new(),
+
+            photon_map: PhotonMap::new(),
+            light_distribution: LightDistribution::new(),
+            integrator_fallback: None
+        }
+    }
+
+    // subsurface scattering is only supported by the path tracer (whitted falls back to it, bidirectional uses the surface bsdf)
+    pub fn init_integrator(&mut self)
+    {
+        self.integrator_fallback = None;
+        self.light_distribution = LightDistribution::new();
+
+        let scene_arc = self.scene.clone();
+        let scene = scene_arc.read().unwrap();
+
+        let has_subsurface = scene.items.iter().any(|item| item.get_basic().visible && item.get_material_cache_without_textures().subsurface > 0.0);
+
+        if has_subsurface && self.config.integrator == IntegratorType::Whitted
+        {
+            println!("warning: the scene contains subsurface scattering materials, which are not supported by the whitted tracer -- falling back to path tracing");
+            self.integrator_fallback = Some(IntegratorType::PathTracing);
+        }
+
+        if self.config.integrator == IntegratorType::Bidirectional
+        {
+            if has_subsurface
+            {
+                println!("warning: the scene contains subsurface scattering materials, which are shaded with the surface bsdf only by the bidirectional path tracer");
+            }
+
+            //lights are chosen by their emitted power (once per rendering)
+            self.light_distribution = self.get_light_distribution(&scene);
         }
     }
 
@@ -248,21 +582,32 @@ impl Raytracing
         let sensor_x = ((x_f + 0.5) / w) * 2.0 - 1.0;
         let sensor_y = 1.0 - ((y_f + 0.5) / h) * 2.0;
 
-        let mut pixel_pos = Vector4::new(sensor_x, sensor_y, -CAM_CLIPPING_PLANE_DIST, 1.0);
-        pixel_pos = scene.cam.projection_inverse * pixel_pos;
-        pixel_pos.w = 1.0;
+        let mut ray;
+        if scene.cam.projection_type != CameraProjection::Perspective
+        {
+            ray = scene.cam.get_projection_ray(sensor_x, sensor_y)?;
+        }
+        else
+        {
+            let mut pixel_pos = Vector4::new(sensor_x, sensor_y, -CAM_CLIPPING_PLANE_DIST, 1.0);
+            pixel_pos = scene.cam.projection_inverse * pixel_pos;
+            pixel_pos.w = 1.0;
 
-        let mut ray_dir = pixel_pos - DEFAULT_VIEW_POS;
-        ray_dir.w = 0.0;
+            let mut ray_dir = pixel_pos - DEFAULT_VIEW_POS;
+            ray_dir.w = 0.0;
 
-        let origin = scene.cam.view_inverse * pixel_pos;
-        let dir = scene.cam.view_inverse * ray_dir;
+            let origin = scene.cam.view_inverse * pixel_pos;
+            let dir = scene.cam.view_inverse * ray_dir;
 
-        let mut ray = Ray::new(Point3::<f32>::from(origin.xyz()), Vector3::<f32>::from(dir.xyz()));
+            ray = Ray::new(Point3::<f32>::from(origin.xyz()), Vector3::<f32>::from(dir.xyz()));
+        }
         ray.dir = ray.dir.normalize();
 
+        //pick at shutter open
+        set_sample_time(0.0);
+
         //intersect
-        let intersection = self.trace(&scene, &ray, false, false, 1);
+        let intersection = self.trace(&scene, &ray, false, RayType::Camera, 1);
 
         if let Some(intersection) = intersection
         {
@@ -276,6 +621,34 @@ impl Raytracing
     {
         let scene = self.scene.read().unwrap();
 
+        let samples = self.config.samples.max(1) as u32;
+        let adaptive = self.config.adaptive_threshold > 0.0 && samples > ADAPTIVE_MIN_SAMPLES;
+
+        let mut accumulation = PixelAccumulation::new();
+
+        for sample_index in 0..samples
+        {
+            self.render_sample(&scene, x, y, sample_index, &mut accumulation);
+
+            if adaptive && accumulation.is_converged(self.config.adaptive_threshold)
+            {
+                break;
+            }
+        }
+
+        self.get_pixel_data(&accumulation, x, y)
+    }
+
+    // renders one sample (progressive rendering: one pass) of the pixel into the accumulation
+    pub fn render_pass(&self, x: i32, y: i32, pass: u32, accumulation: &mut PixelAccumulation)
+    {
+        let scene = self.scene.read().unwrap();
+
+        self.render_sample(&scene, x, y, pass, accumulation);
+    }
+
+    pub fn render_sample(&self, scene: &Scene, x: i32, y: i32, sample_index: u32, accumulation: &mut PixelAccumulation)
+    {
         let x_f = x as f32;
         let y_f = y as f32;
 
@@ -285,148 +658,153 @@ impl Raytracing
         let x_step = 2.0 / w;
         let y_step = 2.0 / h;
 
-        let mut color = Vector3::new(0.0, 0.0, 0.0);
+        let samples = self.config.samples.max(1) as u32;
 
-        let mut samples = vec![];
+        start_pixel_sample(self.config.sampler, samples, x as u32, y as u32, sample_index);
 
-        let mut cell_size = 1;
-        if self.config.samples > 1
+        //motion blur: random time in the shutter interval (used by the shapes and the camera)
+        let mut time = 0.0;
+        if scene.cam.has_motion_blur()
         {
-            //increase samples value to not exactly match power of two with sampling steps
-            //otherweise this would result in crappy visual effects for DOF blur
-            cell_size = (self.config.samples + 2).next_power_of_two() / 2;
+            time = sample_1d();
         }
+        set_sample_time(time);
+
+        //calculate the movement arrount the x/y pos to render (based on anti aliasing and apperture)
+        let mut x_trans = 0.0;
+        let mut y_trans = 0.0;
 
-        for x_i in 0..cell_size
+        if samples > 1
         {
-            for y_i in 0..cell_size
-            {
-                samples.push((x_i, y_i));
-            }
+            let (u, v) = sample_2d();
+            x_trans = x_step * (u - 0.5);
+            y_trans = y_step * (v - 0.5);
         }
 
-        //randomize
-        let mut rng = StdRng::seed_from_u64(0);
-        samples.shuffle(&mut rng);
-
-        //truncate by samples-amout
-        samples.truncate(self.config.samples as usize);
+        let ray;
 
-        let mut depth = 0.0;
-        let mut normal = Vector3::<f32>::zeros();
-        let mut object_id = 0;
-
-        for sample in &samples
+        //orthographic, fisheye, panorama and stereo projections
+        if scene.cam.projection_type != CameraProjection::Perspective
         {
-            let x_i = sample.0;
-            let y_i = sample.1;
+            let sensor_x = (((x_f + 0.5) / w) * 2.0 - 1.0) + x_trans;
+            let sensor_y = (1.0 - ((y_f + 0.5) / h) * 2.0) + y_trans;
 
-            //calculate the movement arrount the x/y pos to render (based on anti aliasing and apperture)
-            let mut x_trans = x_step * x_i as f32 * (1.0 / cell_size as f32);
-            let mut y_trans = y_step * y_i as f32 * (1.0 / cell_size as f32);
+            let projection_ray = scene.cam.get_projection_ray(sensor_x, sensor_y);
 
-            //move translation to center if needed
-            if self.config.aperture_size > 1.0 && self.config.focal_length > 1.0 && self.config.samples > 1
+            //outside of the image circle
+            if projection_ray.is_none()
             {
-                x_trans -= x_step / 2.0;
-                y_trans -= y_step / 2.0;
+                accumulation.add_sample(Vector3::<f32>::zeros(), 0.0, Vector3::<f32>::zeros(), 0, Vector3::<f32>::zeros());
+                return;
             }
 
-            let ray;
+            ray = projection_ray.unwrap();
+        }
+        //with or without anti aliasing and thin lens DOF
+        else
+        {
+            //map x/y to -1 <=> +1
+            let sensor_x = (((x_f + 0.5) / w) * 2.0 - 1.0) + x_trans;
+            let sensor_y = (1.0 - ((y_f + 0.5) / h) * 2.0) + y_trans;
+
+            let mut pixel_pos = Vector4::new(sensor_x, sensor_y, -CAM_CLIPPING_PLANE_DIST, 1.0);
+            pixel_pos = scene.cam.projection_inverse * pixel_pos;
+            pixel_pos.w = 1.0;
 
-            //DOF (depth of field)
-            if self.config.aperture_size > 1.0 && self.config.focal_length > 1.0
-            {
-                let aperture_scale = scene.cam.width as f32 / APERTURE_BASE_RESOLUTION;
-                x_trans *= self.config.aperture_size * aperture_scale;
-                y_trans *= self.config.aperture_size * aperture_scale;
+            let mut ray_dir = pixel_pos - DEFAULT_VIEW_POS;
+            ray_dir.w = 0.0;
 
-                // ***** calculate the center pixel pos
-                let center_x = ((x_f + 0.5) / w) * 2.0 - 1.0;
-                let center_y = 1.0 - ((y_f + 0.5) / h) * 2.0;
+            let origin = scene.cam.view_inverse * pixel_pos;
+            let dir = scene.cam.view_inverse * ray_dir;
 
-                let mut center_pixel_pos = Vector4::new(center_x, center_y, -CAM_CLIPPING_PLANE_DIST, 1.0);
-                center_pixel_pos = scene.cam.projection_inverse * center_pixel_pos;
-                center_pixel_pos.w = 1.0;
+            let mut origin = Point3::<f32>::from(origin.xyz());
+            let mut dir = Vector3::<f32>::from(dir.xyz()).normalize();
 
-                let mut ray_dir = center_pixel_pos - DEFAULT_VIEW_POS;
-                ray_dir.w = 0.0;
+            //thin lens DOF of the physical camera (focus distance and lens radius in scene units --> resolution independent)
+            let lens_radius = scene.cam.get_lens_radius();
+            if lens_radius > 0.0
+            {
+                let cam_dir = scene.cam.dir.normalize();
+                let right = cam_dir.cross(&scene.cam.up).normalize();
+                let up = right.cross(&cam_dir);
 
-                let origin = scene.cam.view_inverse * DEFAULT_VIEW_POS;
-                let dir = (scene.cam.view_inverse * ray_dir).normalize();
+                let cos_theta = dir.dot(&cam_dir);
+                let focus_point = scene.cam.eye_pos + (dir * (scene.cam.focus_distance / cos_theta));
+                let near_dist = (origin - scene.cam.eye_pos).magnitude() * cos_theta;
 
-                let dist = ray_dir.xyz().magnitude();
+                let (u, v) = sample_2d();
+                let (lens_x, lens_y) = concentric_sample_disk(u, v);
+                let lens_point = scene.cam.eye_pos + ((right * lens_x + up * lens_y) * lens_radius);
 
-                // ***** calculate the focal point
-                let dist_perpendicular = CAM_CLIPPING_PLANE_DIST;
-                let p = origin + ((dist_perpendicular/(dist/(dist + self.config.focal_length)))*dir);
+                dir = (focus_point - lens_point).normalize();
+                origin = lens_point + (dir * (near_dist / dir.dot(&cam_dir)));
+            }
 
-                // ***** calculate ray
-                let ray_sensor_x = (((x_f + 0.5) / w) * 2.0 - 1.0) + x_trans;
-                let ray_sensor_y = (1.0 - ((y_f + 0.5) / h) * 2.0) + y_trans;
+            ray = Ray::new(origin, dir);
+        }
 
-                let mut pixel_pos = Vector4::new(ray_sensor_x, ray_sensor_y, -CAM_CLIPPING_PLANE_DIST, 1.0);
-                pixel_pos = scene.cam.projection_inverse * pixel_pos;
-                pixel_pos.w = 1.0;
+        let ray = scene.cam.apply_motion(ray, time);
 
-                let ray_origin = scene.cam.view_inverse * pixel_pos;
-                let mut ray_dir = p - ray_origin; //p is already in view mat space
-                ray_dir.w = 0.0;
+        //debug visualizations (without lighting)
+        if self.config.render_mode != RenderMode::Shaded
+        {
+            let res = self.get_color_depth_normal_id_debug(scene, ray);
+            accumulation.add_sample(res.0, res.1, res.2, res.3, res.4);
+            return;
+        }
 
-                ray = Ray::new(Point3::<f32>::from(ray_origin.xyz()), Vector3::<f32>::from(ray_dir.xyz()));
-            }
-            //with or without anti aliasing and without DOF
-            else
-            {
-                //map x/y to -1 <=> +1
-                let sensor_x = (((x_f + 0.5) / w) * 2.0 - 1.0) + x_trans;
-                let sensor_y = (1.0 - ((y_f + 0.5) / h) * 2.0) + y_trans;
+        //spectral mode: one wavelength per sample (stratified by the sampler -- only sampled if a dispersive material is hit)
+        let mut wavelength_u = None;
+        if self.config.spectral
+        {
+            wavelength_u = Some(sample_1d());
+        }
+        let spectral = SpectralSample::new(wavelength_u);
 
-                let mut pixel_pos = Vector4::new(sensor_x, sensor_y, -CAM_CLIPPING_PLANE_DIST, 1.0);
-                pixel_pos = scene.cam.projection_inverse * pixel_pos;
-                pixel_pos.w = 1.0;
+        let res = match self.integrator_fallback.unwrap_or(self.config.integrator)
+        {
+            IntegratorType::Whitted => self.get_color_depth_normal_id(scene, ray, RayType::Camera, 1, &self.get_global_medium(scene), &spectral),
+            IntegratorType::PathTracing => self.get_color_depth_normal_id_path_tracing(scene, ray, &spectral),
+            IntegratorType::Bidirectional => self.get_color_depth_normal_id_bidirectional(scene, ray, &spectral),
+        };
 
-                let mut ray_dir = pixel_pos - DEFAULT_VIEW_POS;
-                ray_dir.w = 0.0;
+        let mut color = res.0.component_mul(&spectral.get_rgb_weight());
 
-                let origin = scene.cam.view_inverse * pixel_pos;
-                let dir = scene.cam.view_inverse * ray_dir;
+        color *= scene.cam.get_exposure();
 
-                ray = Ray::new(Point3::<f32>::from(origin.xyz()), Vector3::<f32>::from(dir.xyz()));
-            }
+        accumulation.add_sample(color, res.1, res.2, res.3, res.4);
+    }
 
-            let res = self.get_color_depth_normal_id(&scene, ray, 1);
+    pub fn get_pixel_data(&self, accumulation: &PixelAccumulation, x: i32, y: i32) -> PixelData
+    {
+        let samples = accumulation.samples.max(1) as f32;
 
-            color += res.0;
-            depth += res.1;
-            normal += res.2;
-            object_id = res.3;
-        }
+        let color = accumulation.color / samples;
+        let depth = accumulation.depth / samples;
+        let normal = accumulation.normal / samples;
+        let albedo = accumulation.albedo / samples;
 
-        color /= samples.len() as f32;
-        depth /= samples.len() as f32;
-        normal /= samples.len() as f32;
+        let (r, g, b) = self.encode_color(color);
 
-        //clamp
-        color.x = color.x.min(1.0);
-        color.y = color.y.min(1.0);
-        color.z = color.z.min(1.0);
+        PixelData { r: r, g: g, b: b, color: color, x: x, y: y, depth: depth, albedo: albedo, object_id: accumulation.object_id, normal: normal.normalize(), samples: accumulation.samples }
+    }
 
-        let mut r = (color.x * 255.0) as u8;
-        let mut g = (color.y * 255.0) as u8;
-        let mut b = (color.z * 255.0) as u8;
+    // linear color to 8 bit (clamped and gamma encoded)
+    pub fn encode_color(&self, color: Vector3<f32>) -> (u8, u8, u8)
+    {
+        //clamp
+        let color = color.map(|c| c.clamp(0.0, 1.0));
 
-        if self.config.gamma_correction
+        //debug render modes are showing the raw values
+        if self.config.gamma_correction && self.config.render_mode == RenderMode::Shaded
         {
-            r = (self.gamma_encode(color.x) * 255.0) as u8;
-            g = (self.gamma_encode(color.y) * 255.0) as u8;
-            b = (self.gamma_encode(color.z) * 255.0) as u8;
+            return ((self.gamma_encode(color.x) * 255.0) as u8, (self.gamma_encode(color.y) * 255.0) as u8, (self.gamma_encode(color.z) * 255.0) as u8);
         }
 
-        PixelData { r: r, g: g, b: b, x: x, y: y, depth: depth, object_id: object_id, normal: normal.normalize() }
+        ((color.x * 255.0) as u8, (color.y * 255.0) as u8, (color.z * 255.0) as u8)
     }
 
-    pub fn trace<'a>(&self, scene: &'a Scene, ray: &Ray, stop_on_first_hit: bool, for_shadow: bool, depth: u16) -> Option<(f32, Vector3<f32>, &'a dyn Shape, u32)>
+    pub fn get_trace_candidates<'a>(&self, scene: &'a Scene, ray: &Ray) -> Vec<&'a ScemeItem>
     {
         let mut items = vec![];
 
@@ -443,6 +821,14 @@ impl Raytracing
             }
         }
 
+        items
+    }
+
+    pub fn trace<'a>(&self, scene: &'a Scene, ray: &Ray, stop_on_first_hit: bool, ray_type: RayType, depth: u16) -> Option<(f32, Vector3<f32>, &'a dyn Shape, u32)>
+    {
+        let items = self.get_trace_candidates(scene, ray);
+        let for_shadow = ray_type == RayType::Shadow;
+
         //find hits (bbox based)
         let mut hits: Vec<HitResult> = vec![];
         for item in items
@@ -450,8 +836,14 @@ impl Raytracing
             let dist = item.intersect_b_box(&ray, for_shadow);
             if let Some(dist) = dist
             {
+                let basic = item.get_basic();
+                if !basic.visible || !basic.ray_visibility.is_visible(ray_type)
+                {
+                    continue;
+                }
+
                 let material = item.get_material_cache_without_textures();
-                if item.get_basic().visible && material.alpha > 0.0 && (!for_shadow || material.cast_shadow) && (!material.reflection_only || depth > 1)
+                if material.alpha > 0.0 && (!for_shadow || material.cast_shadow) && (!material.reflection_only || depth > 1)
                 {
                     hits.push(HitResult{ item: item.as_ref(), dist: dist });
                 }
@@ -532,7 +924,7 @@ impl Raytracing
         }
     }
 
-    fn fresnel(&self, incident: Vector3<f32>, normal: Vector3<f32>, index: f32) -> f32
+    pub fn fresnel(&self, incident: Vector3<f32>, normal: Vector3<f32>, index: f32) -> f32
     {
         let i_dot_n = incident.dot(&normal);
 
@@ -562,6 +954,75 @@ impl Raytracing
         }
     }
 
+    // rgb fresnel reflectance of a dielectric including the interference of a thin film
+    pub fn fresnel_thin_film(&self, incident: Vector3<f32>, normal: Vector3<f32>, index: f32, thin_film: &ThinFilm) -> Vector3<f32>
+    {
+        let kr = self.fresnel(incident, normal, index);
+
+        //total internal reflection
+        if !thin_film.is_active() || kr >= 1.0
+        {
+            return Vector3::<f32>::repeat(kr);
+        }
+
+        let base_f0 = ((index - 1.0) / (index + 1.0)).powi(2);
+
+        thin_film.apply(Vector3::<f32>::repeat(kr), incident.dot(&normal).abs(), Vector3::<f32>::repeat(base_f0))
+    }
+
+    // chooses between fresnel reflection and transmission (true = reflection) and returns the sample weight
+    pub fn sample_fresnel(&self, kr: Vector3<f32>) -> (bool, Vector3<f32>)
+    {
+        let probability = kr.mean().clamp(0.0, 1.0);
+
+        if sample_1d() < probability
+        {
+            (true, kr / probability)
+        }
+        else
+        {
+            (false, kr.map(|k| 1.0 - k) / (1.0 - probability))
+        }
+    }
+
+    // the fog settings are describing the global homogeneous medium
+    pub fn get_global_medium(&self, scene: &Scene) -> Option<MediumInstance>
+    {
+        if self.config.fog_density <= 0.0
+        {
+            return None;
+        }
+
+        let albedo = self.config.fog_color;
+        let absorption = (Vector3::<f32>::new(1.0, 1.0, 1.0) - albedo) * self.config.fog_density;
+        let scattering = albedo * self.config.fog_density;
+
+        let medium = Medium::new_homogeneous(absorption, scattering, self.config.fog_anisotropy);
+
+        Some(MediumInstance::new_global(medium, scene.b_box))
+    }
+
+    // medium of the ray after passing through the surface of the item
+    pub fn get_medium_after_transmission(&self, scene: &Scene, medium: &Option<MediumInstance>, item: &dyn Shape, normal: Vector3<f32>, dir: Vector3<f32>) -> Option<MediumInstance>
+    {
+        let interior_medium = item.get_material_cache_without_textures().get_interior_medium();
+
+        if interior_medium.is_none()
+        {
+            return medium.clone();
+        }
+
+        //leaving the object
+        if dir.dot(&normal) > 0.0
+        {
+            return self.get_global_medium(scene);
+        }
+
+        let basic = item.get_basic();
+
+        Some(MediumInstance::new_interior(interior_medium.unwrap(), basic.id, basic.get_inverse_trans(), basic.b_box))
+    }
+
     pub fn jitter(&self, dir: Vector3<f32>, spread: f32) -> Vector3<f32>
     {
         if spread <= 0.0
@@ -613,9 +1074,11 @@ impl Raytracing
             return dir;
         }
 
-        let z = rand::thread_rng().gen_range(z_range);
+        let (u, v) = sample_2d();
+
+        let z = z_range.start + (z_range.end - z_range.start) * u;
         let r = (1.0 - z * z).sqrt();
-        let theta = rand::thread_rng().gen_range(-PI..PI);
+        let theta = (2.0 * v - 1.0) * PI;
         let x = r * theta.cos();
         let y = r * theta.sin();
 
@@ -711,29 +1174,280 @@ impl Raytracing
         item_color
     }
 
+    pub fn get_tangent_frame(&self, normal: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>)
+    {
+        let mut tangent = normal.cross(&Vector3::<f32>::new(0.0, 1.0, 0.0));
+
+        if tangent.magnitude()  <= 0.0001
+        {
+            tangent = normal.cross(&Vector3::<f32>::new(0.0, 0.0, 1.0));
+        }
+
+        tangent = tangent.normalize();
+        let bitangent = normal.cross(&tangent).normalize();
+
+        (tangent, bitangent)
+    }
+
+    // tangent frame aligned to the uv derivatives of the shape (falls back to the generic frame)
+    pub fn get_uv_tangent_frame(&self, item: &dyn Shape, hit_point: Point3<f32>, face_id: u32, normal: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>)
+    {
+        if let Some(tangent) = item.get_tangent(hit_point, face_id)
+        {
+            let tangent = tangent - normal * normal.dot(&tangent);
+
+            if tangent.magnitude_squared() > 0.000001
+            {
+                let tangent = tangent.normalize();
+                return (tangent, normal.cross(&tangent));
+            }
+        }
+
+        self.get_tangent_frame(normal)
+    }
+
+    pub fn get_surface_normal(&self, material: &Box<Material>, uv: &Option<Point2<f32>>, normal: Vector3<f32>) -> Vector3<f32>
+    {
+        self.get_mapped_normal(material, uv, normal, TextureType::Normal, material.normal_map_strength)
+    }
+
+    pub fn get_mapped_normal(&self, material: &Box<Material>, uv: &Option<Point2<f32>>, normal: Vector3<f32>, tex_type: TextureType, strength: f32) -> Vector3<f32>
+    {
+        let mut surface_normal = normal;
+
+        let normal_tex_color = self.get_tex_color(material, uv, tex_type);
+        if let Some(normal_tex_color) = normal_tex_color
+        {
+            let (tangent, bitangent) = self.get_tangent_frame(normal);
+
+            //to tagent space -- n * 2 - 1
+            let mut normal_map = normal_tex_color.xyz();
+            normal_map.x = (normal_map.x * 2.0) - 1.0;
+            normal_map.y = (normal_map.y * 2.0) - 1.0;
+            normal_map.z = (normal_map.z * 2.0) - 1.0;
+
+            normal_map.x *= strength;
+            normal_map.y *= strength;
+
+            normal_map = normal_map.normalize();
+
+            let tbn = Matrix3::<f32>::from_columns(&[tangent, bitangent, normal]);
+
+            surface_normal = (tbn * normal_map).normalize();
+        }
+
+        surface_normal
+    }
+
+    pub fn get_alpha(&self, material: &Box<Material>, uv: &Option<Point2<f32>>) -> f32
+    {
+        let base_color = self.get_item_color(material, uv, LightningColorType::Base);
+
+        let mut alpha = material.alpha * base_color.w;
+        let alpha_tex_color = self.get_tex_color(material, uv, TextureType::Alpha);
+        if let Some(alpha_tex_color) = alpha_tex_color
+        {
+            alpha *= alpha_tex_color.x;
+        }
+
+        alpha
+    }
+
+    // the thickness texture blends between the min and max thickness
+    pub fn get_thin_film(&self, material: &Box<Material>, uv: &Option<Point2<f32>>) -> ThinFilm
+    {
+        let mut thin_film = ThinFilm::new();
+
+        if material.iridescence > 0.0
+        {
+            thin_film.weight = material.iridescence;
+            thin_film.ior = material.iridescence_ior;
+            thin_film.thickness = material.iridescence_thickness_max;
+
+            if let Some(thickness_tex) = self.get_tex_color(material, uv, TextureType::IridescenceThickness)
+            {
+                thin_film.thickness = material.iridescence_thickness_min + (material.iridescence_thickness_max - material.iridescence_thickness_min) * thickness_tex.y;
+            }
+        }
+
+        thin_film
+    }
+
+    pub fn get_surface_interaction(&self, ray: &Ray, hit_dist: f32, normal: Vector3<f32>, item: &dyn Shape, face_id: u32, material: &Box<Material>) -> SurfaceInteraction
+    {
+        let hit_point = ray.origin + (ray.dir * hit_dist);
+
+        //get uv
+        let mut uv = None;
+        if material.has_any_texture()
+        {
+            uv = Some(item.get_uv(hit_point, face_id));
+        }
+
+        //normal mapping
+        let surface_normal = self.get_surface_normal(material, &uv, normal);
+
+        //colors
+        let ambient_color = self.get_item_color(material, &uv, LightningColorType::Ambient);
+        let base_color = self.get_item_color(material, &uv, LightningColorType::Base);
+
+        //alpha
+        let alpha = self.get_alpha(material, &uv);
+
+        //reflectivity (the reflectivity texture is used as metallic map for pbr materials)
+        let mut reflectivity = material.reflectivity;
+        let mut metallic = material.metallic;
+        let tex_reflexivity = self.get_tex_color(material, &uv, TextureType::Reflectivity);
+        if let Some(tex_reflexivity) = tex_reflexivity
+        {
+            if material.shading_model == ShadingModel::Pbr
+            {
+                metallic *= tex_reflexivity.x;
+            }
+            else
+            {
+                reflectivity = tex_reflexivity.x;
+            }
+        }
+
+        //roughness map (overwrites roughness material setting -- or scales the roughness factor for pbr materials)
+        let mut roughness = 0.0;
+        let mut pbr_roughness = material.pbr_roughness;
+        let roughness_tex_color = self.get_tex_color(material, &uv, TextureType::Roughness);
+
+        if material.shading_model == ShadingModel::Pbr
+        {
+            if let Some(roughness_tex_color) = roughness_tex_color
+            {
+                pbr_roughness *= roughness_tex_color.x;
+            }
+        }
+        else if material.monte_carlo
+        {
+            roughness = material.roughness;
+
+            if let Some(roughness_tex_color) = roughness_tex_color
+            {
+                roughness = (1.0 / PI / 2.0) * roughness_tex_color.x;
+            }
+        }
+
+        //ambient occlusion
+        let mut ambient_occlusion = 1.0;
+        if let Some(ambient_occlusion_tex) = self.get_tex_color(material, &uv, TextureType::AmbientOcclusion)
+        {
+            ambient_occlusion = ambient_occlusion_tex.x;
+        }
+
+        //clearcoat with its own normal map
+        let mut clearcoat_normal = None;
+        if material.clearcoat > 0.0 && material.has_texture(TextureType::ClearcoatNormal)
+        {
+            clearcoat_normal = Some(self.get_mapped_normal(material, &uv, normal, TextureType::ClearcoatNormal, material.clearcoat_normal_map_strength));
+        }
+
+        //thin film
+        let thin_film = self.get_thin_film(material, &uv);
+
+        //anisotropy direction (rotated tangent -- the frame follows the uv layout of the shape)
+        let mut anisotropy = material.anisotropy;
+        let mut tangent = None;
+        if anisotropy > 0.0
+        {
+            let (t, b) = self.get_uv_tangent_frame(item, hit_point, face_id, surface_normal);
+
+            //direction (rg) and strength (b) in tangent space
+            let mut direction = Vector2::<f32>::new(1.0, 0.0);
+            if let Some(anisotropy_tex) = self.get_tex_color(material, &uv, TextureType::Anisotropy)
+            {
+                direction = Vector2::<f32>::new(anisotropy_tex.x * 2.0 - 1.0, anisotropy_tex.y * 2.0 - 1.0);
+                anisotropy *= anisotropy_tex.z;
+            }
+
+            let (sin, cos) = material.anisotropy_rotation.sin_cos();
+            let direction = Vector2::<f32>::new(direction.x * cos - direction.y * sin, direction.x * sin + direction.y * cos);
+
+            tangent = Some(t * direction.x + b * direction.y);
+        }
+
+        SurfaceInteraction
+        {
+            hit_point: hit_point,
+            normal: normal,
+            surface_normal: surface_normal,
+            uv: uv,
+
+            base_color: base_color.xyz(),
+            emission: ambient_color.xyz(),
+            alpha: alpha,
+            reflectivity: reflectivity,
+            roughness: roughness,
+            refraction_index: material.refraction_index,
+            ambient_occlusion: ambient_occlusion,
+
+            shading_model: material.shading_model,
+            metallic: metallic,
+            pbr_roughness: pbr_roughness,
+
+            clearcoat: material.clearcoat,
+            clearcoat_roughness: material.clearcoat_roughness,
+            clearcoat_normal: clearcoat_normal,
+            sheen_color: material.sheen_color,
+            sheen_roughness: material.sheen_roughness,
+            anisotropy: anisotropy,
+            tangent: tangent,
+
+            thin_film: thin_film,
+        }
+    }
+
     pub fn reflect(&self, i: Vector3<f32>, n: Vector3<f32>) -> Vector3<f32>
     {
         //https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/reflect.xhtml
         i - 2.0 * n.dot(&i) * n
     }
 
-    pub fn get_color_depth_normal_id(&self, scene: &Scene, ray: Ray, depth: u16) -> (Vector3<f32>, f32, Vector3<f32>, u32)
+    pub fn get_color_depth_normal_id(&self, scene: &Scene, ray: Ray, ray_type: RayType, depth: u16, medium: &Option<MediumInstance>, wavelength: &SpectralSample) -> (Vector3<f32>, f32, Vector3<f32>, u32, Vector3<f32>)
     {
         let mut r = ray;
         r.dir = r.dir.normalize();
 
         //intersect
-        let intersection = self.trace(&scene, &r, false, false, depth);
+        let intersection = self.trace(&scene, &r, false, ray_type, depth);
 
         let mut out_depth: f32 = 0.0;
         let mut out_normal = Vector3::zeros();
         let mut out_id: u32 = 0;
+        let mut out_albedo = Vector3::zeros();
 
         let mut color = Vector3::new(0.0, 0.0, 0.0);
+        let mut max_dist = std::f32::MAX;
 
-        if let Some(intersection) = intersection
+        //area lights in front of the nearest object are visible (camera, reflection and refraction rays)
+        let mut object_dist = std::f32::MAX;
+        if let Some(intersection) = &intersection
+        {
+            object_dist = intersection.0;
+        }
+
+        let area_light_hit = self.intersect_area_lights(scene, &r, object_dist);
+
+        if let Some((light_index, light_dist, light_normal)) = area_light_hit
+        {
+            let light = &scene.lights[light_index];
+
+            max_dist = light_dist;
+
+            out_depth = light_dist;
+            out_normal = light_normal;
+            out_id = light.id;
+
+            color = light.get_area_radiance();
+        }
+        else if let Some(intersection) = intersection
         {
             let hit_dist = intersection.0;
+            max_dist = hit_dist;
             let normal = intersection.1;
             let item = intersection.2;
             let face_id = intersection.3;
@@ -743,90 +1457,45 @@ impl Raytracing
             out_normal = normal;
             out_id = intersection.2.get_basic().id;
 
-            let mut surface_normal = normal;
-            let hit_point = r.origin + (r.dir * hit_dist);
+            //uv, normal mapping, colors, alpha and roughness (textures included)
+            let surface = self.get_surface_interaction(&r, hit_dist, normal, item, face_id, &material);
 
-            //get uv
-            let mut uv = None;
-            if material.has_any_texture()
-            {
-                uv = Some((*item).get_uv(hit_point, face_id));
-            }
+            let hit_point = surface.hit_point;
+            let uv = surface.uv;
+            let mut surface_normal = surface.surface_normal;
 
-            //normal mapping
-            let normal_tex_color = self.get_tex_color(&material, &uv, TextureType::Normal);
-            if let Some(normal_tex_color) = normal_tex_color
+            //microfacet bsdf for pbr materials
+            let mut pbr_bsdf = None;
+            if material.shading_model == ShadingModel::Pbr
             {
-                let mut tangent = normal.cross(&Vector3::<f32>::new(0.0, 1.0, 0.0));
-
-                if tangent.magnitude()  <= 0.0001
-                {
-                    tangent = normal.cross(&Vector3::<f32>::new(0.0, 0.0, 1.0));
-                }
-
-                tangent = tangent.normalize();
-                let bitangent = normal.cross(&tangent).normalize();
-
-                //to tagent space -- n * 2 - 1
-                let mut normal_map = normal_tex_color.xyz();
-                normal_map.x = (normal_map.x * 2.0) - 1.0;
-                normal_map.y = (normal_map.y * 2.0) - 1.0;
-                normal_map.z = (normal_map.z * 2.0) - 1.0;
-
-                normal_map.x *= material.normal_map_strength;
-                normal_map.y *= material.normal_map_strength;
-
-                normal_map = normal_map.normalize();
-
-                let tbn = Matrix3::<f32>::from_columns(&[tangent, bitangent, normal]);
-
-                surface_normal = (tbn * normal_map).normalize();
+                pbr_bsdf = Some(surface.get_bsdf());
             }
 
-            //roughness map (overwrites roughness material setting)
-            let roughness_tex_color = self.get_tex_color(&material, &uv, TextureType::Roughness);
-            if self.config.monte_carlo && material.monte_carlo && (material.roughness > 0.0 || roughness_tex_color.is_some())
+            //roughness (material setting or roughness map)
+            if pbr_bsdf.is_none() && self.config.monte_carlo && surface.roughness > 0.0
             {
-                let mut roughness = material.roughness;
-
-                if let Some(roughness_tex_color) = roughness_tex_color
-                {
-                    roughness = (1.0 / PI / 2.0) * roughness_tex_color.x;
-                }
-
-                surface_normal = self.jitter(surface_normal, roughness);
+                surface_normal = self.jitter(surface_normal, surface.roughness);
             }
 
             //ambient, diffuse, specular colors
-            let ambient_color = self.get_item_color(&material, &uv, LightningColorType::Ambient);
-            let base_color = self.get_item_color(&material, &uv, LightningColorType::Base);
-            let specular_color = self.get_item_color(&material, &uv, LightningColorType::Specular);
+            let ambient_color = surface.emission;
+            let base_color = surface.base_color;
+            let specular_color = self.get_item_color(&material, &uv, LightningColorType::Specular).xyz();
 
-            //alpha mapping
-            let mut alpha = material.alpha * base_color.w;
-            let alpha_tex_color = self.get_tex_color(&material, &uv, TextureType::Alpha);
-            if let Some(alpha_tex_color) = alpha_tex_color
-            {
-                alpha *= alpha_tex_color.x;
-            }
+            out_albedo = base_color;
+
+            let alpha = surface.alpha;
 
             //diffuse/specular color
             for light in &scene.lights
             {
-                if !light.enabled
+                if !light.is_active() || !light.illuminates(item.get_basic())
                 {
                     continue;
                 }
 
-                //get direction to light based on light type
-                let direction_to_light;
-
-                match light.light_type
-                {
-                    LightType::Directional => direction_to_light = (-light.dir).normalize(),
-                    LightType::Point => direction_to_light = (light.pos - hit_point).normalize(),
-                    LightType::Spot => direction_to_light = (light.pos - hit_point).normalize(),
-                }
+                //get direction to light and intensity based on light type
+                let (direction_to_light, mut intensity, light_dist) = light.get_direction_and_intensity(&hit_point);
 
                 //lambert
                 let dot_light = surface_normal.dot(&direction_to_light).max(0.0);
@@ -841,31 +1510,11 @@ impl Raytracing
 
                 let specular = specular_color * light_power;
 
-                //light intensity
-                let mut intensity;
-                match light.light_type
+                let mut light_color = specular + base;
+                if let Some(pbr_bsdf) = &pbr_bsdf
                 {
-                    LightType::Directional => intensity = light.intensity,
-                    LightType::Point =>
-                    {
-                        let r2 = (light.pos - hit_point).norm() as f32;
-                        intensity = light.intensity / (4.0 * PI * r2)
-                    },
-                    LightType::Spot =>
-                    {
-                        //use point as base and check angle
-                        let r2 = (light.pos - hit_point).norm() as f32;
-                        intensity = light.intensity / (4.0 * PI * r2);
-
-                        let light_dir = light.dir.normalize();
-                        let dot = (-direction_to_light).dot(&light_dir);
-                        let angle = dot.acos();
-
-                        if angle > light.max_angle
-                        {
-                            intensity = 0.0;
-                        }
-                    }
+                    //scaled by PI to match the lambert brightness
+                    light_color = pbr_bsdf.eval(surface_normal, view_dir, direction_to_light) * (dot_light * PI);
                 }
 
                 //shadow
@@ -874,21 +1523,25 @@ impl Raytracing
                     let shadow_ray_start = hit_point + (surface_normal * SHADOW_BIAS);
                     let mut shadow_ray_dir = direction_to_light;
 
-                    if self.config.monte_carlo && material.monte_carlo
+                    //area lights are already sampled over their surface
+                    if self.config.monte_carlo && material.monte_carlo && light.light_type != LightType::Area
                     {
                         shadow_ray_dir = self.jitter(shadow_ray_dir, material.shadow_softness);
                     }
 
+                    //attenuation by the surrounding medium
+                    if let Some(medium) = medium
+                    {
+                        light_color = light_color.component_mul(&medium.transmittance(&shadow_ray_start, &shadow_ray_dir, light_dist, sample_1d()));
+                    }
+
                     let shadow_ray = Ray::new(shadow_ray_start, shadow_ray_dir);
-                    let shadow_intersection = self.trace(&scene, &shadow_ray, true, true, depth);
+                    let shadow_intersection = self.trace(&scene, &shadow_ray, true, RayType::Shadow, depth);
 
                     let mut in_light = shadow_intersection.is_none();
-                    if !in_light && (light.light_type == LightType::Point || light.light_type == LightType::Spot)
+                    if !in_light && light.light_type != LightType::Directional
                     {
-                        let light_dist: Vector3<f32> = light.pos - hit_point;
-                        let len = light_dist.norm();
-
-                        in_light = shadow_intersection.unwrap().0 > len
+                        in_light = shadow_intersection.unwrap().0 > light_dist
                     }
 
                     //shadow intensity (including alpha map based shadow check)
@@ -909,27 +1562,59 @@ impl Raytracing
                             shadow_source_alpha *= shadow_alpha_tex_color.x;
                         }
 
+                        //the light through refractive objects is part of the caustics (photon map)
+                        if !self.photon_map.is_empty() && shadow_obj_material.is_refractive()
+                        {
+                            shadow_source_alpha = 1.0;
+                        }
+
                         intensity = intensity * (1.0 - shadow_source_alpha);
                     }
                 }
 
                 //color based on components
-                color.x = color.x + ((light.color.x * (specular.x + base.x)) * intensity);
-                color.y = color.y + ((light.color.y * (specular.y + base.y)) * intensity);
-                color.z = color.z + ((light.color.z * (specular.z + base.z)) * intensity);
+                color.x = color.x + ((light.color.x * light_color.x) * intensity);
+                color.y = color.y + ((light.color.y * light_color.y) * intensity);
+                color.z = color.z + ((light.color.z * light_color.z) * intensity);
+            }
+
+            //emissive shapes and environment (one random sample per hit)
+            if (!scene.emitters.is_empty() || scene.environment.is_active()) && material.receive_shadow
+            {
+                let view_dir = (-r.dir).normalize();
+
+                //lambert for phong materials
+                let lambert = Bsdf::new(ShadingModel::Phong, base_color, 0.0, 0.0, material.refraction_index);
+                let bsdf = pbr_bsdf.as_ref().unwrap_or(&lambert);
+
+                let scatter = Scatter::Surface { bsdf: bsdf, n: surface_normal, wo: view_dir };
+
+                color += self.sample_emissive_shapes(scene, &scatter, &hit_point, medium, depth, false);
+                color += self.sample_environment(scene, &scatter, &hit_point, medium, depth, false);
+            }
+
+            //caustics (photon map)
+            if !self.photon_map.is_empty() && material.receive_shadow
+            {
+                let view_dir = (-r.dir).normalize();
+
+                let lambert = Bsdf::new(ShadingModel::Phong, base_color, 0.0, 0.0, material.refraction_index);
+                let bsdf = pbr_bsdf.as_ref().unwrap_or(&lambert);
+
+                color += self.get_caustic_radiance(bsdf, surface_normal, view_dir, &hit_point);
             }
 
-            let refraction_index = material.refraction_index;
+            let refraction_index = material.get_refraction_index(wavelength);
 
-            //fresnel
-            let kr = self.fresnel(r.dir, surface_normal, refraction_index);
+            //fresnel (rgb for thin films)
+            let thin_film = surface.thin_film;
+            let kr = self.fresnel_thin_film(r.dir, surface_normal, refraction_index, &thin_film);
 
-            //reflectivity
-            let mut reflectivity = material.reflectivity;
-            let tex_reflexivity = self.get_tex_color(&material, &uv, TextureType::Reflectivity);
-            if let Some(tex_reflexivity) = tex_reflexivity
+            //mirror reflectivity (replaced by the fresnel weighted glossy reflection of the bsdf for pbr materials)
+            let mut reflectivity = surface.reflectivity;
+            if pbr_bsdf.is_some()
             {
-                reflectivity = tex_reflexivity.x;
+                reflectivity = 0.0;
             }
 
             color = color * (1.0 - reflectivity);
@@ -938,12 +1623,51 @@ impl Raytracing
             if reflectivity > 0.0 && depth <= self.config.max_recursion
             {
                 let reflection_ray = self.create_reflection(surface_normal, r.dir, hit_point);
-                let reflection_color = self.get_color_depth_normal_id(scene, reflection_ray, depth + 1).0;
+                let reflection_color = self.get_color_depth_normal_id(scene, reflection_ray, RayType::Reflection, depth + 1, medium, wavelength).0;
 
                 //color = color + (reflection_color * reflectivity * kr);
                 color = color + (reflection_color * reflectivity);
             }
 
+            //glossy reflection of pbr materials (fresnel weighted, fading out with roughness)
+            if let Some(pbr_bsdf) = &pbr_bsdf
+            {
+                let n_dot_v = surface_normal.dot(&(-r.dir)).max(0.0);
+                let specular_weight = pbr_bsdf.fresnel(n_dot_v) * (1.0 - pbr_bsdf.roughness).powi(2);
+
+                if max_component(specular_weight) > PBR_MIN_REFLECTION_WEIGHT && depth <= self.config.max_recursion
+                {
+                    let mut reflection_ray = self.create_reflection(surface_normal, r.dir, hit_point);
+
+                    if self.config.monte_carlo && material.monte_carlo
+                    {
+                        reflection_ray.dir = self.jitter(reflection_ray.dir, pbr_bsdf.alpha() * PI / 2.0);
+                    }
+
+                    let reflection_color = self.get_color_depth_normal_id(scene, reflection_ray, RayType::Reflection, depth + 1, medium, wavelength).0;
+
+                    color = color + reflection_color.component_mul(&specular_weight);
+                }
+
+                //clearcoat reflection (with its own normal and roughness)
+                let clearcoat_normal = pbr_bsdf.get_clearcoat_normal(surface_normal);
+                let clearcoat_weight = pbr_bsdf.get_clearcoat_fresnel(surface_normal, -r.dir) * (1.0 - pbr_bsdf.clearcoat_roughness.clamp(0.0, 1.0)).powi(2);
+
+                if clearcoat_weight > PBR_MIN_REFLECTION_WEIGHT && depth <= self.config.max_recursion
+                {
+                    let mut reflection_ray = self.create_reflection(clearcoat_normal, r.dir, hit_point);
+
+                    if self.config.monte_carlo && material.monte_carlo
+                    {
+                        reflection_ray.dir = self.jitter(reflection_ray.dir, pbr_bsdf.clearcoat_alpha() * PI / 2.0);
+                    }
+
+                    let reflection_color = self.get_color_depth_normal_id(scene, reflection_ray, RayType::Reflection, depth + 1, medium, wavelength).0;
+
+                    color = color + (reflection_color * clearcoat_weight);
+                }
+            }
+
             //refraction
             if alpha < 1.0 && depth <= self.config.max_recursion
             {
@@ -951,12 +1675,13 @@ impl Raytracing
 
                 if let Some(transmission_ray) = transmission_ray
                 {
-                    let transmission_ray_res = self.get_color_depth_normal_id(scene, transmission_ray, depth + 1);
+                    let transmission_medium = self.get_medium_after_transmission(scene, medium, item, normal, transmission_ray.dir);
+                    let transmission_ray_res = self.get_color_depth_normal_id(scene, transmission_ray, RayType::Refraction, depth + 1, &transmission_medium, wavelength);
                     let refraction_color = transmission_ray_res.0;
 
-                    if kr < 1.0
+                    if kr.max() < 1.0
                     {
-                        color = (color * alpha) + (refraction_color * (1.0 - kr) * (1.0 - alpha));
+                        color = (color * alpha) + (refraction_color.component_mul(&kr.map(|k| 1.0 - k)) * (1.0 - alpha));
                     }
                     else
                     {
@@ -966,34 +1691,64 @@ impl Raytracing
                     if approx_equal(alpha, 0.0)
                     {
                         out_id = transmission_ray_res.3;
+                        out_albedo = transmission_ray_res.4;
                     }
                 }
+
+                //interference colors of thin films are visible in the reflection
+                if thin_film.is_active()
+                {
+                    let mut n = surface_normal;
+                    if n.dot(&r.dir) > 0.0
+                    {
+                        n = -n;
+                    }
+
+                    let reflection_ray = self.create_reflection(n, r.dir, hit_point);
+                    let reflection_color = self.get_color_depth_normal_id(scene, reflection_ray, RayType::Reflection, depth + 1, medium, wavelength).0;
+
+                    color = color + (reflection_color.component_mul(&kr) * (1.0 - alpha));
+                }
             }
             else if alpha < 1.0
             {
                 color = color * alpha;
             }
 
-            //fog
-            {
-                let fog_amount = (self.config.fog_density * hit_dist).min(1.0);
+            //ambient occlusion
+            color *= surface.ambient_occlusion;
 
-                color = ((1.0 - fog_amount) * color) + (self.config.fog_color * fog_amount);
-            }
+            //ambient / emissive
+            color += ambient_color;
+        }
+        else
+        {
+            //environment
+            color = scene.environment.eval(&r.dir);
+        }
 
-            //ambient occlusion
-            let ambient_occlusion = self.get_tex_color(&material, &uv, TextureType::AmbientOcclusion);
-            if let Some(ambient_occlusion) = ambient_occlusion
+        //participating media (attenuation and single scattering)
+        if let Some(current_medium) = medium
+        {
+            let mut u = 0.5;
+            let mut jitter = 0.5;
+            if self.config.monte_carlo
             {
-                color.x *= ambient_occlusion.x;
-                color.y *= ambient_occlusion.x;
-                color.z *= ambient_occlusion.x;
+                u = sample_1d();
+                jitter = sample_1d();
             }
 
-            //ambient / emissive
-            color += ambient_color.xyz();
+            color = color.component_mul(&current_medium.transmittance(&r.origin, &r.dir, max_dist, jitter));
+
+            if let Some((scatter_dist, weight)) = current_medium.sample_scattering(&r.origin, &r.dir, max_dist, u, jitter)
+            {
+                let scatter_point = r.origin + (r.dir * scatter_dist);
+                let scatter = Scatter::Medium { anisotropy: current_medium.medium.anisotropy, dir: r.dir };
+
+                color += weight.component_mul(&self.sample_direct_lighting(scene, &scatter, &scatter_point, None, medium, depth));
+            }
         }
 
-        (color, out_depth, out_normal, out_id)
+        (color, out_depth, out_normal, out_id, out_albedo)
     }
 }
\ No newline at end of file
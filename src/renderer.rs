@@ -1,7 +1,7 @@
 use std::thread::JoinHandle;
 use std::sync::mpsc;
 use std::sync::mpsc::{Sender, Receiver};
-use std::sync::Mutex;
+use std::sync::{Mutex, Condvar};
 use std::sync::{Arc, RwLock};
 use std::time::{Instant, Duration};
 
@@ -11,7 +11,7 @@ extern crate num_cpus;
 
 use rand::seq::SliceRandom;
 
-use crate::raytracing::{Raytracing, PixelData};
+use crate::raytracing::{Raytracing, PixelData, PixelAccumulation, ADAPTIVE_MIN_SAMPLES};
 
 //const BLOCK_SIZE: i32 = 32;
 const BLOCK_SIZE: i32 = 2;
@@ -22,14 +22,27 @@ struct CellRange
     x0: i32,
     x1: i32,
     y0: i32,
-    y1: i32
+    y1: i32,
+
+    pass: u32, //progressive rendering
+    accumulation: Vec<PixelAccumulation> //cell local float buffer (merged into the global one once per pass)
 }
 
 impl CellRange
 {
     pub fn new() -> CellRange
     {
-        CellRange {x0: 0, x1: 0, y0: 0, y1: 0}
+        CellRange {x0: 0, x1: 0, y0: 0, y1: 0, pass: 0, accumulation: vec![]}
+    }
+
+    pub fn pixels(&self) -> u64
+    {
+        ((self.x1 - self.x0 + 1) * (self.y1 - self.y0 + 1)) as u64
+    }
+
+    pub fn local_index(&self, x: i32, y: i32) -> usize
+    {
+        ((y - self.y0) * (self.x1 - self.x0 + 1) + (x - self.x0)) as usize
     }
 }
 
@@ -45,12 +58,16 @@ pub struct RendererManager
     running: Arc<Mutex<bool>>,
 
     cell_list: Arc<Mutex<VecDeque<CellRange>>>,
+    cell_signal: Arc<Condvar>, //signals new cells (or finished/stopped rendering) to waiting threads
 
     threads: Vec<JoinHandle<()>>,
     message_sender: Sender<PixelData>,
     message_receiver: Receiver<PixelData>,
 
-    pixels_rendered: Arc<Mutex<u64>>,
+    pixels_rendered: Arc<Mutex<u64>>, //rendered pixels (progressive rendering: one per pass)
+    pixels_finished: Arc<Mutex<u64>>,
+
+    accumulation: Arc<Mutex<Vec<PixelAccumulation>>>, //float buffer for progressive rendering
 
     start_time: Instant,
     done_time: Duration,
@@ -82,12 +99,16 @@ impl RendererManager
             running: std::sync::Arc::new(std::sync::Mutex::new(false)),
 
             cell_list: std::sync::Arc::new(std::sync::Mutex::new(VecDeque::new())),
+            cell_signal: std::sync::Arc::new(Condvar::new()),
 
             threads: vec![],
             message_sender: tx,
             message_receiver: rx,
 
             pixels_rendered: std::sync::Arc::new(std::sync::Mutex::new(0)),
+            pixels_finished: std::sync::Arc::new(std::sync::Mutex::new(0)),
+
+            accumulation: std::sync::Arc::new(std::sync::Mutex::new(vec![])),
 
             start_time: Instant::now(),
             done_time: Duration::new(0, 0),
@@ -109,16 +130,36 @@ impl RendererManager
 
         println!("rendering threads: {}", self.thread_amount);
 
+        //adaptive sampling needs enough samples for the variance estimate
+        {
+            let config = &self.raytracing.read().unwrap().config;
+            if config.adaptive_threshold > 0.0 && config.samples as u32 <= ADAPTIVE_MIN_SAMPLES
+            {
+                println!("adaptive sampling disabled: more than {} samples are needed (samples: {})", ADAPTIVE_MIN_SAMPLES, config.samples);
+            }
+        }
+
         //start time
         self.start_time = Instant::now();
         self.done_time = Duration::new(0, 0);
 
         //init pixel stat
         { *(self.pixels_rendered.lock().unwrap()) = 0; }
+        { *(self.pixels_finished.lock().unwrap()) = 0; }
+
+        //init accumulation buffer
+        {
+            let pixels = (self.width * self.height).max(0) as usize;
+            *(self.accumulation.lock().unwrap()) = vec![PixelAccumulation::new(); pixels];
+        }
 
         //running
         { *(self.running.lock().unwrap()) = true; }
 
+        //integrator and caustic photons (based on the current scene)
+        { self.raytracing.write().unwrap().init_integrator(); }
+        { self.raytracing.write().unwrap().build_photon_map(); }
+
         //init cells
         { (*(self.cell_list.lock().unwrap())).clear(); }
 
@@ -134,7 +175,8 @@ impl RendererManager
                 let mut y1 = y + BLOCK_SIZE -1 ;
                 if y1 >= self.height { y1 = self.height - 1; }
 
-                let cell = CellRange { x0: x, x1: x1, y0: y, y1: y1 };
+                let mut cell = CellRange { x0: x, x1: x1, y0: y, y1: y1, pass: 0, accumulation: vec![] };
+                cell.accumulation = vec![PixelAccumulation::new(); cell.pixels() as usize];
 
                 (*(self.cell_list.lock().unwrap())).push_back(cell);
 
@@ -184,6 +226,12 @@ impl RendererManager
             *(running_ref) = false;
         }
 
+        //wake up threads waiting for cells
+        {
+            let _cell_list = self.cell_list.lock().unwrap();
+            self.cell_signal.notify_all();
+        }
+
         //let bt = backtrace::Backtrace::new();
         //println!("{:?}",bt);
 
@@ -227,7 +275,33 @@ impl RendererManager
 
     pub fn is_done(&self) -> bool
     {
-        (*(self.pixels_rendered.lock().unwrap())) == self.width as u64 * self.height as u64
+        (*(self.pixels_finished.lock().unwrap())) == self.width as u64 * self.height as u64
+    }
+
+    pub fn get_progress(&self) -> f32
+    {
+        let pixels = (self.width as u64 * self.height as u64).max(1) as f32;
+
+        if self.is_done()
+        {
+            return 1.0;
+        }
+
+        let config = self.raytracing.read().unwrap().config;
+
+        if !config.progressive
+        {
+            return self.get_rendered_pixels() as f32 / pixels;
+        }
+
+        // progressive: based on the sample target or the time limit (whatever comes first)
+        let mut progress = self.get_rendered_pixels() as f32 / (pixels * config.samples.max(1) as f32);
+        if config.time_limit > 0.0
+        {
+            progress = progress.max(self.start_time.elapsed().as_secs_f32() / config.time_limit);
+        }
+
+        progress.min(1.0)
     }
 
     pub fn check_and_get_elapsed_time(&mut self) -> u128
@@ -254,10 +328,17 @@ impl RendererManager
     {
         let tx = self.message_sender.clone();
         let cell_list = Arc::clone(&self.cell_list);
+        let cell_signal = Arc::clone(&self.cell_signal);
         let raytracing = Arc::clone(&self.raytracing);
+        let accumulation = Arc::clone(&self.accumulation);
+
+        let width = self.width;
+        let pixels = self.width as u64 * self.height as u64;
+        let start_time = self.start_time;
 
         let running_mutex = Arc::clone(&self.running);
         let pixels_rendered = Arc::clone(&self.pixels_rendered);
+        let pixels_finished = Arc::clone(&self.pixels_finished);
         let check_running = move || -> bool
         {
             let running = running_mutex.lock().unwrap();
@@ -266,6 +347,10 @@ impl RendererManager
 
         let handle = std::thread::spawn(move ||
         {
+            let config = raytracing.read().unwrap().config;
+            let samples = config.samples.max(1) as u32;
+            let adaptive = config.adaptive_threshold > 0.0;
+
             let mut running = true;
             'outer: while running
             {
@@ -278,12 +363,23 @@ impl RendererManager
                 //get new cell from list
                 let mut range = CellRange::new();
                 {
-                    let front = (*(cell_list.lock().unwrap())).pop_front();
+                    let mut cell_list_ref = cell_list.lock().unwrap();
+                    let front = cell_list_ref.pop_front();
 
                     if front.is_some()
                     {
                         range = front.unwrap();
                     }
+                    else if config.progressive && *(pixels_finished.lock().unwrap()) < pixels
+                    {
+                        //other threads are still rendering cells of the current pass (wait for them to queue or finish their cells)
+                        //running is checked with the cell list locked: stop() notifies with the same lock held
+                        if check_running()
+                        {
+                            let _cell_list_ref = cell_signal.wait(cell_list_ref).unwrap();
+                        }
+                        continue 'outer;
+                    }
                     else
                     {
                         running = false;
@@ -291,7 +387,7 @@ impl RendererManager
                 }
 
                 //render
-                if running
+                if running && !config.progressive
                 {
                     for y in range.y0 .. range.y1 + 1
                     {
@@ -306,14 +402,93 @@ impl RendererManager
                             let pixel_val = rt.render(x,y);
 
                             { *(pixels_rendered.lock().unwrap()) += 1; }
+                            { *(pixels_finished.lock().unwrap()) += 1; }
 
                             let _ = tx.send(pixel_val);
                         }
                     }
                 }
+                //progressive: one sample per pixel and pass (the cell gets queued again for the next pass)
+                else if running
+                {
+                    let time_exceeded = config.time_limit > 0.0 && start_time.elapsed().as_secs_f32() >= config.time_limit;
+
+                    let mut next_pass = false;
+                    let mut rendered = 0;
+
+                    if !time_exceeded
+                    {
+                        for y in range.y0 .. range.y1 + 1
+                        {
+                            for x in range.x0 .. range.x1 + 1
+                            {
+                                if !check_running()
+                                {
+                                    break 'outer;
+                                }
+
+                                let index = range.local_index(x, y);
+                                let pixel_accumulation = &mut range.accumulation[index];
+
+                                //noise target reached
+                                if adaptive && pixel_accumulation.is_converged(config.adaptive_threshold)
+                                {
+                                    continue;
+                                }
+
+                                let rt = raytracing.read().unwrap();
+                                rt.render_pass(x, y, range.pass, pixel_accumulation);
+                                rendered += 1;
+
+                                let _ = tx.send(rt.get_pixel_data(pixel_accumulation, x, y));
+
+                                if !(adaptive && pixel_accumulation.is_converged(config.adaptive_threshold))
+                                {
+                                    next_pass = true;
+                                }
+                            }
+                        }
+                    }
+
+                    //merge the cell into the float buffer and the stats (once per pass)
+                    {
+                        let mut accumulation_ref = accumulation.lock().unwrap();
+                        for y in range.y0 .. range.y1 + 1
+                        {
+                            for x in range.x0 .. range.x1 + 1
+                            {
+                                accumulation_ref[(y * width + x) as usize] = range.accumulation[range.local_index(x, y)];
+                            }
+                        }
+                    }
+                    { *(pixels_rendered.lock().unwrap()) += rendered; }
+
+                    //sample target reached
+                    if range.pass + 1 >= samples
+                    {
+                        next_pass = false;
+                    }
+
+                    if next_pass
+                    {
+                        range.pass += 1;
+
+                        let mut cell_list_ref = cell_list.lock().unwrap();
+                        cell_list_ref.push_back(range);
+                        cell_signal.notify_one();
+                    }
+                    else
+                    {
+                        *(pixels_finished.lock().unwrap()) += range.pixels();
+
+                        //waiting threads can end if this was the last cell
+                        let _cell_list_ref = cell_list.lock().unwrap();
+                        cell_signal.notify_all();
+                    }
+                }
             }
         });
 
         handle
     }
-}
\ No newline at end of file
+}
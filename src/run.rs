@@ -1,7 +1,7 @@
 extern crate rand;
 extern crate image;
 
-use chrono::{Datelike, Timelike, Utc, DateTime};
+use chrono::{Datelike, Timelike, Utc, DateTime, TimeZone};
 use egui::{Color32, ScrollArea, RichText, Modifiers, Ui};
 use nalgebra::{Vector3};
 use rfd::FileDialog;
@@ -19,12 +19,16 @@ use image::{ImageBuffer, RgbaImage, Rgba};
 
 use std::fs::File;
 
-use crate::camera::Camera;
-use crate::post_processing::run_post_processing;
+use crate::camera::{Camera, CameraProjection};
+use crate::post_processing::{run_post_processing, denoise, get_sample_count_image};
 use crate::renderer::RendererManager;
-use crate::raytracing::Raytracing;
-use crate::scene::{Scene, LightType};
-use crate::shape::{TextureType};
+use crate::raytracing::{Raytracing, IntegratorType};
+use crate::debug_render::RenderMode;
+use crate::sampler::SamplerType;
+use crate::scene::{Scene, LightType, AreaShape};
+use crate::shape::{TextureType, ShadingModel};
+use crate::medium::DensityType;
+use crate::spectrum::DispersionModel;
 
 const IMAGE_PATH: &str = "data/output";
 const ANIMATION_PATH: &str = "data/output/animation";
@@ -111,13 +115,19 @@ pub struct Run//<'a>
     loading_scene: Arc<Mutex<SceneLoadType>>,
     start_after_init: bool,
 
+    camera_selection: Option<String>,
+    render_all_cameras: bool,
+
     dir_scenes_list: Vec<String>,
     rendering_scenes_list: Vec<String>,
 
     image: RgbaImage,
+    colors: Vec<Vector3<f32>>, // linear (not clamped or gamma encoded) colors for the denoiser
     normals: Vec<Vector3<f32>>, // access data via: y * w + x
     depth: Vec<f32>,
     objects: Vec<u32>,
+    albedo: Vec<Vector3<f32>>,
+    sample_counts: Vec<u32>,
 
     scene: Arc<RwLock<Scene>>,
     pub raytracing: Arc<RwLock<Raytracing>>,
@@ -128,7 +138,9 @@ pub struct Run//<'a>
     stopped: bool,
 
     help_printed: bool,
-    selected_scene_id: usize
+    selected_scene_id: usize,
+
+    settings_changed: bool
 }
 
 impl Run
@@ -159,21 +171,29 @@ impl Run
             rendering_scenes_list: scenes_list,
             start_after_init: start_after_init,
 
+            camera_selection: None,
+            render_all_cameras: false,
+
             scene: scene,
             raytracing: rt,
             rendering: rendering,
 
             image: ImageBuffer::new(0, 0),
+            colors: vec![],
             normals: vec![],
             depth: vec![],
             objects: vec![],
+            albedo: vec![],
+            sample_counts: vec![],
 
             stats: Stats::new(),
 
             stopped: false,
 
             help_printed: false,
-            selected_scene_id: 0
+            selected_scene_id: 0,
+
+            settings_changed: false
         }
     }
 
@@ -183,9 +203,18 @@ impl Run
         let h = self.height as usize;
 
         self.image = ImageBuffer::new(w as u32, h as u32);
+        self.colors = vec![Vector3::<f32>::zeros(); w * h];
         self.normals = vec![Vector3::<f32>::zeros(); w * h];
         self.depth = vec![0.0; w * h];
         self.objects = vec![0; w * h];
+        self.albedo = vec![Vector3::<f32>::zeros(); w * h];
+        self.sample_counts = vec![0; w * h];
+    }
+
+    // camera name, camera index or "all" to render all cameras in a batch
+    pub fn set_camera_selection(&mut self, camera: Option<String>)
+    {
+        self.camera_selection = camera;
     }
 
     pub fn init_stats(&mut self)
@@ -272,6 +301,8 @@ impl Run
 
             { *(self.loading_scene.lock().unwrap()) = SceneLoadType::Complete; }
 
+            self.apply_camera_selection();
+
             let scene_len;
             {
                 scene_len = self.scene.read().unwrap().items.len();
@@ -285,6 +316,37 @@ impl Run
         }
     }
 
+    pub fn apply_camera_selection(&mut self)
+    {
+        if self.camera_selection.is_none()
+        {
+            return;
+        }
+
+        let selection = self.camera_selection.clone().unwrap();
+        let mut scene = self.scene.write().unwrap();
+
+        if selection == "all"
+        {
+            self.render_all_cameras = scene.cameras.len() > 1;
+            scene.set_active_camera(0);
+            return;
+        }
+
+        self.render_all_cameras = false;
+
+        let mut index = scene.get_camera_index(&selection);
+        if index.is_none()
+        {
+            index = selection.parse::<usize>().ok();
+        }
+
+        if index.is_none() || !scene.set_active_camera(index.unwrap())
+        {
+            println!("camera not found: {}", selection);
+        }
+    }
+
     pub fn load_window_pos_and_res(&mut self)
     {
         //only if window is enabled
@@ -401,6 +463,13 @@ impl Run
         //reinit scene with resolution
         {
             let mut scene = self.scene.write().unwrap();
+
+            //start with the first camera again in batch mode
+            if self.render_all_cameras
+            {
+                scene.set_active_camera(0);
+            }
+
             scene.cam.init(self.width as u32, self.height as u32);
         }
 
@@ -449,7 +518,15 @@ impl Run
 
             {
                 self.stats.frame += 1;
-                self.scene.write().unwrap().apply_frame(self.stats.frame);
+                let mut scene = self.scene.write().unwrap();
+
+                if self.render_all_cameras
+                {
+                    scene.set_active_camera(0);
+                }
+
+                scene.apply_frame(self.stats.frame);
+                scene.update(); //bvh bounds of the moved items (and the motion blur)
                 render_next_frame = true
             }
 
@@ -484,6 +561,48 @@ impl Run
         render_next_frame
     }
 
+    pub fn render_next_camera_if_possible(&mut self) -> bool
+    {
+        if !self.render_all_cameras || !self.stats.completed
+        {
+            return false;
+        }
+
+        let next_camera;
+        {
+            let scene = self.scene.read().unwrap();
+            next_camera = scene.active_camera + 1;
+
+            if next_camera >= scene.cameras.len()
+            {
+                return false;
+            }
+        }
+
+        //stop and switch to the next camera
+        self.rendering.stop();
+        thread::sleep(Duration::from_millis(100));
+
+        {
+            let mut scene = self.scene.write().unwrap();
+            scene.set_active_camera(next_camera);
+            scene.cam.init(self.width as u32, self.height as u32);
+            scene.update(); //the motion blur depends on the shutter of the camera
+        }
+
+        //reset stats
+        self.stats.completed = false;
+
+        //print some stats
+        self.print_frame_info();
+
+        //restart
+        self.init_image();
+        self.rendering.restart(self.width, self.height);
+
+        true
+    }
+
     pub fn print_frame_info(&self)
     {
         let mut frames = 1;
@@ -495,9 +614,15 @@ impl Run
             }
         }
         println!("");
-        let print_str;
+        let mut print_str;
         { print_str = format!("frame: {}/{}", self.stats.frame + 1, frames); }
 
+        if self.render_all_cameras
+        {
+            let scene = self.scene.read().unwrap();
+            print_str = format!("{}, camera: {}/{} ({})", print_str, scene.active_camera + 1, scene.cameras.len(), scene.cam.name);
+        }
+
         println!("{}", print_str);
         for _ in 0..print_str.len() { print!("="); }
         println!("");
@@ -529,11 +654,13 @@ impl Run
                     let depth = item.depth;
                     let object = item.object_id;
 
-                    //let color = Rgba([(item.normal.x * 255.0) as u8, (item.normal.y * 255.0) as u8, (item.normal.z * 255.0) as u8, 255]);
                     self.image.put_pixel(x as u32, y as u32, color);
+                    self.colors[y * w + x] = item.color;
                     self.normals[y * w + x] = normal;
                     self.depth[y * w + x] = depth;
                     self.objects[y * w + x] = object;
+                    self.albedo[y * w + x] = item.albedo;
+                    self.sample_counts[y * w + x] = item.samples;
 
                     self.stats.pps_current += 1;
                     change = true;
@@ -544,7 +671,7 @@ impl Run
         change
     }
 
-    pub fn save_image(&mut self, postfix: Option<&str>)
+    pub fn get_image_filename(&self, postfix: Option<&str>) -> String
     {
         let mut out_dir = IMAGE_PATH;
 
@@ -563,6 +690,14 @@ impl Run
 
         //let filename = format!("{}/output_{}-{}-{}_{}-{}-{}_{:0>8}.png", out_dir, self.stats.output_time.year(), self.stats.output_time.month(), self.stats.output_time.day(), self.stats.output_time.hour(), self.stats.output_time.minute(), self.stats.output_time.second(), self.stats.frame);
         let mut filename = format!("{}/output_{}-{}-{}_{}-{}-{}_{:0>8}", out_dir, self.stats.output_time.year(), self.stats.output_time.month(), self.stats.output_time.day(), self.stats.output_time.hour(), self.stats.output_time.minute(), self.stats.output_time.second(), self.stats.frame);
+
+        //add the camera name in batch mode
+        if self.render_all_cameras
+        {
+            let camera_name = self.scene.read().unwrap().cam.name.replace(|c: char| !c.is_alphanumeric(), "_");
+            filename = format!("{}_{}", filename, camera_name);
+        }
+
         if postfix.is_some()
         {
             filename = format!("{}_{}.png", filename, postfix.unwrap());
@@ -572,6 +707,12 @@ impl Run
             filename = format!("{}.png", filename);
         }
 
+        filename
+    }
+
+    pub fn save_image(&mut self, postfix: Option<&str>)
+    {
+        let filename = self.get_image_filename(postfix);
 
         let res = self.image.save(&filename);
 
@@ -585,6 +726,25 @@ impl Run
         }
     }
 
+    pub fn save_sample_count_image(&mut self)
+    {
+        let max_samples = self.raytracing.read().unwrap().config.samples as u32;
+
+        let image = get_sample_count_image(&self.sample_counts, self.image.width(), self.image.height(), max_samples);
+        let filename = self.get_image_filename(Some("samples"));
+
+        let res = image.save(&filename);
+
+        if res.is_err()
+        {
+            println!("error on saving sample count image to {}", &filename);
+        }
+        else
+        {
+            println!("sample count image saved to {}", &filename);
+        }
+    }
+
     pub fn post_processing(&mut self)
     {
         let config;
@@ -594,7 +754,27 @@ impl Run
             cam = self.raytracing.read().unwrap().scene.read().unwrap().cam.clone();
         }
 
-        let processed_image = run_post_processing(config, &self.image, &self.normals, &self.depth, &self.objects, &cam);
+        //denoising is done on the linear colors (before clamping and gamma encoding)
+        let mut image = self.image.clone();
+        if config.denoise
+        {
+            let width = self.image.width();
+            let height = self.image.height();
+
+            let colors = denoise(&self.colors, width, height, &self.normals, &self.depth, &self.objects, &self.albedo, config.denoise_iterations);
+
+            let raytracing = self.raytracing.read().unwrap();
+            for y in 0..height
+            {
+                for x in 0..width
+                {
+                    let (r, g, b) = raytracing.encode_color(colors[(y * width + x) as usize]);
+                    image.put_pixel(x, y, Rgba([r, g, b, 255]));
+                }
+            }
+        }
+
+        let processed_image = run_post_processing(config, &image, &self.normals, &self.depth, &self.objects, &cam);
         self.image = processed_image.clone();
         self.save_image(Some("post"));
     }
@@ -625,7 +805,17 @@ impl Run
                 self.stats.completed = true;
 
                 //save
-                self.save_image(None)
+                self.save_image(None);
+
+                if self.raytracing.read().unwrap().config.sample_count_image
+                {
+                    self.save_sample_count_image();
+                }
+
+                if self.raytracing.read().unwrap().post_processing.denoise
+                {
+                    self.post_processing();
+                }
             }
 
             if !self.stats.completed
@@ -638,7 +828,7 @@ impl Run
         //animation
         if !self.stopped
         {
-            let has_next = self.render_next_frame_if_possible();
+            let has_next = self.render_next_camera_if_possible() || self.render_next_frame_if_possible();
 
             if self.stats.completed && !has_next
             {
@@ -676,6 +866,17 @@ impl Run
 
 // ******************** GUI ********************
 
+// comma separated names (ui text fields: kept as typed, the names are trimmed on use)
+fn split_names(names: &String) -> Vec<String>
+{
+    if names.is_empty()
+    {
+        return vec![];
+    }
+
+    names.split(',').map(|name| name.to_string()).collect()
+}
+
 fn image_to_retained_image(image: RgbaImage) -> RetainedImage
 {
     let pixels = image.as_flat_samples();
@@ -737,19 +938,45 @@ impl Run
             let mut shininess;
             let mut reflectivity;
             let mut refraction_index;
+            let mut dispersion_model;
+            let mut cauchy_coefficients;
+            let mut sellmeier_b;
+            let mut sellmeier_c;
+            let mut attenuation_distance;
+            let mut subsurface;
+            let mut subsurface_radius;
+            let mut subsurface_scale;
             let mut normal_map_strength;
+            let mut emissive;
             let mut cast_shadow;
             let mut receive_shadow;
             let mut shadow_softness;
             let mut roughness;
+            let mut shading_model;
+            let mut metallic;
+            let mut pbr_roughness;
+            let mut clearcoat;
+            let mut clearcoat_roughness;
+            let mut clearcoat_normal_map_strength;
+            let mut sheen_roughness;
+            let mut anisotropy;
+            let mut anisotropy_rotation;
+            let mut iridescence;
+            let mut iridescence_ior;
+            let mut iridescence_thickness_min;
+            let mut iridescence_thickness_max;
             let mut monte_carlo;
             let mut smooth_shading;
             let mut reflection_only;
             let mut backface_cullig;
+            let mut medium;
 
             let mut ambient_color;
             let mut base_color;
             let mut specular_color;
+            let mut transmission_color;
+            let mut subsurface_color;
+            let mut sheen_color;
 
             {
                 let scene = self.scene.read().unwrap();
@@ -761,15 +988,38 @@ impl Run
                 shininess = mat.shininess;
                 reflectivity = mat.reflectivity;
                 refraction_index = mat.refraction_index;
+                dispersion_model = mat.dispersion_model;
+                cauchy_coefficients = mat.cauchy_coefficients;
+                sellmeier_b = mat.sellmeier_b;
+                sellmeier_c = mat.sellmeier_c;
+                attenuation_distance = mat.attenuation_distance;
+                subsurface = mat.subsurface;
+                subsurface_radius = mat.subsurface_radius;
+                subsurface_scale = mat.subsurface_scale;
                 normal_map_strength = mat.normal_map_strength;
+                emissive = mat.emissive;
                 cast_shadow = mat.cast_shadow;
                 receive_shadow = mat.receive_shadow;
                 shadow_softness = mat.shadow_softness;
                 roughness = mat.roughness;
+                shading_model = mat.shading_model;
+                metallic = mat.metallic;
+                pbr_roughness = mat.pbr_roughness;
+                clearcoat = mat.clearcoat;
+                clearcoat_roughness = mat.clearcoat_roughness;
+                clearcoat_normal_map_strength = mat.clearcoat_normal_map_strength;
+                sheen_roughness = mat.sheen_roughness;
+                anisotropy = mat.anisotropy;
+                anisotropy_rotation = mat.anisotropy_rotation;
+                iridescence = mat.iridescence;
+                iridescence_ior = mat.iridescence_ior;
+                iridescence_thickness_min = mat.iridescence_thickness_min;
+                iridescence_thickness_max = mat.iridescence_thickness_max;
                 monte_carlo = mat.monte_carlo;
                 smooth_shading = mat.smooth_shading;
                 reflection_only = mat.reflection_only;
                 backface_cullig = mat.backface_cullig;
+                medium = mat.medium.clone();
 
                 let r = (mat.ambient_color.x * 255.0) as u8;
                 let g = (mat.ambient_color.y * 255.0) as u8;
@@ -785,6 +1035,21 @@ impl Run
                 let g = (mat.specular_color.y * 255.0) as u8;
                 let b = (mat.specular_color.z * 255.0) as u8;
                 specular_color = Color32::from_rgb(r, g, b);
+
+                let r = (mat.transmission_color.x * 255.0) as u8;
+                let g = (mat.transmission_color.y * 255.0) as u8;
+                let b = (mat.transmission_color.z * 255.0) as u8;
+                transmission_color = Color32::from_rgb(r, g, b);
+
+                let r = (mat.subsurface_color.x * 255.0) as u8;
+                let g = (mat.subsurface_color.y * 255.0) as u8;
+                let b = (mat.subsurface_color.z * 255.0) as u8;
+                subsurface_color = Color32::from_rgb(r, g, b);
+
+                let r = (mat.sheen_color.x * 255.0) as u8;
+                let g = (mat.sheen_color.y * 255.0) as u8;
+                let b = (mat.sheen_color.z * 255.0) as u8;
+                sheen_color = Color32::from_rgb(r, g, b);
             }
 
             let mut apply_settings = false;
@@ -794,11 +1059,95 @@ impl Run
             apply_settings = ui.add(egui::Slider::new(&mut shininess, 0.0..=1.0).text("shininess")).changed() || apply_settings;
             apply_settings = ui.add(egui::Slider::new(&mut reflectivity, 0.0..=1.0).text("reflectivity")).changed() || apply_settings;
             apply_settings = ui.add(egui::Slider::new(&mut refraction_index, 1.0..=5.0).text("refraction index")).changed() || apply_settings;
+
+            ui.horizontal(|ui|
+            {
+                ui.label("dispersion:");
+                apply_settings = ui.selectable_value(&mut dispersion_model, DispersionModel::None, "none").changed() || apply_settings;
+                apply_settings = ui.selectable_value(&mut dispersion_model, DispersionModel::Cauchy, "Cauchy").changed() || apply_settings;
+                apply_settings = ui.selectable_value(&mut dispersion_model, DispersionModel::Sellmeier, "Sellmeier").changed() || apply_settings;
+            });
+
+            if dispersion_model == DispersionModel::Cauchy
+            {
+                ui.horizontal(|ui|
+                {
+                    ui.label("A, B, C:");
+                    apply_settings = ui.add(egui::DragValue::new(&mut cauchy_coefficients.x).speed(0.001)).changed() || apply_settings;
+                    apply_settings = ui.add(egui::DragValue::new(&mut cauchy_coefficients.y).speed(0.0001)).changed() || apply_settings;
+                    apply_settings = ui.add(egui::DragValue::new(&mut cauchy_coefficients.z).speed(0.00001)).changed() || apply_settings;
+                });
+            }
+            else if dispersion_model == DispersionModel::Sellmeier
+            {
+                ui.horizontal(|ui|
+                {
+                    ui.label("B1, B2, B3:");
+                    apply_settings = ui.add(egui::DragValue::new(&mut sellmeier_b.x).speed(0.001)).changed() || apply_settings;
+                    apply_settings = ui.add(egui::DragValue::new(&mut sellmeier_b.y).speed(0.001)).changed() || apply_settings;
+                    apply_settings = ui.add(egui::DragValue::new(&mut sellmeier_b.z).speed(0.001)).changed() || apply_settings;
+                });
+
+                ui.horizontal(|ui|
+                {
+                    ui.label("C1, C2, C3:");
+                    apply_settings = ui.add(egui::DragValue::new(&mut sellmeier_c.x).speed(0.0001)).changed() || apply_settings;
+                    apply_settings = ui.add(egui::DragValue::new(&mut sellmeier_c.y).speed(0.0001)).changed() || apply_settings;
+                    apply_settings = ui.add(egui::DragValue::new(&mut sellmeier_c.z).speed(0.1)).changed() || apply_settings;
+                });
+            }
+
+            apply_settings = ui.add(egui::Slider::new(&mut attenuation_distance, 0.0..=100.0).logarithmic(true).text("attenuation distance (0 = off)")).changed() || apply_settings;
+
+            ui.horizontal(|ui|
+            {
+                ui.label("transmission color:");
+                apply_settings = ui.color_edit_button_srgba(&mut transmission_color).changed() || apply_settings;
+            });
+
             apply_settings = ui.add(egui::Slider::new(&mut normal_map_strength, 0.0..=100.0).text("normal map strength")).changed() || apply_settings;
+            apply_settings = ui.checkbox(&mut emissive, "emissive (light source)").changed() || apply_settings;
             apply_settings = ui.checkbox(&mut cast_shadow, "cast shadow").changed() || apply_settings;
             apply_settings = ui.checkbox(&mut receive_shadow, "receive shadow").changed() || apply_settings;
             apply_settings = ui.add(egui::Slider::new(&mut shadow_softness, 0.0..=100.0).text("shadow softness")).changed() || apply_settings;
             apply_settings = ui.add(egui::Slider::new(&mut roughness, 0.0..=PI/2.0).text("roughness")).changed() || apply_settings;
+
+            ui.horizontal(|ui|
+            {
+                ui.label("shading model:");
+                apply_settings = ui.selectable_value(&mut shading_model, ShadingModel::Phong, "Phong (legacy)").changed() || apply_settings;
+                apply_settings = ui.selectable_value(&mut shading_model, ShadingModel::Pbr, "PBR (GGX)").changed() || apply_settings;
+            });
+
+            if shading_model == ShadingModel::Pbr
+            {
+                apply_settings = ui.add(egui::Slider::new(&mut metallic, 0.0..=1.0).text("metallic")).changed() || apply_settings;
+                apply_settings = ui.add(egui::Slider::new(&mut pbr_roughness, 0.0..=1.0).text("pbr roughness")).changed() || apply_settings;
+
+                ui.collapsing("Layers", |ui|
+                {
+                    apply_settings = ui.add(egui::Slider::new(&mut clearcoat, 0.0..=1.0).text("clearcoat")).changed() || apply_settings;
+                    if clearcoat > 0.0
+                    {
+                        apply_settings = ui.add(egui::Slider::new(&mut clearcoat_roughness, 0.0..=1.0).text("clearcoat roughness")).changed() || apply_settings;
+                        apply_settings = ui.add(egui::Slider::new(&mut clearcoat_normal_map_strength, 0.0..=100.0).text("clearcoat normal map strength")).changed() || apply_settings;
+                    }
+
+                    ui.horizontal(|ui|
+                    {
+                        ui.label("sheen color:");
+                        apply_settings = ui.color_edit_button_srgba(&mut sheen_color).changed() || apply_settings;
+                    });
+                    apply_settings = ui.add(egui::Slider::new(&mut sheen_roughness, 0.0..=1.0).text("sheen roughness")).changed() || apply_settings;
+
+                    apply_settings = ui.add(egui::Slider::new(&mut anisotropy, 0.0..=1.0).text("anisotropy")).changed() || apply_settings;
+                    if anisotropy > 0.0
+                    {
+                        apply_settings = ui.add(egui::Slider::new(&mut anisotropy_rotation, 0.0..=PI).text("anisotropy rotation")).changed() || apply_settings;
+                    }
+                });
+            }
+
             apply_settings = ui.checkbox(&mut monte_carlo, "monte carlo").changed() || apply_settings;
             apply_settings = ui.checkbox(&mut smooth_shading, "smooth shading").changed() || apply_settings;
             apply_settings = ui.checkbox(&mut reflection_only, "reflection only").changed() || apply_settings;
@@ -822,9 +1171,114 @@ impl Run
                 apply_settings = ui.color_edit_button_srgba(&mut specular_color).changed() || apply_settings;
             });
 
+            // ********** subsurface scattering
+            ui.collapsing("Subsurface", |ui|
+            {
+                apply_settings = ui.add(egui::Slider::new(&mut subsurface, 0.0..=1.0).text("subsurface (0 = off)")).changed() || apply_settings;
+
+                if subsurface > 0.0
+                {
+                    ui.horizontal(|ui|
+                    {
+                        ui.label("subsurface color:");
+                        apply_settings = ui.color_edit_button_srgba(&mut subsurface_color).changed() || apply_settings;
+                    });
+
+                    ui.horizontal(|ui|
+                    {
+                        ui.label("radius (r, g, b):");
+                        apply_settings = ui.add(egui::DragValue::new(&mut subsurface_radius.x).speed(0.01).clamp_range(0.0..=100.0)).changed() || apply_settings;
+                        apply_settings = ui.add(egui::DragValue::new(&mut subsurface_radius.y).speed(0.01).clamp_range(0.0..=100.0)).changed() || apply_settings;
+                        apply_settings = ui.add(egui::DragValue::new(&mut subsurface_radius.z).speed(0.01).clamp_range(0.0..=100.0)).changed() || apply_settings;
+                    });
+
+                    apply_settings = ui.add(egui::Slider::new(&mut subsurface_scale, 0.0001..=10.0).logarithmic(true).text("subsurface scale")).changed() || apply_settings;
+                }
+            });
+
+            // ********** thin film
+            ui.collapsing("Thin film", |ui|
+            {
+                apply_settings = ui.add(egui::Slider::new(&mut iridescence, 0.0..=1.0).text("iridescence (0 = off)")).changed() || apply_settings;
+
+                if iridescence > 0.0
+                {
+                    apply_settings = ui.add(egui::Slider::new(&mut iridescence_ior, 1.0..=3.0).text("film ior")).changed() || apply_settings;
+                    apply_settings = ui.add(egui::Slider::new(&mut iridescence_thickness_min, 0.0..=2000.0).text("min thickness (nm, texture)")).changed() || apply_settings;
+                    apply_settings = ui.add(egui::Slider::new(&mut iridescence_thickness_max, 0.0..=2000.0).text("thickness (nm)")).changed() || apply_settings;
+                }
+            });
+
+            // ********** interior medium
+            ui.collapsing("Medium", |ui|
+            {
+                apply_settings = ui.checkbox(&mut medium.enabled, "interior medium (volumetric)").changed() || apply_settings;
+
+                if medium.enabled
+                {
+                    let r = (medium.absorption.x * 255.0).min(255.0) as u8;
+                    let g = (medium.absorption.y * 255.0).min(255.0) as u8;
+                    let b = (medium.absorption.z * 255.0).min(255.0) as u8;
+                    let mut absorption = Color32::from_rgb(r, g, b);
+
+                    let r = (medium.scattering.x * 255.0).min(255.0) as u8;
+                    let g = (medium.scattering.y * 255.0).min(255.0) as u8;
+                    let b = (medium.scattering.z * 255.0).min(255.0) as u8;
+                    let mut scattering = Color32::from_rgb(r, g, b);
+
+                    let mut changed_colors = false;
+
+                    ui.horizontal(|ui|
+                    {
+                        ui.label("absorption:");
+                        changed_colors = ui.color_edit_button_srgba(&mut absorption).changed() || changed_colors;
+                    });
+
+                    ui.horizontal(|ui|
+                    {
+                        ui.label("scattering:");
+                        changed_colors = ui.color_edit_button_srgba(&mut scattering).changed() || changed_colors;
+                    });
+
+                    if changed_colors
+                    {
+                        medium.absorption = Vector3::<f32>::new(absorption.r() as f32 / 255.0, absorption.g() as f32 / 255.0, absorption.b() as f32 / 255.0);
+                        medium.scattering = Vector3::<f32>::new(scattering.r() as f32 / 255.0, scattering.g() as f32 / 255.0, scattering.b() as f32 / 255.0);
+                        apply_settings = true;
+                    }
+
+                    apply_settings = ui.add(egui::Slider::new(&mut medium.density, 0.0..=100.0).text("density")).changed() || apply_settings;
+                    apply_settings = ui.add(egui::Slider::new(&mut medium.anisotropy, -0.99..=0.99).text("anisotropy")).changed() || apply_settings;
+
+                    ui.horizontal(|ui|
+                    {
+                        ui.label("density type:");
+                        apply_settings = ui.selectable_value(&mut medium.density_type, DensityType::Homogeneous, "homogeneous").changed() || apply_settings;
+                        apply_settings = ui.selectable_value(&mut medium.density_type, DensityType::Noise, "noise").changed() || apply_settings;
+
+                        if medium.grid.is_some()
+                        {
+                            apply_settings = ui.selectable_value(&mut medium.density_type, DensityType::Grid, "grid").changed() || apply_settings;
+                        }
+                    });
+
+                    if medium.density_type == DensityType::Noise
+                    {
+                        apply_settings = ui.add(egui::Slider::new(&mut medium.noise_scale, 0.01..=100.0).logarithmic(true).text("noise scale")).changed() || apply_settings;
+                        apply_settings = ui.add(egui::Slider::new(&mut medium.noise_octaves, 1..=8).text("noise octaves")).changed() || apply_settings;
+                    }
+
+                    if !medium.is_homogeneous()
+                    {
+                        apply_settings = ui.add(egui::Slider::new(&mut medium.step_size, 0.001..=1.0).logarithmic(true).text("step size")).changed() || apply_settings;
+                    }
+                }
+            });
+
 
             if apply_settings
             {
+                self.settings_changed = true;
                 let mut scene = self.scene.write().unwrap();
                 let mat_arc = scene.get_material_by_id_mut(material_id).unwrap();
                 let mut mat = mat_arc.write().unwrap();
@@ -834,15 +1288,38 @@ impl Run
                 mat.shininess = shininess;
                 mat.reflectivity = reflectivity;
                 mat.refraction_index = refraction_index;
+                mat.dispersion_model = dispersion_model;
+                mat.cauchy_coefficients = cauchy_coefficients;
+                mat.sellmeier_b = sellmeier_b;
+                mat.sellmeier_c = sellmeier_c;
+                mat.attenuation_distance = attenuation_distance;
+                mat.subsurface = subsurface;
+                mat.subsurface_radius = subsurface_radius;
+                mat.subsurface_scale = subsurface_scale;
                 mat.normal_map_strength = normal_map_strength;
+                mat.emissive = emissive;
                 mat.cast_shadow = cast_shadow;
                 mat.receive_shadow = receive_shadow;
                 mat.shadow_softness = shadow_softness;
                 mat.roughness = roughness;
+                mat.shading_model = shading_model;
+                mat.metallic = metallic;
+                mat.pbr_roughness = pbr_roughness;
+                mat.clearcoat = clearcoat;
+                mat.clearcoat_roughness = clearcoat_roughness;
+                mat.clearcoat_normal_map_strength = clearcoat_normal_map_strength;
+                mat.sheen_roughness = sheen_roughness;
+                mat.anisotropy = anisotropy;
+                mat.anisotropy_rotation = anisotropy_rotation;
+                mat.iridescence = iridescence;
+                mat.iridescence_ior = iridescence_ior;
+                mat.iridescence_thickness_min = iridescence_thickness_min;
+                mat.iridescence_thickness_max = iridescence_thickness_max;
                 mat.monte_carlo = monte_carlo;
                 mat.smooth_shading = smooth_shading;
                 mat.reflection_only = reflection_only;
                 mat.backface_cullig = backface_cullig;
+                mat.medium = medium;
 
                 let r = ((ambient_color.r() as f32) / 255.0).clamp(0.0, 1.0);
                 let g = ((ambient_color.g() as f32) / 255.0).clamp(0.0, 1.0);
@@ -858,6 +1335,21 @@ impl Run
                 let g = ((specular_color.g() as f32) / 255.0).clamp(0.0, 1.0);
                 let b = ((specular_color.b() as f32) / 255.0).clamp(0.0, 1.0);
                 mat.specular_color = Vector3::<f32>::new(r, g, b);
+
+                let r = ((transmission_color.r() as f32) / 255.0).clamp(0.0, 1.0);
+                let g = ((transmission_color.g() as f32) / 255.0).clamp(0.0, 1.0);
+                let b = ((transmission_color.b() as f32) / 255.0).clamp(0.0, 1.0);
+                mat.transmission_color = Vector3::<f32>::new(r, g, b);
+
+                let r = ((subsurface_color.r() as f32) / 255.0).clamp(0.0, 1.0);
+                let g = ((subsurface_color.g() as f32) / 255.0).clamp(0.0, 1.0);
+                let b = ((subsurface_color.b() as f32) / 255.0).clamp(0.0, 1.0);
+                mat.subsurface_color = Vector3::<f32>::new(r, g, b);
+
+                let r = ((sheen_color.r() as f32) / 255.0).clamp(0.0, 1.0);
+                let g = ((sheen_color.g() as f32) / 255.0).clamp(0.0, 1.0);
+                let b = ((sheen_color.b() as f32) / 255.0).clamp(0.0, 1.0);
+                mat.sheen_color = Vector3::<f32>::new(r, g, b);
             }
 
 
@@ -873,6 +1365,9 @@ impl Run
                 let mut roughness_texture_label: String = "unset".to_string();
                 let mut ao_texture_label: String = "unset".to_string();
                 let mut reflectivity_texture_label: String = "unset".to_string();
+                let mut clearcoat_normal_texture_label: String = "unset".to_string();
+                let mut iridescence_thickness_texture_label: String = "unset".to_string();
+                let mut anisotropy_texture_label: String = "unset".to_string();
 
                 let has_ambient;
                 let has_base;
@@ -882,6 +1377,9 @@ impl Run
                 let has_roughness;
                 let has_ao;
                 let has_reflectivity;
+                let has_clearcoat_normal;
+                let has_iridescence_thickness;
+                let has_anisotropy;
 
                 {
                     let scene = self.scene.read().unwrap();
@@ -896,6 +1394,9 @@ impl Run
                     has_roughness = material.texture_roughness.width() > 0;
                     has_ao = material.texture_ambient_occlusion.width() > 0;
                     has_reflectivity = material.texture_reflectivity.width() > 0;
+                    has_clearcoat_normal = material.texture_clearcoat_normal.width() > 0;
+                    has_iridescence_thickness = material.texture_iridescence_thickness.width() > 0;
+                    has_anisotropy = material.texture_anisotropy.width() > 0;
 
                     if has_ambient { ambient_texture_label = format!("{}x{}", material.texture_ambient.width(), material.texture_ambient.height()); }
                     if has_base { base_texture_label = format!("{}x{}", material.texture_base.width(), material.texture_base.height()); }
@@ -905,6 +1406,9 @@ impl Run
                     if has_roughness { roughness_texture_label = format!("{}x{}", material.texture_roughness.width(), material.texture_roughness.height()); }
                     if has_ao { ao_texture_label = format!("{}x{}", material.texture_ambient_occlusion.width(), material.texture_ambient_occlusion.height()); }
                     if has_reflectivity { reflectivity_texture_label = format!("{}x{}", material.texture_reflectivity.width(), material.texture_reflectivity.height()); }
+                    if has_clearcoat_normal { clearcoat_normal_texture_label = format!("{}x{}", material.texture_clearcoat_normal.width(), material.texture_clearcoat_normal.height()); }
+                    if has_iridescence_thickness { iridescence_thickness_texture_label = format!("{}x{}", material.texture_iridescence_thickness.width(), material.texture_iridescence_thickness.height()); }
+                    if has_anisotropy { anisotropy_texture_label = format!("{}x{}", material.texture_anisotropy.width(), material.texture_anisotropy.height()); }
                 }
 
 
@@ -917,6 +1421,9 @@ impl Run
                 tex_items.push(("roughness texture", has_roughness, roughness_texture_label, TextureType::Roughness));
                 tex_items.push(("ambient occlusion texture", has_ao, ao_texture_label, TextureType::AmbientOcclusion));
                 tex_items.push(("reflectivity texture", has_reflectivity, reflectivity_texture_label, TextureType::Reflectivity));
+                tex_items.push(("clearcoat normal texture", has_clearcoat_normal, clearcoat_normal_texture_label, TextureType::ClearcoatNormal));
+                tex_items.push(("iridescence thickness texture", has_iridescence_thickness, iridescence_thickness_texture_label, TextureType::IridescenceThickness));
+                tex_items.push(("anisotropy texture", has_anisotropy, anisotropy_texture_label, TextureType::Anisotropy));
 
                 for tex in tex_items
                 {
@@ -937,6 +1444,7 @@ impl Run
                         {
                             if let Some(path) = FileDialog::new().add_filter("Image", &["jpg", "png"]).set_directory("/").pick_file()
                             {
+                                self.settings_changed = true;
                                 let mut scene = self.scene.write().unwrap();
                                 let mat_arc = scene.get_material_by_id_mut(material_id).unwrap();
                                 let mut material = mat_arc.write().unwrap();
@@ -947,6 +1455,7 @@ impl Run
 
                         if tex.1 && ui.button("🗑").clicked()
                         {
+                            self.settings_changed = true;
                             let mut scene = self.scene.write().unwrap();
                             let mat_arc = scene.get_material_by_id_mut(material_id).unwrap();
                             let mut material = mat_arc.write().unwrap();
@@ -996,51 +1505,111 @@ impl Run
             let is_loading_scene;
             { is_loading_scene = *(self.loading_scene.lock().unwrap()) != SceneLoadType::Complete; }
 
-            let settings_updates_allowed = !(running && !is_done) && !is_loading_scene;
+            let progressive_rendering = self.raytracing.read().unwrap().config.progressive;
+
+            //progressive rendering: the settings can be changed while rendering (the accumulation restarts)
+            let settings_updates_allowed = (progressive_rendering || !(running && !is_done)) && !is_loading_scene;
+
+            let integrator;
+            let mut integrator_new;
+
+            let render_mode;
+            let mut render_mode_new;
 
             let samples;
             let mut samples_new;
 
+            let sampler;
+            let mut sampler_new;
+
+            let adaptive_threshold;
+            let mut adaptive_threshold_new;
+
+            let sample_count_image;
+            let mut sample_count_image_new;
+
+            let progressive;
+            let mut progressive_new;
+
+            let time_limit;
+            let mut time_limit_new;
+
             let monte_carlo;
             let mut monte_carlo_new;
 
+            let spectral;
+            let mut spectral_new;
+
             let threads;
             let mut threads_new;
 
-            let focal_length;
-            let mut focal_length_new;
-
-            let aperture_size;
-            let mut aperture_size_new;
-
             let fog_density;
             let mut fog_density_new;
 
             let fog_color;
             let mut fog_color_new;
 
+            let fog_anisotropy;
+            let mut fog_anisotropy_new;
+
+            let photon_count;
+            let mut photon_count_new;
+
+            let photon_radius;
+            let mut photon_radius_new;
+
             let max_recursion;
             let mut max_recursion_new;
 
+            let max_diffuse_bounces;
+            let mut max_diffuse_bounces_new;
+
+            let max_glossy_bounces;
+            let mut max_glossy_bounces_new;
+
+            let max_transmission_bounces;
+            let mut max_transmission_bounces_new;
+
+            let indirect_clamp;
+            let mut indirect_clamp_new;
+
             let gamma_correction;
             let mut gamma_correction_new;
 
             {
                 let rt = self.raytracing.read().unwrap();
+                integrator = rt.config.integrator;
+                integrator_new = rt.config.integrator;
+
+                render_mode = rt.config.render_mode;
+                render_mode_new = rt.config.render_mode;
+
                 monte_carlo = rt.config.monte_carlo;
                 monte_carlo_new = rt.config.monte_carlo;
 
+                spectral = rt.config.spectral;
+                spectral_new = rt.config.spectral;
+
                 samples = rt.config.samples;
                 samples_new = rt.config.samples;
 
-                threads = self.rendering.thread_amount;
-                threads_new = self.rendering.thread_amount;
+                sampler = rt.config.sampler;
+                sampler_new = rt.config.sampler;
+
+                adaptive_threshold = rt.config.adaptive_threshold;
+                adaptive_threshold_new = rt.config.adaptive_threshold;
+
+                sample_count_image = rt.config.sample_count_image;
+                sample_count_image_new = rt.config.sample_count_image;
 
-                focal_length = rt.config.focal_length;
-                focal_length_new = rt.config.focal_length;
+                progressive = rt.config.progressive;
+                progressive_new = rt.config.progressive;
 
-                aperture_size = rt.config.aperture_size;
-                aperture_size_new = rt.config.aperture_size;
+                time_limit = rt.config.time_limit;
+                time_limit_new = rt.config.time_limit;
+
+                threads = self.rendering.thread_amount;
+                threads_new = self.rendering.thread_amount;
 
                 fog_density = rt.config.fog_density;
                 fog_density_new = rt.config.fog_density;
@@ -1051,9 +1620,30 @@ impl Run
                 fog_color = Color32::from_rgb(r, g, b);
                 fog_color_new = fog_color;
 
+                fog_anisotropy = rt.config.fog_anisotropy;
+                fog_anisotropy_new = rt.config.fog_anisotropy;
+
+                photon_count = rt.config.photon_count;
+                photon_count_new = rt.config.photon_count;
+
+                photon_radius = rt.config.photon_radius;
+                photon_radius_new = rt.config.photon_radius;
+
                 max_recursion = rt.config.max_recursion;
                 max_recursion_new = rt.config.max_recursion;
 
+                max_diffuse_bounces = rt.config.max_diffuse_bounces;
+                max_diffuse_bounces_new = rt.config.max_diffuse_bounces;
+
+                max_glossy_bounces = rt.config.max_glossy_bounces;
+                max_glossy_bounces_new = rt.config.max_glossy_bounces;
+
+                max_transmission_bounces = rt.config.max_transmission_bounces;
+                max_transmission_bounces_new = rt.config.max_transmission_bounces;
+
+                indirect_clamp = rt.config.indirect_clamp;
+                indirect_clamp_new = rt.config.indirect_clamp;
+
                 gamma_correction = rt.config.gamma_correction;
                 gamma_correction_new = rt.config.gamma_correction;
             }
@@ -1104,11 +1694,13 @@ impl Run
                 if ui.button("add ground plane").clicked()
                 {
                     self.scene.write().unwrap().add_ground_plane();
+                    self.settings_changed = true;
                 }
 
                 if ui.button("add environment sphere").clicked()
                 {
                     self.scene.write().unwrap().add_environment_sphere();
+                    self.settings_changed = true;
                 }
 
                 ui.separator();
@@ -1118,9 +1710,54 @@ impl Run
                     // ********** rendering settings **********
                     ui.heading("Rendering Settings");
 
+                    ui.horizontal(|ui|
+                    {
+                        ui.label("integrator:");
+                        ui.selectable_value(&mut integrator_new, IntegratorType::Whitted, "Whitted (preview)");
+                        ui.selectable_value(&mut integrator_new, IntegratorType::PathTracing, "Path Tracing");
+                        ui.selectable_value(&mut integrator_new, IntegratorType::Bidirectional, "Bidirectional");
+                    });
+
+                    ui.horizontal(|ui|
+                    {
+                        ui.label("render mode:");
+                        egui::ComboBox::from_id_source("render_mode").selected_text(format!("{:?}", render_mode_new)).show_ui(ui, |ui|
+                        {
+                            ui.selectable_value(&mut render_mode_new, RenderMode::Shaded, "Shaded");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::ShadingNormals, "Shading normals");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::GeometricNormals, "Geometric normals");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::Uvs, "UVs");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::Barycentrics, "Barycentrics");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::Depth, "Depth");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::MaterialId, "Material ID");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::ObjectId, "Object ID");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::Albedo, "Albedo");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::BvhHeatMap, "BVH heat map");
+                            ui.selectable_value(&mut render_mode_new, RenderMode::IntersectionHeatMap, "Intersection heat map");
+                        });
+                    });
+
                     ui.add(egui::Slider::new(&mut samples_new, 1..=1024).text("samples"));
+
+                    ui.horizontal(|ui|
+                    {
+                        ui.label("sampler:");
+                        ui.selectable_value(&mut sampler_new, SamplerType::Independent, "Independent");
+                        ui.selectable_value(&mut sampler_new, SamplerType::Stratified, "Stratified");
+                        ui.selectable_value(&mut sampler_new, SamplerType::Halton, "Halton");
+                        ui.selectable_value(&mut sampler_new, SamplerType::Sobol, "Sobol");
+                        ui.selectable_value(&mut sampler_new, SamplerType::BlueNoise, "Blue Noise");
+                    });
+
+                    ui.add(egui::Slider::new(&mut adaptive_threshold_new, 0.0..=0.1).logarithmic(true).text("adaptive sampling threshold (0 = off)"));
+                    ui.checkbox(&mut sample_count_image_new, "save sample count image");
+
+                    ui.checkbox(&mut progressive_new, "Progressive (1 sample per pass, restarts on changes)");
+                    ui.add_enabled(progressive_new, egui::Slider::new(&mut time_limit_new, 0.0..=3600.0).logarithmic(true).suffix(" s").text("time limit (0 = off)"));
+
                     ui.checkbox(&mut self.animate, "Animation");
                     ui.checkbox(&mut monte_carlo_new, "Monte Carlo");
+                    ui.checkbox(&mut spectral_new, "Spectral (dispersion)");
 
                     let max_threads = num_cpus::get() as u32;
                     ui.add(egui::Slider::new(&mut threads_new, 1..=max_threads).text("CPU threads"));
@@ -1129,29 +1766,41 @@ impl Run
                     // ********** scene settings **********
                     ui.heading("Scene Settings");
 
-                    ui.add(egui::Slider::new(&mut focal_length_new, 1.0..=128.0).suffix(" unit").text("focal length"));
-                    ui.add(egui::Slider::new(&mut aperture_size_new, 1.0..=128.0).suffix(" px").text("aperture size"));
-
-                    ui.add(egui::Slider::new(&mut fog_density_new, 0.0..=1.0).text("fog density (distance based amount)"));
+                    ui.add(egui::Slider::new(&mut fog_density_new, 0.0..=1.0).text("fog density (extinction per unit)"));
 
                     ui.horizontal(|ui|
                     {
-                        ui.label("fog color:");
+                        ui.label("fog color (scattering albedo):");
                         ui.color_edit_button_srgba(&mut fog_color_new);
                     });
 
+                    ui.add(egui::Slider::new(&mut fog_anisotropy_new, -0.99..=0.99).text("fog anisotropy"));
+
+                    ui.add(egui::Slider::new(&mut photon_count_new, 0..=10000000).logarithmic(true).text("caustic photons (0 = off)"));
+                    ui.add(egui::Slider::new(&mut photon_radius_new, 0.001..=1.0).logarithmic(true).suffix(" unit").text("photon gather radius"));
+
                     ui.add(egui::Slider::new(&mut max_recursion_new, 1..=64).text("max recursion"));
+                    ui.add(egui::Slider::new(&mut max_diffuse_bounces_new, 0..=64).text("max diffuse bounces"));
+                    ui.add(egui::Slider::new(&mut max_glossy_bounces_new, 0..=64).text("max glossy bounces"));
+                    ui.add(egui::Slider::new(&mut max_transmission_bounces_new, 0..=64).text("max transmission bounces"));
+                    ui.add(egui::Slider::new(&mut indirect_clamp_new, 0.0..=100.0).logarithmic(true).text("indirect clamp (0 = off)"));
                     ui.checkbox(&mut gamma_correction_new, "gamma correction");
 
                     ui.separator();
 
                     {
+                        let config = self.raytracing.read().unwrap().config;
+
+                        if integrator != integrator_new { self.raytracing.write().unwrap().config.integrator = integrator_new; }
+                        if render_mode != render_mode_new { self.raytracing.write().unwrap().config.render_mode = render_mode_new; }
                         if samples != samples_new { self.raytracing.write().unwrap().config.samples = samples_new; }
+                        if sampler != sampler_new { self.raytracing.write().unwrap().config.sampler = sampler_new; }
+                        if adaptive_threshold != adaptive_threshold_new { self.raytracing.write().unwrap().config.adaptive_threshold = adaptive_threshold_new; }
+                        if sample_count_image != sample_count_image_new { self.raytracing.write().unwrap().config.sample_count_image = sample_count_image_new; }
                         if monte_carlo != monte_carlo_new { self.raytracing.write().unwrap().config.monte_carlo = monte_carlo_new; }
+                        if spectral != spectral_new { self.raytracing.write().unwrap().config.spectral = spectral_new; }
                         if threads != threads_new { self.rendering.thread_amount = threads_new; }
 
-                        if focal_length != focal_length_new { self.raytracing.write().unwrap().config.focal_length = focal_length_new; }
-                        if aperture_size != aperture_size_new { self.raytracing.write().unwrap().config.aperture_size = aperture_size_new; }
                         if fog_density != fog_density_new { self.raytracing.write().unwrap().config.fog_density = fog_density_new; }
                         if fog_color != fog_color_new
                         {
@@ -1160,19 +1809,201 @@ impl Run
                             let b = ((fog_color_new.b() as f32) / 255.0).clamp(0.0, 1.0);
                             self.raytracing.write().unwrap().config.fog_color = Vector3::<f32>::new(r, g, b);
                         }
+                        if fog_anisotropy != fog_anisotropy_new { self.raytracing.write().unwrap().config.fog_anisotropy = fog_anisotropy_new; }
+                        if photon_count != photon_count_new { self.raytracing.write().unwrap().config.photon_count = photon_count_new; }
+                        if photon_radius != photon_radius_new { self.raytracing.write().unwrap().config.photon_radius = photon_radius_new; }
                         if max_recursion != max_recursion_new { self.raytracing.write().unwrap().config.max_recursion = max_recursion_new; }
+                        if max_diffuse_bounces != max_diffuse_bounces_new { self.raytracing.write().unwrap().config.max_diffuse_bounces = max_diffuse_bounces_new; }
+                        if max_glossy_bounces != max_glossy_bounces_new { self.raytracing.write().unwrap().config.max_glossy_bounces = max_glossy_bounces_new; }
+                        if max_transmission_bounces != max_transmission_bounces_new { self.raytracing.write().unwrap().config.max_transmission_bounces = max_transmission_bounces_new; }
+                        if indirect_clamp != indirect_clamp_new { self.raytracing.write().unwrap().config.indirect_clamp = indirect_clamp_new; }
                         if gamma_correction != gamma_correction_new { self.raytracing.write().unwrap().config.gamma_correction = gamma_correction_new; }
+                        if progressive != progressive_new { self.raytracing.write().unwrap().config.progressive = progressive_new; }
+                        if time_limit != time_limit_new { self.raytracing.write().unwrap().config.time_limit = time_limit_new; }
+
+                        if config != self.raytracing.read().unwrap().config || threads != threads_new
+                        {
+                            self.settings_changed = true;
+                        }
+                    }
+
+                    // ********** Environment **********
+                    ui.heading("Environment");
+
+                    let mut environment_enabled;
+                    let mut environment_intensity;
+                    let mut environment_rotation;
+                    let environment_path;
+                    {
+                        let scene = self.scene.read().unwrap();
+                        environment_enabled = scene.environment.enabled;
+                        environment_intensity = scene.environment.intensity;
+                        environment_rotation = scene.environment.rotation.to_degrees();
+                        environment_path = scene.environment.path.clone();
+                    }
+
+                    {
+                        let mut apply_settings = false;
+
+                        ui.horizontal(|ui|
+                        {
+                            if environment_path.is_empty()
+                            {
+                                ui.label("environment map: unset");
+                            }
+                            else
+                            {
+                                ui.label(format!("environment map: {}", environment_path));
+                            }
+
+                            if ui.button("+").clicked()
+                            {
+                                if let Some(path) = FileDialog::new().add_filter("HDR Image", &["hdr", "exr"]).set_directory("/").pick_file()
+                                {
+                                    self.scene.write().unwrap().environment.load(&path.display().to_string());
+                                    self.settings_changed = true;
+                                }
+                            }
+
+                            if !environment_path.is_empty() && ui.button("🗑").clicked()
+                            {
+                                self.scene.write().unwrap().environment.unload();
+                                self.settings_changed = true;
+                            }
+                        });
+
+                        apply_settings = ui.checkbox(&mut environment_enabled, "enabled").changed() || apply_settings;
+                        apply_settings = ui.add(egui::Slider::new(&mut environment_intensity, 0.0..=100.0).text("intensity")).changed() || apply_settings;
+                        apply_settings = ui.add(egui::Slider::new(&mut environment_rotation, 0.0..=360.0).suffix("°").text("rotation")).changed() || apply_settings;
+
+                        if apply_settings
+                        {
+                            self.settings_changed = true;
+                            let mut scene = self.scene.write().unwrap();
+                            scene.environment.enabled = environment_enabled;
+                            scene.environment.intensity = environment_intensity;
+                            scene.environment.rotation = environment_rotation.to_radians();
+                        }
                     }
 
+                    ui.separator();
+
+                    // ********** Sky **********
+                    ui.heading("Sky");
+
+                    let mut sky_enabled;
+                    let mut sky_turbidity;
+                    let mut sky_ground_albedo;
+                    let mut sky_intensity;
+                    let mut sky_sun_intensity;
+                    let mut sky_sun_elevation;
+                    let mut sky_sun_azimuth;
+                    let mut sky_use_location;
+                    let mut sky_latitude;
+                    let mut sky_longitude;
+                    let mut sky_year;
+                    let mut sky_month;
+                    let mut sky_day;
+                    let mut sky_hour;
+                    let mut sky_minute;
+                    {
+                        let scene = self.scene.read().unwrap();
+                        sky_enabled = scene.sky.enabled;
+                        sky_turbidity = scene.sky.turbidity;
+                        sky_ground_albedo = scene.sky.ground_albedo;
+                        sky_intensity = scene.sky.intensity;
+                        sky_sun_intensity = scene.sky.sun_intensity;
+                        sky_sun_elevation = scene.sky.sun_elevation;
+                        sky_sun_azimuth = scene.sky.sun_azimuth;
+                        sky_use_location = scene.sky.use_location;
+                        sky_latitude = scene.sky.latitude;
+                        sky_longitude = scene.sky.longitude;
+                        sky_year = scene.sky.date_time.year();
+                        sky_month = scene.sky.date_time.month();
+                        sky_day = scene.sky.date_time.day();
+                        sky_hour = scene.sky.date_time.hour();
+                        sky_minute = scene.sky.date_time.minute();
+                    }
+
+                    {
+                        let mut apply_settings = false;
+                        let mut set_now = false;
+
+                        apply_settings = ui.checkbox(&mut sky_enabled, "enabled").changed() || apply_settings;
+                        apply_settings = ui.add(egui::Slider::new(&mut sky_turbidity, 1.7..=10.0).text("turbidity")).changed() || apply_settings;
+                        apply_settings = ui.add(egui::Slider::new(&mut sky_ground_albedo, 0.0..=1.0).text("ground albedo")).changed() || apply_settings;
+                        apply_settings = ui.add(egui::Slider::new(&mut sky_intensity, 0.0..=10.0).text("sky intensity")).changed() || apply_settings;
+                        apply_settings = ui.add(egui::Slider::new(&mut sky_sun_intensity, 0.0..=100.0).text("sun intensity")).changed() || apply_settings;
+                        apply_settings = ui.checkbox(&mut sky_use_location, "sun position by location and time").changed() || apply_settings;
+
+                        if sky_use_location
+                        {
+                            apply_settings = ui.add(egui::Slider::new(&mut sky_latitude, -90.0..=90.0).suffix("°").text("latitude")).changed() || apply_settings;
+                            apply_settings = ui.add(egui::Slider::new(&mut sky_longitude, -180.0..=180.0).suffix("°").text("longitude")).changed() || apply_settings;
+
+                            ui.horizontal(|ui|
+                            {
+                                ui.label("date (UTC):");
+                                apply_settings = ui.add(egui::DragValue::new(&mut sky_year).clamp_range(1900..=2100)).changed() || apply_settings;
+                                apply_settings = ui.add(egui::DragValue::new(&mut sky_month).clamp_range(1..=12)).changed() || apply_settings;
+                                apply_settings = ui.add(egui::DragValue::new(&mut sky_day).clamp_range(1..=31)).changed() || apply_settings;
+                                ui.label("time:");
+                                apply_settings = ui.add(egui::DragValue::new(&mut sky_hour).clamp_range(0..=23)).changed() || apply_settings;
+                                apply_settings = ui.add(egui::DragValue::new(&mut sky_minute).clamp_range(0..=59)).changed() || apply_settings;
+
+                                set_now = ui.button("now").clicked();
+                                apply_settings = set_now || apply_settings;
+                            });
+                        }
+                        else
+                        {
+                            apply_settings = ui.add(egui::Slider::new(&mut sky_sun_elevation, -10.0..=90.0).suffix("°").text("sun elevation")).changed() || apply_settings;
+                            apply_settings = ui.add(egui::Slider::new(&mut sky_sun_azimuth, 0.0..=360.0).suffix("°").text("sun azimuth")).changed() || apply_settings;
+                        }
+
+                        if apply_settings
+                        {
+                            self.settings_changed = true;
+                            let mut scene = self.scene.write().unwrap();
+                            scene.sky.enabled = sky_enabled;
+                            scene.sky.turbidity = sky_turbidity;
+                            scene.sky.ground_albedo = sky_ground_albedo;
+                            scene.sky.intensity = sky_intensity;
+                            scene.sky.sun_intensity = sky_sun_intensity;
+                            scene.sky.sun_elevation = sky_sun_elevation;
+                            scene.sky.sun_azimuth = sky_sun_azimuth;
+                            scene.sky.use_location = sky_use_location;
+                            scene.sky.latitude = sky_latitude;
+                            scene.sky.longitude = sky_longitude;
+
+                            if set_now
+                            {
+                                scene.sky.date_time = Utc::now();
+                            }
+                            else if let Some(date_time) = Utc.with_ymd_and_hms(sky_year, sky_month, sky_day, sky_hour, sky_minute, 0).single()
+                            {
+                                scene.sky.date_time = date_time;
+                            }
+
+                            scene.apply_sky();
+                        }
+                    }
+
+                    ui.separator();
+
                     // ********** Post Processing **********
                     ui.heading("Post Processing");
 
                     let mut cavaty;
                     let mut outline;
+                    let mut denoise;
+                    let mut denoise_iterations;
                     {
                         let rt = self.raytracing.read().unwrap();
                         cavaty = rt.post_processing.cavity;
                         outline = rt.post_processing.outline;
+                        denoise = rt.post_processing.denoise;
+                        denoise_iterations = rt.post_processing.denoise_iterations;
                     }
 
                     {
@@ -1180,12 +2011,16 @@ impl Run
 
                         apply_settings = ui.checkbox(&mut cavaty, "Cavity").changed() || apply_settings;
                         apply_settings = ui.checkbox(&mut outline, "Outline").changed() || apply_settings;
+                        apply_settings = ui.checkbox(&mut denoise, "Denoise").changed() || apply_settings;
+                        apply_settings = ui.add_enabled(denoise, egui::Slider::new(&mut denoise_iterations, 1..=8).text("denoise iterations")).changed() || apply_settings;
 
                         if apply_settings
                         {
                             let mut rt = self.raytracing.write().unwrap();
                             rt.post_processing.cavity = cavaty;
                             rt.post_processing.outline = outline;
+                            rt.post_processing.denoise = denoise;
+                            rt.post_processing.denoise_iterations = denoise_iterations;
                         }
                     }
 
@@ -1206,6 +2041,39 @@ impl Run
                         ui.heading("Camera");
                         ui.vertical(|ui|
                         {
+                            // ********** camera selector **********
+                            let camera_names: Vec<String>;
+                            let mut active_camera;
+                            {
+                                let scene = self.scene.read().unwrap();
+                                camera_names = scene.cameras.iter().map(|cam| cam.name.clone()).collect();
+                                active_camera = scene.active_camera;
+                            }
+
+                            if camera_names.len() > 1
+                            {
+                                let active_camera_before = active_camera;
+
+                                ui.horizontal(|ui|
+                                {
+                                    ui.label("active camera:");
+                                    egui::ComboBox::from_id_source("camera_selector").width(200.0).show_index
+                                    (
+                                        ui,
+                                        &mut active_camera,
+                                        camera_names.len(),
+                                        |i| camera_names[i].to_owned()
+                                    );
+                                });
+
+                                if active_camera != active_camera_before
+                                {
+                                    self.settings_changed = true;
+                                    self.render_all_cameras = false;
+                                    self.scene.write().unwrap().set_active_camera(active_camera);
+                                }
+                            }
+
                             ui.collapsing("Camera settings", |ui|
                             {
                                 ui.horizontal_wrapped(|ui|
@@ -1219,6 +2087,25 @@ impl Run
                                     let mut clipping_near;
                                     let mut clipping_far;
 
+                                    let mut projection_type;
+                                    let mut ortho_scale;
+                                    let mut fisheye_fov;
+                                    let mut stereo_eye_distance;
+
+                                    let mut sensor_width;
+                                    let mut sensor_height;
+                                    let focal_length;
+                                    let mut focal_length_new;
+                                    let mut dof;
+                                    let mut f_number;
+                                    let mut focus_distance;
+                                    let mut shutter_speed;
+                                    let mut iso;
+                                    let mut exposure;
+
+                                    let mut shutter_open;
+                                    let mut shutter_close;
+
                                     {
                                         let scene = self.scene.read().unwrap();
                                         let cam = &scene.cam;
@@ -1231,12 +2118,55 @@ impl Run
 
                                         clipping_near = cam.clipping_near;
                                         clipping_far = cam.clipping_far;
+
+                                        projection_type = cam.projection_type;
+                                        ortho_scale = cam.ortho_scale;
+                                        fisheye_fov = cam.fisheye_fov.to_degrees();
+                                        stereo_eye_distance = cam.stereo_eye_distance;
+
+                                        sensor_width = cam.sensor_width;
+                                        sensor_height = cam.sensor_height;
+                                        focal_length = cam.get_focal_length();
+                                        focal_length_new = focal_length;
+                                        dof = cam.dof;
+                                        f_number = cam.f_number;
+                                        focus_distance = cam.focus_distance;
+                                        shutter_speed = cam.shutter_speed;
+                                        iso = cam.iso;
+                                        exposure = cam.exposure;
+
+                                        shutter_open = cam.shutter_open;
+                                        shutter_close = cam.shutter_close;
                                     }
 
                                     let mut apply_settings = false;
 
                                     ui.vertical(|ui|
                                     {
+                                        ui.horizontal(|ui|
+                                        {
+                                            ui.label("projection:");
+                                            apply_settings = ui.selectable_value(&mut projection_type, CameraProjection::Perspective, "perspective").changed() || apply_settings;
+                                            apply_settings = ui.selectable_value(&mut projection_type, CameraProjection::Orthographic, "orthographic").changed() || apply_settings;
+                                            apply_settings = ui.selectable_value(&mut projection_type, CameraProjection::FisheyeEquidistant, "fisheye (equidistant)").changed() || apply_settings;
+                                            apply_settings = ui.selectable_value(&mut projection_type, CameraProjection::FisheyeEquisolid, "fisheye (equisolid)").changed() || apply_settings;
+                                            apply_settings = ui.selectable_value(&mut projection_type, CameraProjection::Equirectangular, "equirectangular").changed() || apply_settings;
+                                            apply_settings = ui.selectable_value(&mut projection_type, CameraProjection::Stereo, "stereo").changed() || apply_settings;
+                                        });
+
+                                        if projection_type == CameraProjection::Orthographic
+                                        {
+                                            apply_settings = ui.add(egui::Slider::new(&mut ortho_scale, 0.01..=1000.0).logarithmic(true).text("orthographic scale (half height)")).changed() || apply_settings;
+                                        }
+                                        else if projection_type == CameraProjection::FisheyeEquidistant || projection_type == CameraProjection::FisheyeEquisolid
+                                        {
+                                            apply_settings = ui.add(egui::Slider::new(&mut fisheye_fov, 1.0..=360.0).suffix(" °").text("fisheye field of view")).changed() || apply_settings;
+                                        }
+                                        else if projection_type == CameraProjection::Stereo
+                                        {
+                                            apply_settings = ui.add(egui::Slider::new(&mut stereo_eye_distance, 0.0..=1.0).text("eye distance")).changed() || apply_settings;
+                                        }
+
                                         apply_settings = ui.add(egui::Slider::new(&mut fov, 0.001..=360.0).suffix(" °").text("field of view (fov)")).changed() || apply_settings;
 
                                         ui.horizontal(|ui|
@@ -1265,10 +2195,35 @@ impl Run
 
                                         apply_settings = ui.add(egui::Slider::new(&mut clipping_near, 0.0..=10.0).text("near clipping plane")).changed() || apply_settings;
                                         apply_settings = ui.add(egui::Slider::new(&mut clipping_far, 1.0..=100000.0).text("far clipping plane")).changed() || apply_settings;
+
+                                        ui.horizontal(|ui|
+                                        {
+                                            ui.label("sensor:");
+                                            apply_settings = ui.add(egui::DragValue::new(&mut sensor_width).speed(0.1).clamp_range(1.0..=100.0).prefix("w: ").suffix(" mm")).changed() || apply_settings;
+                                            apply_settings = ui.add(egui::DragValue::new(&mut sensor_height).speed(0.1).clamp_range(1.0..=100.0).prefix("h: ").suffix(" mm")).changed() || apply_settings;
+                                        });
+
+                                        apply_settings = ui.add(egui::Slider::new(&mut focal_length_new, 1.0..=1000.0).logarithmic(true).suffix(" mm").text("focal length")).changed() || apply_settings;
+
+                                        apply_settings = ui.checkbox(&mut dof, "depth of field (thin lens)").changed() || apply_settings;
+                                        apply_settings = ui.add_enabled(dof, egui::Slider::new(&mut focus_distance, 0.01..=1000.0).logarithmic(true).text("focus distance")).changed() || apply_settings;
+
+                                        apply_settings = ui.add(egui::Slider::new(&mut f_number, 0.5..=64.0).logarithmic(true).prefix("f/").text("aperture (f-number)")).changed() || apply_settings;
+                                        apply_settings = ui.add(egui::Slider::new(&mut shutter_speed, 0.0001..=30.0).logarithmic(true).suffix(" s").text("shutter speed")).changed() || apply_settings;
+                                        apply_settings = ui.add(egui::Slider::new(&mut iso, 25.0..=25600.0).logarithmic(true).text("ISO")).changed() || apply_settings;
+                                        apply_settings = ui.checkbox(&mut exposure, "exposure (aperture, shutter speed and ISO)").changed() || apply_settings;
+
+                                        ui.horizontal(|ui|
+                                        {
+                                            ui.label("motion blur shutter:");
+                                            apply_settings = ui.add(egui::DragValue::new(&mut shutter_open).speed(0.01).clamp_range(-1.0..=1.0).prefix("open: ").suffix(" frames")).changed() || apply_settings;
+                                            apply_settings = ui.add(egui::DragValue::new(&mut shutter_close).speed(0.01).clamp_range(-1.0..=1.0).prefix("close: ").suffix(" frames")).changed() || apply_settings;
+                                        });
                                     });
 
                                     if apply_settings
                                     {
+                                        self.settings_changed = true;
                                         let mut scene = self.scene.write().unwrap();
                                         let cam = & mut scene.cam;
 
@@ -1280,6 +2235,27 @@ impl Run
 
                                         cam.clipping_near = clipping_near;
                                         cam.clipping_far = clipping_far;
+
+                                        cam.projection_type = projection_type;
+                                        cam.ortho_scale = ortho_scale;
+                                        cam.fisheye_fov = fisheye_fov.to_radians();
+                                        cam.stereo_eye_distance = stereo_eye_distance;
+
+                                        cam.sensor_width = sensor_width;
+                                        cam.sensor_height = sensor_height;
+                                        if focal_length != focal_length_new
+                                        {
+                                            cam.set_focal_length(focal_length_new);
+                                        }
+                                        cam.dof = dof;
+                                        cam.f_number = f_number;
+                                        cam.focus_distance = focus_distance;
+                                        cam.shutter_speed = shutter_speed;
+                                        cam.iso = iso;
+                                        cam.exposure = exposure;
+
+                                        cam.shutter_open = shutter_open;
+                                        cam.shutter_close = shutter_close;
                                     }
                                 });
                             });
@@ -1293,6 +2269,7 @@ impl Run
 
                             if ui.button("+").clicked()
                             {
+                                self.settings_changed = true;
                                 let mut scene = self.scene.write().unwrap();
                                 scene.add_default_light();
                             }
@@ -1323,6 +2300,12 @@ impl Run
                                         let mut intensity;
                                         let mut max_angle;
                                         let mut light_type;
+                                        let mut area_shape;
+                                        let mut width;
+                                        let mut height;
+                                        let mut radius;
+                                        let mut include;
+                                        let mut exclude;
 
                                         {
                                             let scene = self.scene.read().unwrap();
@@ -1341,6 +2324,14 @@ impl Run
                                             intensity = item.intensity;
                                             max_angle = item.max_angle;
                                             light_type = item.light_type;
+                                            area_shape = item.area_shape;
+                                            width = item.width;
+                                            height = item.height;
+                                            radius = item.radius;
+
+                                            //comma separated object or group names
+                                            include = item.include.join(",");
+                                            exclude = item.exclude.join(",");
                                         }
 
                                         let mut apply_settings = false;
@@ -1379,11 +2370,47 @@ impl Run
                                                 apply_settings = ui.selectable_value(& mut light_type, LightType::Directional, "Directional").changed() || apply_settings;
                                                 apply_settings = ui.selectable_value(& mut light_type, LightType::Point, "Point").changed() || apply_settings;
                                                 apply_settings = ui.selectable_value(& mut light_type, LightType::Spot, "Spot").changed() || apply_settings;
+                                                apply_settings = ui.selectable_value(& mut light_type, LightType::Area, "Area").changed() || apply_settings;
+                                            });
+
+                                            if light_type == LightType::Area
+                                            {
+                                                ui.horizontal(|ui|
+                                                {
+                                                    ui.label("shape:");
+                                                    apply_settings = ui.selectable_value(& mut area_shape, AreaShape::Rect, "Rect").changed() || apply_settings;
+                                                    apply_settings = ui.selectable_value(& mut area_shape, AreaShape::Disk, "Disk").changed() || apply_settings;
+                                                    apply_settings = ui.selectable_value(& mut area_shape, AreaShape::Sphere, "Sphere").changed() || apply_settings;
+                                                });
+
+                                                if area_shape == AreaShape::Rect
+                                                {
+                                                    apply_settings = ui.add(egui::Slider::new(&mut width, 0.01..=100.0).text("width")).changed() || apply_settings;
+                                                    apply_settings = ui.add(egui::Slider::new(&mut height, 0.01..=100.0).text("height")).changed() || apply_settings;
+                                                }
+                                                else
+                                                {
+                                                    apply_settings = ui.add(egui::Slider::new(&mut radius, 0.01..=100.0).text("radius")).changed() || apply_settings;
+                                                }
+                                            }
+
+                                            // light linking
+                                            ui.horizontal(|ui|
+                                            {
+                                                ui.label("include:");
+                                                apply_settings = ui.text_edit_singleline(&mut include).on_hover_text("comma separated object or group names (empty = all objects)").changed() || apply_settings;
+                                            });
+
+                                            ui.horizontal(|ui|
+                                            {
+                                                ui.label("exclude:");
+                                                apply_settings = ui.text_edit_singleline(&mut exclude).on_hover_text("comma separated object or group names").changed() || apply_settings;
                                             });
                                         });
 
                                         if apply_settings
                                         {
+                                            self.settings_changed = true;
                                             let mut scene = self.scene.write().unwrap();
                                             let item = scene.get_light_by_id_mut(item.0).unwrap();
 
@@ -1400,11 +2427,18 @@ impl Run
                                             item.intensity = intensity;
                                             item.max_angle = max_angle;
                                             item.light_type = light_type;
+                                            item.area_shape = area_shape;
+                                            item.width = width;
+                                            item.height = height;
+                                            item.radius = radius;
+                                            item.include = split_names(&include);
+                                            item.exclude = split_names(&exclude);
                                         }
                                     });
 
                                     if ui.button(RichText::new("delete").color(ui.visuals().error_fg_color)).clicked()
                                     {
+                                        self.settings_changed = true;
                                         let mut scene = self.scene.write().unwrap();
                                         scene.delete_light_by_id(item.0);
                                     }
@@ -1462,6 +2496,8 @@ impl Run
                                     {
                                         // basic settings
                                         let mut visible;
+                                        let mut ray_visibility;
+                                        let mut group;
                                         let mut flip_normals;
 
                                         {
@@ -1469,6 +2505,8 @@ impl Run
                                             let item = scene.get_obj_by_id(item.0).unwrap();
 
                                             visible = item.get_basic().visible;
+                                            ray_visibility = item.get_basic().ray_visibility;
+                                            group = item.get_basic().group.clone();
                                             flip_normals = item.get_basic().flip_normals;
                                         }
 
@@ -1477,15 +2515,35 @@ impl Run
                                         ui.vertical(|ui|
                                         {
                                             apply_settings = ui.checkbox(&mut visible, "Visible").changed() || apply_settings;
+
+                                            ui.horizontal(|ui|
+                                            {
+                                                ui.label("visible for:");
+                                                apply_settings = ui.checkbox(&mut ray_visibility.camera, "camera").changed() || apply_settings;
+                                                apply_settings = ui.checkbox(&mut ray_visibility.shadow, "shadow").changed() || apply_settings;
+                                                apply_settings = ui.checkbox(&mut ray_visibility.reflection, "reflection").changed() || apply_settings;
+                                                apply_settings = ui.checkbox(&mut ray_visibility.refraction, "refraction").changed() || apply_settings;
+                                                apply_settings = ui.checkbox(&mut ray_visibility.indirect, "indirect").changed() || apply_settings;
+                                            });
+
+                                            ui.horizontal(|ui|
+                                            {
+                                                ui.label("group:");
+                                                apply_settings = ui.text_edit_singleline(&mut group).on_hover_text("used for light linking").changed() || apply_settings;
+                                            });
+
                                             apply_settings = ui.checkbox(&mut flip_normals, "flip normals").changed() || apply_settings;
                                         });
 
                                         if apply_settings
                                         {
+                                            self.settings_changed = true;
                                             let mut scene = self.scene.write().unwrap();
                                             let item = scene.get_obj_by_id_mut(item.0).unwrap();
 
                                             item.get_basic_mut().visible = visible;
+                                            item.get_basic_mut().ray_visibility = ray_visibility;
+                                            item.get_basic_mut().group = group;
                                             item.get_basic_mut().flip_normals = flip_normals;
                                         }
                                     });
@@ -1506,6 +2564,7 @@ impl Run
 
                                     if ui.button(RichText::new("delete").color(ui.visuals().error_fg_color)).clicked()
                                     {
+                                        self.settings_changed = true;
                                         let mut scene = self.scene.write().unwrap();
                                         scene.delete_object_by_id(item.0);
                                     }
@@ -1553,6 +2612,19 @@ impl Run
             });
         });
 
+        //progressive rendering: restart the accumulation on setting changes (instead of stop/restart by hand)
+        if self.settings_changed
+        {
+            self.settings_changed = false;
+
+            let progressive = self.raytracing.read().unwrap().config.progressive;
+            if progressive && self.rendering.is_running()
+            {
+                self.restart_rendering();
+                self.stopped = false;
+            }
+        }
+
         // ********** status **********
         let bottom_frame = egui::containers::Frame
         {
@@ -1578,7 +2650,7 @@ impl Run
                 }
 
                 let pixels = self.rendering.get_rendered_pixels();
-                let progress = pixels as f32 / (self.width * self.height) as f32;
+                let progress = self.rendering.get_progress();
 
                 let mut frames = 1;
                 {
@@ -0,0 +1,740 @@
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+use crate::helper::rand;
+
+const ONE_MINUS_EPSILON: f32 = 0.99999994;
+
+const BLUE_NOISE_SIZE: usize = 64;
+const BLUE_NOISE_SIGMA: f32 = 1.5;
+const BLUE_NOISE_INITIAL_DENSITY: f32 = 0.1;
+
+const PRIMES: [u32; 32] =
+[
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131
+];
+
+/*
+some resources:
+
+samplers in general:
+https://pbr-book.org/4ed/Sampling_and_Reconstruction/Sampling_Interface
+
+owen scrambled sobol with hash based shuffling (padding of 2d sobol points):
+Burley 2020, "Practical Hash-based Owen Scrambling"
+https://jcgt.org/published/0009/04/01/
+
+blue noise dithered sampling and void and cluster:
+Georgiev and Fajardo 2016, "Blue-noise Dithered Sampling"
+Ulichney 1993, "The void-and-cluster method for dither array generation"
+
+every call of get_1d/get_2d is using the next dimension of the current pixel sample
+the integrators are requesting their random numbers in a fixed order (camera, lens, wavelength, bsdf, light, ...) which is needed for low discrepancy sequences
+*/
+
+// ******************** SamplerType ********************
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SamplerType
+{
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise
+}
+
+impl SamplerType
+{
+    pub fn from_str(sampler: &str) -> Option<SamplerType>
+    {
+        match sampler
+        {
+            "independent" | "random" => Some(SamplerType::Independent),
+            "stratified" => Some(SamplerType::Stratified),
+            "halton" => Some(SamplerType::Halton),
+            "sobol" => Some(SamplerType::Sobol),
+            "blue_noise" | "bluenoise" => Some(SamplerType::BlueNoise),
+            _ => None
+        }
+    }
+}
+
+// ******************** Sampler ********************
+
+pub trait Sampler
+{
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32);
+
+    fn get_1d(&mut self) -> f32;
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+pub fn create_sampler(sampler_type: SamplerType, samples_per_pixel: u32) -> Box<dyn Sampler>
+{
+    match sampler_type
+    {
+        SamplerType::Independent => Box::new(IndependentSampler::new()),
+        SamplerType::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel)),
+        SamplerType::Halton => Box::new(HaltonSampler::new()),
+        SamplerType::Sobol => Box::new(SobolSampler::new()),
+        SamplerType::BlueNoise => Box::new(BlueNoiseSampler::new())
+    }
+}
+
+// ********** active sampler of the render thread **********
+
+struct ActiveSampler
+{
+    sampler_type: SamplerType,
+    samples_per_pixel: u32,
+    sampler: Box<dyn Sampler>
+}
+
+thread_local!
+{
+    static ACTIVE_SAMPLER: RefCell<Option<ActiveSampler>> = RefCell::new(None);
+}
+
+// starts a new pixel sample on the current thread (the sampler gets recreated if the settings are changing)
+pub fn start_pixel_sample(sampler_type: SamplerType, samples_per_pixel: u32, x: u32, y: u32, sample_index: u32)
+{
+    ACTIVE_SAMPLER.with(|active|
+    {
+        let mut active = active.borrow_mut();
+
+        let recreate = match active.as_ref()
+        {
+            Some(active) => active.sampler_type != sampler_type || active.samples_per_pixel != samples_per_pixel,
+            None => true
+        };
+
+        if recreate
+        {
+            *active = Some(ActiveSampler
+            {
+                sampler_type: sampler_type,
+                samples_per_pixel: samples_per_pixel,
+                sampler: create_sampler(sampler_type, samples_per_pixel)
+            });
+        }
+
+        active.as_mut().unwrap().sampler.start_pixel_sample(x, y, sample_index);
+    });
+}
+
+// next sample dimension of the current pixel sample (uniform random outside of a pixel sample)
+pub fn sample_1d() -> f32
+{
+    ACTIVE_SAMPLER.with(|active|
+    {
+        match active.borrow_mut().as_mut()
+        {
+            Some(active) => active.sampler.get_1d(),
+            None => rand(0.0, 1.0)
+        }
+    })
+}
+
+pub fn sample_2d() -> (f32, f32)
+{
+    ACTIVE_SAMPLER.with(|active|
+    {
+        match active.borrow_mut().as_mut()
+        {
+            Some(active) => active.sampler.get_2d(),
+            None => (rand(0.0, 1.0), rand(0.0, 1.0))
+        }
+    })
+}
+
+// ******************** IndependentSampler ********************
+
+pub struct IndependentSampler
+{
+    seed: u32,
+    dimension: u32
+}
+
+impl IndependentSampler
+{
+    pub fn new() -> IndependentSampler
+    {
+        IndependentSampler { seed: 0, dimension: 0 }
+    }
+}
+
+impl Sampler for IndependentSampler
+{
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32)
+    {
+        self.seed = hash_combine(hash_pixel(x, y), sample_index);
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32
+    {
+        let value = hash_to_float(hash_combine(self.seed, self.dimension));
+        self.dimension += 1;
+
+        value
+    }
+
+    fn get_2d(&mut self) -> (f32, f32)
+    {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// ******************** StratifiedSampler ********************
+
+pub struct StratifiedSampler
+{
+    samples_per_pixel: u32,
+    samples_per_axis: u32, //0 if the sample count is not a square number (2d strata are falling back to latin hypercube)
+
+    pixel_seed: u32,
+    sample_index: u32,
+    dimension: u32
+}
+
+impl StratifiedSampler
+{
+    pub fn new(samples_per_pixel: u32) -> StratifiedSampler
+    {
+        let samples_per_pixel = samples_per_pixel.max(1);
+
+        let mut samples_per_axis = (samples_per_pixel as f32).sqrt().round() as u32;
+        if samples_per_axis * samples_per_axis != samples_per_pixel
+        {
+            samples_per_axis = 0;
+        }
+
+        StratifiedSampler
+        {
+            samples_per_pixel: samples_per_pixel,
+            samples_per_axis: samples_per_axis,
+
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0
+        }
+    }
+
+    fn get_seed(&self, dimension: u32) -> u32
+    {
+        // every pass over all samples of the pixel gets new strata permutations
+        let pass = self.sample_index / self.samples_per_pixel;
+        hash_combine(hash_combine(self.pixel_seed, dimension), pass)
+    }
+
+    fn get_stratified(&self, dimension: u32, strata: u32) -> f32
+    {
+        let seed = self.get_seed(dimension);
+        let stratum = permutation_element(self.sample_index % self.samples_per_pixel, strata, seed);
+        let jitter = hash_to_float(hash_combine(seed, self.sample_index));
+
+        ((stratum as f32 + jitter) / strata as f32).min(ONE_MINUS_EPSILON)
+    }
+}
+
+impl Sampler for StratifiedSampler
+{
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32)
+    {
+        self.pixel_seed = hash_pixel(x, y);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32
+    {
+        let value = self.get_stratified(self.dimension, self.samples_per_pixel);
+        self.dimension += 1;
+
+        value
+    }
+
+    fn get_2d(&mut self) -> (f32, f32)
+    {
+        // jittered grid
+        if self.samples_per_axis > 0
+        {
+            let seed = self.get_seed(self.dimension);
+            let stratum = permutation_element(self.sample_index % self.samples_per_pixel, self.samples_per_pixel, seed);
+
+            let jitter_x = hash_to_float(hash_combine(seed, self.sample_index * 2));
+            let jitter_y = hash_to_float(hash_combine(seed, self.sample_index * 2 + 1));
+
+            let strata = self.samples_per_axis as f32;
+            let x = ((stratum % self.samples_per_axis) as f32 + jitter_x) / strata;
+            let y = ((stratum / self.samples_per_axis) as f32 + jitter_y) / strata;
+
+            self.dimension += 2;
+
+            return (x.min(ONE_MINUS_EPSILON), y.min(ONE_MINUS_EPSILON));
+        }
+
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// ******************** HaltonSampler ********************
+
+pub struct HaltonSampler
+{
+    pixel_seed: u32,
+    sample_index: u32,
+    dimension: u32
+}
+
+impl HaltonSampler
+{
+    pub fn new() -> HaltonSampler
+    {
+        HaltonSampler { pixel_seed: 0, sample_index: 0, dimension: 0 }
+    }
+}
+
+impl Sampler for HaltonSampler
+{
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32)
+    {
+        self.pixel_seed = hash_pixel(x, y);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32
+    {
+        let seed = hash_combine(self.pixel_seed, self.dimension);
+
+        let value;
+        if (self.dimension as usize) < PRIMES.len()
+        {
+            value = scrambled_radical_inverse(PRIMES[self.dimension as usize], self.sample_index, seed);
+        }
+        else
+        {
+            // out of primes: the higher dimensions are uniform random
+            value = hash_to_float(hash_combine(seed, self.sample_index));
+        }
+
+        self.dimension += 1;
+
+        value
+    }
+
+    fn get_2d(&mut self) -> (f32, f32)
+    {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// ******************** SobolSampler ********************
+
+pub struct SobolSampler
+{
+    pixel_seed: u32,
+    sample_index: u32,
+    dimension: u32
+}
+
+impl SobolSampler
+{
+    pub fn new() -> SobolSampler
+    {
+        SobolSampler { pixel_seed: 0, sample_index: 0, dimension: 0 }
+    }
+}
+
+impl Sampler for SobolSampler
+{
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32)
+    {
+        self.pixel_seed = hash_pixel(x, y);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32
+    {
+        let value = owen_scrambled_sobol_2d(self.sample_index, hash_combine(self.pixel_seed, self.dimension)).0;
+        self.dimension += 1;
+
+        value
+    }
+
+    fn get_2d(&mut self) -> (f32, f32)
+    {
+        let value = owen_scrambled_sobol_2d(self.sample_index, hash_combine(self.pixel_seed, self.dimension));
+        self.dimension += 2;
+
+        value
+    }
+}
+
+// ******************** BlueNoiseSampler ********************
+
+// all pixels are sharing the same sobol sequence which gets shifted by a (per dimension offsetted) blue noise tile
+// --> the error is distributed as blue noise over the image (less visible at low sample counts)
+pub struct BlueNoiseSampler
+{
+    x: u32,
+    y: u32,
+    sample_index: u32,
+    dimension: u32
+}
+
+impl BlueNoiseSampler
+{
+    pub fn new() -> BlueNoiseSampler
+    {
+        BlueNoiseSampler { x: 0, y: 0, sample_index: 0, dimension: 0 }
+    }
+
+    fn get_shift(&self, dimension: u32) -> f32
+    {
+        // decorrelate the dimensions by shifting the tile (r2 sequence)
+        let offset_x = ((dimension as f32 * 0.7548776662).fract() * BLUE_NOISE_SIZE as f32) as u32;
+        let offset_y = ((dimension as f32 * 0.5698402910).fract() * BLUE_NOISE_SIZE as f32) as u32;
+
+        get_blue_noise(self.x + offset_x, self.y + offset_y)
+    }
+}
+
+impl Sampler for BlueNoiseSampler
+{
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32)
+    {
+        self.x = x;
+        self.y = y;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32
+    {
+        let value = owen_scrambled_sobol_2d(self.sample_index, hash(self.dimension)).0;
+        let value = (value + self.get_shift(self.dimension)).fract();
+
+        self.dimension += 1;
+
+        value.min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32)
+    {
+        let value = owen_scrambled_sobol_2d(self.sample_index, hash(self.dimension));
+        let x = (value.0 + self.get_shift(self.dimension)).fract();
+        let y = (value.1 + self.get_shift(self.dimension + 1)).fract();
+
+        self.dimension += 2;
+
+        (x.min(ONE_MINUS_EPSILON), y.min(ONE_MINUS_EPSILON))
+    }
+}
+
+// ******************** hashing ********************
+
+fn hash(mut x: u32) -> u32
+{
+    // lowbias32
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+
+    x
+}
+
+fn hash_combine(seed: u32, value: u32) -> u32
+{
+    hash(seed ^ (value.wrapping_add(0x9e3779b9).wrapping_add(seed << 6).wrapping_add(seed >> 2)))
+}
+
+fn hash_pixel(x: u32, y: u32) -> u32
+{
+    hash_combine(hash(x), y)
+}
+
+fn hash_to_float(value: u32) -> f32
+{
+    (value >> 8) as f32 * (1.0 / 16777216.0)
+}
+
+// element i of a random permutation of 0..length (Kensler 2013, "Correlated Multi-Jittered Sampling")
+fn permutation_element(mut i: u32, length: u32, seed: u32) -> u32
+{
+    if length <= 1
+    {
+        return 0;
+    }
+
+    let mut w = length - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop
+    {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < length
+        {
+            break;
+        }
+    }
+
+    (i.wrapping_add(seed)) % length
+}
+
+// ******************** low discrepancy sequences ********************
+
+// radical inverse with owen scrambled digits (every digit gets permuted based on the previous digits)
+fn scrambled_radical_inverse(base: u32, mut index: u32, seed: u32) -> f32
+{
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0f64;
+    let mut reversed_digits: u64 = 0;
+
+    // until the precision of f32 is reached (this includes the leading zero digits)
+    while inv_base_m > 1.0e-8
+    {
+        let next = index / base;
+        let digit = index - next * base;
+
+        let digit_seed = hash_combine(seed, reversed_digits as u32);
+        let digit = permutation_element(digit, base, digit_seed);
+
+        reversed_digits = reversed_digits * base as u64 + digit as u64;
+        inv_base_m *= inv_base;
+        index = next;
+    }
+
+    ((reversed_digits as f64 * inv_base_m) as f32).min(ONE_MINUS_EPSILON)
+}
+
+// first two dimensions of the sobol sequence
+fn sobol_2d(mut index: u32) -> (u32, u32)
+{
+    let x = index.reverse_bits();
+
+    let mut y = 0;
+    let mut direction = 1u32 << 31;
+    while index != 0
+    {
+        if index & 1 != 0
+        {
+            y ^= direction;
+        }
+
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+
+    (x, y)
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32
+{
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32
+{
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// shuffled and owen scrambled 2d sobol point (each seed results in an independent (0,2)-sequence)
+fn owen_scrambled_sobol_2d(index: u32, seed: u32) -> (f32, f32)
+{
+    let index = nested_uniform_scramble(index, seed);
+    let (x, y) = sobol_2d(index);
+
+    let x = nested_uniform_scramble(x, hash_combine(seed, 0));
+    let y = nested_uniform_scramble(y, hash_combine(seed, 1));
+
+    (hash_to_float(x), hash_to_float(y))
+}
+
+// ******************** blue noise ********************
+
+fn get_blue_noise(x: u32, y: u32) -> f32
+{
+    static BLUE_NOISE: OnceLock<Vec<f32>> = OnceLock::new();
+
+    let tile = BLUE_NOISE.get_or_init(|| generate_blue_noise(BLUE_NOISE_SIZE));
+
+    let x = x as usize % BLUE_NOISE_SIZE;
+    let y = y as usize % BLUE_NOISE_SIZE;
+
+    tile[y * BLUE_NOISE_SIZE + x]
+}
+
+// void and cluster dither array (values are the ranks mapped to 0..1)
+fn generate_blue_noise(size: usize) -> Vec<f32>
+{
+    let amount = size * size;
+
+    // gaussian energy kernel (wrapped arround the tile)
+    let mut kernel = vec![0.0f32; amount];
+    for y in 0..size
+    {
+        for x in 0..size
+        {
+            let dx = x.min(size - x) as f32;
+            let dy = y.min(size - y) as f32;
+            kernel[y * size + x] = (-(dx * dx + dy * dy) / (2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp();
+        }
+    }
+
+    let update_energy = |energy: &mut Vec<f32>, index: usize, sign: f32|
+    {
+        let px = index % size;
+        let py = index / size;
+
+        for y in 0..size
+        {
+            for x in 0..size
+            {
+                let kx = (x + size - px) % size;
+                let ky = (y + size - py) % size;
+                energy[y * size + x] += sign * kernel[ky * size + kx];
+            }
+        }
+    };
+
+    // highest (tightest cluster) or lowest (largest void) energy of all pixels with the given state
+    let find = |pattern: &Vec<bool>, energy: &Vec<f32>, state: bool, highest: bool| -> usize
+    {
+        let mut best = 0;
+        let mut best_energy = 0.0;
+        let mut found = false;
+
+        for i in 0..amount
+        {
+            if pattern[i] != state
+            {
+                continue;
+            }
+
+            if !found || (highest && energy[i] > best_energy) || (!highest && energy[i] < best_energy)
+            {
+                best = i;
+                best_energy = energy[i];
+                found = true;
+            }
+        }
+
+        best
+    };
+
+    // ***** initial binary pattern
+    let mut pattern = vec![false; amount];
+    let mut energy = vec![0.0f32; amount];
+
+    let initial_ones = ((amount as f32 * BLUE_NOISE_INITIAL_DENSITY) as usize).max(1);
+    let mut ones = 0;
+    let mut i = 0;
+    while ones < initial_ones
+    {
+        let index = hash(i as u32) as usize % amount;
+        if !pattern[index]
+        {
+            pattern[index] = true;
+            update_energy(&mut energy, index, 1.0);
+            ones += 1;
+        }
+        i += 1;
+    }
+
+    // relax: move the tightest cluster into the largest void until it converges
+    loop
+    {
+        let cluster = find(&pattern, &energy, true, true);
+        pattern[cluster] = false;
+        update_energy(&mut energy, cluster, -1.0);
+
+        let void = find(&pattern, &energy, false, false);
+        pattern[void] = true;
+        update_energy(&mut energy, void, 1.0);
+
+        if void == cluster
+        {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0usize; amount];
+
+    // ***** phase 1: remove the clusters of the initial pattern
+    {
+        let mut pattern = pattern.clone();
+        let mut energy = energy.clone();
+
+        for rank in (0..ones).rev()
+        {
+            let cluster = find(&pattern, &energy, true, true);
+            pattern[cluster] = false;
+            update_energy(&mut energy, cluster, -1.0);
+            ranks[cluster] = rank;
+        }
+    }
+
+    // ***** phase 2: fill the largest voids up to the half
+    for rank in ones..(amount / 2).max(ones)
+    {
+        let void = find(&pattern, &energy, false, false);
+        pattern[void] = true;
+        update_energy(&mut energy, void, 1.0);
+        ranks[void] = rank;
+    }
+
+    // ***** phase 3: the unset pixels are the minority now --> fill the tightest clusters of unset pixels
+    let mut energy = vec![0.0f32; amount];
+    for i in 0..amount
+    {
+        if !pattern[i]
+        {
+            update_energy(&mut energy, i, 1.0);
+        }
+    }
+
+    for rank in (amount / 2).max(ones)..amount
+    {
+        let cluster = find(&pattern, &energy, false, true);
+        pattern[cluster] = true;
+        update_energy(&mut energy, cluster, -1.0);
+        ranks[cluster] = rank;
+    }
+
+    ranks.iter().map(|rank| (*rank as f32 + 0.5) / amount as f32).collect()
+}
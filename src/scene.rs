@@ -1,22 +1,32 @@
 use bvh::bvh::BVHNode;
 use easy_gltf::Projection;
-use nalgebra::{Point2, Point3, Vector3};
+use nalgebra::{Matrix4, Point2, Point3, Vector3};
 use parry3d::query::Ray;
+use parry3d::bounding_volume::Aabb;
 use serde_json::Value;
+use chrono::{DateTime, Utc};
 
 use easy_gltf::Light::{Directional, Point, Spot};
 
 use image::{DynamicImage, Rgba, RgbaImage, ImageBuffer};
 
-use crate::helper::download;
+use crate::helper::{download, onb, max_component};
+use crate::sampler::{SamplerType, sample_2d};
 use crate::post_processing::PostProcessingConfig;
-use crate::raytracing::RaytracingConfig;
-use crate::shape::{Shape, TextureType, Material, ShapeBasics, MaterialItem};
+use crate::raytracing::{RaytracingConfig, IntegratorType};
+use crate::debug_render::RenderMode;
+use crate::shape::{Shape, TextureType, Material, ShapeBasics, MaterialItem, ShadingModel};
 
 use crate::shape::sphere::Sphere;
 use crate::shape::mesh::Mesh;
-use crate::camera::{Camera, OBLIQUE_CAM_POS, DEFAULT_FOV};
+use crate::camera::{Camera, CameraProjection, OBLIQUE_CAM_POS, DEFAULT_FOV};
 use crate::animation::{Animation, Frame, Keyframe};
+use crate::emitter::Emitters;
+use crate::environment::Environment;
+use crate::sky::{Sky, SKY_ENVIRONMENT_NAME};
+use crate::gltf_extensions::GltfExtensions;
+use crate::medium::{Medium, DensityType, DensityGrid};
+use crate::spectrum::DispersionModel;
 
 use std::f32::consts::PI;
 use std::path::Path;
@@ -25,6 +35,8 @@ use std::sync::{Arc, RwLock};
 pub type ScemeItem = Box<dyn Shape + Send + Sync>;
 pub type LightItem = Box<Light>;
 
+const GLTF_DEFAULT_IOR: f32 = 1.5;
+
 // ******************** LightType ********************
 
 #[derive(PartialEq, Debug, Copy, Clone)]
@@ -32,11 +44,41 @@ pub enum LightType
 {
     Directional,
     Point,
-    Spot
+    Spot,
+    Area
+}
+
+// ******************** AreaShape ********************
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum AreaShape
+{
+    Rect,
+    Disk,
+    Sphere
+}
+
+impl AreaShape
+{
+    pub fn from_str(area_shape: &str) -> Option<AreaShape>
+    {
+        match area_shape
+        {
+            "rect" | "rectangle" => Some(AreaShape::Rect),
+            "disk" => Some(AreaShape::Disk),
+            "sphere" => Some(AreaShape::Sphere),
+            _ => None
+        }
+    }
 }
 
 // ******************** Light ********************
 
+fn has_link_names(names: &Vec<String>) -> bool
+{
+    names.iter().any(|name| !name.trim().is_empty())
+}
+
 pub struct Light
 {
     pub enabled: bool,
@@ -47,7 +89,17 @@ pub struct Light
     pub color: Vector3<f32>,
     pub intensity: f32,
     pub max_angle: f32, //in rad
-    pub light_type: LightType
+    pub light_type: LightType,
+
+    //area light (rect and disk are emitting into dir)
+    pub area_shape: AreaShape,
+    pub width: f32,
+    pub height: f32,
+    pub radius: f32,
+
+    //light linking: names of objects or groups (an empty include list links all objects)
+    pub include: Vec<String>,
+    pub exclude: Vec<String>
 }
 
 impl Light
@@ -58,7 +110,243 @@ impl Light
         {
             LightType::Point => return "Point".to_string(),
             LightType::Directional => return "Directional".to_string(),
-            LightType::Spot => return "Spot".to_string()
+            LightType::Spot => return "Spot".to_string(),
+            LightType::Area => return "Area".to_string()
+        }
+    }
+
+    pub fn is_linked(&self) -> bool
+    {
+        has_link_names(&self.include) || has_link_names(&self.exclude)
+    }
+
+    // light linking: checks if the light is affecting the object (based on its name or group)
+    pub fn illuminates(&self, basic: &ShapeBasics) -> bool
+    {
+        if !self.is_linked()
+        {
+            return true;
+        }
+
+        let matches = |names: &Vec<String>| names.iter().map(|name| name.trim()).any(|name| !name.is_empty() && (name == basic.name || name == basic.group));
+
+        if has_link_names(&self.include) && !matches(&self.include)
+        {
+            return false;
+        }
+
+        !matches(&self.exclude)
+    }
+
+    // returns the direction to the light, the light intensity at the given point and the distance to the light
+    pub fn get_direction_and_intensity(&self, hit_point: &Point3<f32>) -> (Vector3<f32>, f32, f32)
+    {
+        match self.light_type
+        {
+            LightType::Directional =>
+            {
+                ((-self.dir).normalize(), self.intensity, std::f32::MAX)
+            },
+            LightType::Point =>
+            {
+                let r = (self.pos - hit_point).norm();
+                let direction_to_light = (self.pos - hit_point).normalize();
+
+                (direction_to_light, self.intensity / (4.0 * PI * r), r)
+            },
+            LightType::Spot =>
+            {
+                //use point as base and check angle
+                let r = (self.pos - hit_point).norm();
+                let direction_to_light = (self.pos - hit_point).normalize();
+                let mut intensity = self.intensity / (4.0 * PI * r);
+
+                let light_dir = self.dir.normalize();
+                let dot = (-direction_to_light).dot(&light_dir);
+                let angle = dot.acos();
+
+                if angle > self.max_angle
+                {
+                    intensity = 0.0;
+                }
+
+                (direction_to_light, intensity, r)
+            },
+            LightType::Area =>
+            {
+                //random point on the light surface (soft shadows are resolved by multiple samples per pixel)
+                let (u1, u2) = sample_2d();
+                let (light_point, light_normal, area_pdf) = self.sample_area(hit_point, u1, u2);
+
+                let r = (light_point - hit_point).norm();
+                let direction_to_light = (light_point - hit_point).normalize();
+
+                let cos_light = light_normal.dot(&(-direction_to_light));
+                if cos_light <= 0.0 || r <= 0.0 || area_pdf <= 0.0
+                {
+                    return (direction_to_light, 0.0, r);
+                }
+
+                //same estimator as the path tracer: radiance * cos / (pdf * r²)
+                //(divided by PI because the whitted lambert is not normalized)
+                let radiance = self.intensity / self.get_area();
+
+                (direction_to_light, radiance * cos_light / (area_pdf * r * r * PI), r)
+            }
+        }
+    }
+
+    pub fn get_area(&self) -> f32
+    {
+        match self.area_shape
+        {
+            AreaShape::Rect => self.width * self.height,
+            AreaShape::Disk => PI * self.radius * self.radius,
+            AreaShape::Sphere => 4.0 * PI * self.radius * self.radius
+        }
+    }
+
+    // enabled and (for area lights) with a non degenerated surface
+    pub fn is_active(&self) -> bool
+    {
+        self.enabled && (self.light_type != LightType::Area || self.get_area() > 0.0)
+    }
+
+    // emitted radiance of area lights
+    pub fn get_area_radiance(&self) -> Vector3<f32>
+    {
+        let area = self.get_area();
+
+        if area <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        self.color * (self.intensity / area)
+    }
+
+    // pdf (area measure) of the points returned by sample_area
+    pub fn get_area_pdf(&self) -> f32
+    {
+        if self.get_area() <= 0.0
+        {
+            return 0.0;
+        }
+
+        match self.area_shape
+        {
+            // only the hemisphere facing the hit point is sampled
+            AreaShape::Sphere => 1.0 / (2.0 * PI * self.radius * self.radius),
+            _ => 1.0 / self.get_area()
+        }
+    }
+
+    // returns a point on the light surface, its normal and the pdf (area measure)
+    pub fn sample_area(&self, hit_point: &Point3<f32>, u1: f32, u2: f32) -> (Point3<f32>, Vector3<f32>, f32)
+    {
+        match self.area_shape
+        {
+            AreaShape::Rect =>
+            {
+                let normal = self.dir.normalize();
+                let (tangent, bitangent) = onb(normal);
+
+                let point = self.pos + (tangent * ((u1 - 0.5) * self.width)) + (bitangent * ((u2 - 0.5) * self.height));
+
+                (point, normal, self.get_area_pdf())
+            },
+            AreaShape::Disk =>
+            {
+                let normal = self.dir.normalize();
+                let (tangent, bitangent) = onb(normal);
+
+                let r = self.radius * u1.sqrt();
+                let phi = 2.0 * PI * u2;
+
+                let point = self.pos + (tangent * (r * phi.cos())) + (bitangent * (r * phi.sin()));
+
+                (point, normal, self.get_area_pdf())
+            },
+            AreaShape::Sphere =>
+            {
+                //uniform sampling of the hemisphere facing the hit point
+                let w = (hit_point - self.pos).normalize();
+                let (tangent, bitangent) = onb(w);
+
+                let z = u1;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * u2;
+
+                let normal = (tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + w * z).normalize();
+                let point = self.pos + (normal * self.radius);
+
+                (point, normal, self.get_area_pdf())
+            }
+        }
+    }
+
+    // intersection of a ray with the (front facing) light surface -- returns the distance and the light normal
+    pub fn intersect_area(&self, ray: &Ray) -> Option<(f32, Vector3<f32>)>
+    {
+        match self.area_shape
+        {
+            AreaShape::Rect | AreaShape::Disk =>
+            {
+                let normal = self.dir.normalize();
+
+                let denom = normal.dot(&ray.dir);
+                if denom >= 0.0
+                {
+                    return None;
+                }
+
+                let dist = (self.pos - ray.origin).dot(&normal) / denom;
+                if dist <= 0.0
+                {
+                    return None;
+                }
+
+                let local = (ray.origin + (ray.dir * dist)) - self.pos;
+
+                if self.area_shape == AreaShape::Rect
+                {
+                    let (tangent, bitangent) = onb(normal);
+
+                    if local.dot(&tangent).abs() > self.width / 2.0 || local.dot(&bitangent).abs() > self.height / 2.0
+                    {
+                        return None;
+                    }
+                }
+                else if local.norm() > self.radius
+                {
+                    return None;
+                }
+
+                Some((dist, normal))
+            },
+            AreaShape::Sphere =>
+            {
+                let oc = ray.origin - self.pos;
+
+                let b = oc.dot(&ray.dir);
+                let c = oc.dot(&oc) - self.radius * self.radius;
+                let discriminant = b * b - c;
+
+                if discriminant < 0.0 || c <= 0.0
+                {
+                    return None;
+                }
+
+                let dist = -b - discriminant.sqrt();
+                if dist <= 0.0
+                {
+                    return None;
+                }
+
+                let normal = ((ray.origin + (ray.dir * dist)) - self.pos).normalize();
+
+                Some((dist, normal))
+            }
         }
     }
 }
@@ -70,15 +358,24 @@ pub struct Scene
 {
     pub item_id: u32, //for scene and light items
 
-    pub cam: Camera,
+    pub cam: Camera, //active camera
+    pub cameras: Vec<Camera>,
+    pub active_camera: usize,
     pub items: Vec<ScemeItem>,
     pub lights: Vec<LightItem>,
     pub animation: Animation,
+    pub frame: u64, //current animation frame
     pub materials: Vec<MaterialItem>,
 
     pub raytracing_config: RaytracingConfig,
     pub post_processing: PostProcessingConfig,
 
+    pub emitters: Emitters,
+    pub environment: Environment,
+    pub sky: Sky,
+
+    pub b_box: Aabb, //world space bounds of all items
+
     bvh: bvh::bvh::BVH
 }
 
@@ -91,14 +388,23 @@ impl Scene
             item_id: 0,
 
             cam: Camera::new(),
+            cameras: vec![],
+            active_camera: 0,
             items: vec![],
             lights: vec![],
             materials: vec![],
             animation: Animation::new(),
+            frame: 0,
 
             raytracing_config: RaytracingConfig::new(),
             post_processing: PostProcessingConfig::new(),
 
+            emitters: Emitters::new(),
+            environment: Environment::new(),
+            sky: Sky::new(),
+
+            b_box: Aabb::new_invalid(),
+
             bvh: bvh::bvh::BVH { nodes: vec![] }
         }
     }
@@ -109,6 +415,66 @@ impl Scene
         self.items.clear();
         self.lights.clear();
         self.materials.clear();
+        self.environment = Environment::new();
+        self.sky = Sky::new();
+    }
+
+    // ********** cameras **********
+
+    pub fn add_camera(&mut self, cam: Camera, activate: bool)
+    {
+        self.cameras.push(cam);
+
+        if activate || self.cameras.len() == 1
+        {
+            self.set_active_camera(self.cameras.len() - 1);
+        }
+    }
+
+    // replaces the active camera (or adds it if there is none)
+    pub fn replace_active_camera(&mut self, cam: Camera)
+    {
+        if self.active_camera < self.cameras.len()
+        {
+            self.cameras[self.active_camera] = cam.clone();
+            self.cam = cam;
+        }
+        else
+        {
+            self.add_camera(cam, true);
+        }
+    }
+
+    pub fn set_active_camera(&mut self, index: usize) -> bool
+    {
+        if index >= self.cameras.len()
+        {
+            return false;
+        }
+
+        // keep the changes of the current camera (UI)
+        if self.active_camera < self.cameras.len() && self.active_camera != index
+        {
+            self.cameras[self.active_camera] = self.cam.clone();
+        }
+
+        let width = self.cam.width;
+        let height = self.cam.height;
+
+        self.cam = self.cameras[index].clone();
+        self.active_camera = index;
+
+        if width > 0 && height > 0
+        {
+            self.cam.init(width, height);
+        }
+
+        true
+    }
+
+    pub fn get_camera_index(&self, name: &str) -> Option<usize>
+    {
+        self.cameras.iter().position(|cam| cam.name == name)
     }
 
     pub fn get_next_id(&mut self) -> u32
@@ -144,6 +510,10 @@ impl Scene
         {
             loaded_ids = self.load_wavefront(path);
         }
+        else if extension == "hdr" || extension == "exr"
+        {
+            self.environment.load(path);
+        }
         else
         {
             println!("can not load {}", path);
@@ -170,20 +540,63 @@ impl Scene
                 let data = scene_data.unwrap();
 
                 let camera = &data["camera"];
+                let cameras = data["cameras"].as_array();
                 let lights = data["lights"].as_array();
                 let objects = data["objects"].as_array();
                 let animation = &data["animation"];
                 let config = &data["config"];
                 let post = &data["post"];
+                let environment = &data["environment"];
+
+                // a user environment map is only replaced by the sky if it is explicitly enabled
+                let mut sky_explicitly_enabled = false;
 
                 // ********** config **********
                 if !config.is_null()
                 {
+                    if !&config["integrator"].is_null()
+                    {
+                        let integrator = config["integrator"].as_str().unwrap();
+                        if let Some(integrator) = IntegratorType::from_str(integrator)
+                        {
+                            self.raytracing_config.integrator = integrator;
+                        }
+                        else
+                        {
+                            println!("unknown integrator: {}", integrator);
+                        }
+                    }
+                    if !&config["render_mode"].is_null()
+                    {
+                        let render_mode = config["render_mode"].as_str().unwrap();
+                        if let Some(render_mode) = RenderMode::from_str(render_mode)
+                        {
+                            self.raytracing_config.render_mode = render_mode;
+                        }
+                        else
+                        {
+                            println!("unknown render mode: {}", render_mode);
+                        }
+                    }
                     if !&config["monte_carlo"].is_null() { self.raytracing_config.monte_carlo = config["monte_carlo"].as_bool().unwrap(); }
+                    if !&config["spectral"].is_null() { self.raytracing_config.spectral = config["spectral"].as_bool().unwrap(); }
                     if !&config["samples"].is_null() { self.raytracing_config.samples = config["samples"].as_u64().unwrap() as u16;}
-
-                    if !&config["focal_length"].is_null() { self.raytracing_config.focal_length = config["focal_length"].as_f64().unwrap() as f32; }
-                    if !&config["aperture_size"].is_null() { self.raytracing_config.aperture_size = config["aperture_size"].as_f64().unwrap() as f32; }
+                    if !&config["sampler"].is_null()
+                    {
+                        let sampler = config["sampler"].as_str().unwrap();
+                        if let Some(sampler) = SamplerType::from_str(sampler)
+                        {
+                            self.raytracing_config.sampler = sampler;
+                        }
+                        else
+                        {
+                            println!("unknown sampler: {}", sampler);
+                        }
+                    }
+                    if !&config["adaptive_threshold"].is_null() { self.raytracing_config.adaptive_threshold = config["adaptive_threshold"].as_f64().unwrap() as f32; }
+                    if !&config["sample_count_image"].is_null() { self.raytracing_config.sample_count_image = config["sample_count_image"].as_bool().unwrap(); }
+                    if !&config["progressive"].is_null() { self.raytracing_config.progressive = config["progressive"].as_bool().unwrap(); }
+                    if !&config["time_limit"].is_null() { self.raytracing_config.time_limit = config["time_limit"].as_f64().unwrap() as f32; }
 
                     if !&config["fog_density"].is_null() { self.raytracing_config.fog_density = config["fog_density"].as_f64().unwrap() as f32; }
                     if !&config["fog_color"].is_null()
@@ -192,9 +605,60 @@ impl Scene
                         self.raytracing_config.fog_color.y = config["fog_color"]["g"].as_f64().unwrap() as f32;
                         self.raytracing_config.fog_color.z = config["fog_color"]["b"].as_f64().unwrap() as f32;
                     }
+                    if !&config["fog_anisotropy"].is_null() { self.raytracing_config.fog_anisotropy = config["fog_anisotropy"].as_f64().unwrap() as f32; }
+
+                    if !&config["photon_count"].is_null() { self.raytracing_config.photon_count = config["photon_count"].as_u64().unwrap() as u32; }
+                    if !&config["photon_radius"].is_null() { self.raytracing_config.photon_radius = config["photon_radius"].as_f64().unwrap() as f32; }
 
                     if !&config["max_recursion"].is_null() { self.raytracing_config.max_recursion = config["max_recursion"].as_u64().unwrap() as u16; }
+                    if !&config["max_diffuse_bounces"].is_null() { self.raytracing_config.max_diffuse_bounces = config["max_diffuse_bounces"].as_u64().unwrap() as u16; }
+                    if !&config["max_glossy_bounces"].is_null() { self.raytracing_config.max_glossy_bounces = config["max_glossy_bounces"].as_u64().unwrap() as u16; }
+                    if !&config["max_transmission_bounces"].is_null() { self.raytracing_config.max_transmission_bounces = config["max_transmission_bounces"].as_u64().unwrap() as u16; }
+                    if !&config["indirect_clamp"].is_null() { self.raytracing_config.indirect_clamp = config["indirect_clamp"].as_f64().unwrap() as f32; }
                     if !&config["gamma_correction"].is_null() { self.raytracing_config.gamma_correction = config["gamma_correction"].as_bool().unwrap(); }
+
+                    // sky
+                    let sky = &config["sky"];
+                    if !sky.is_null()
+                    {
+                        self.sky.enabled = !self.environment.is_loaded() || self.environment.path == SKY_ENVIRONMENT_NAME;
+
+                        if !&sky["enabled"].is_null()
+                        {
+                            self.sky.enabled = sky["enabled"].as_bool().unwrap();
+                            sky_explicitly_enabled = self.sky.enabled;
+                        }
+                        if !&sky["turbidity"].is_null() { self.sky.turbidity = sky["turbidity"].as_f64().unwrap() as f32; }
+                        if !&sky["ground_albedo"].is_null() { self.sky.ground_albedo = sky["ground_albedo"].as_f64().unwrap() as f32; }
+                        if !&sky["intensity"].is_null() { self.sky.intensity = sky["intensity"].as_f64().unwrap() as f32; }
+                        if !&sky["sun_intensity"].is_null() { self.sky.sun_intensity = sky["sun_intensity"].as_f64().unwrap() as f32; }
+
+                        if !&sky["sun_dir"].is_null()
+                        {
+                            let sun_dir = self.get_vec_from_json_object("sun_dir", &sky, Vector3::<f32>::new(0.0, 1.0, 0.0));
+                            self.sky.set_sun_direction(sun_dir);
+                        }
+
+                        if !&sky["sun_elevation"].is_null() { self.sky.sun_elevation = sky["sun_elevation"].as_f64().unwrap() as f32; }
+                        if !&sky["sun_azimuth"].is_null() { self.sky.sun_azimuth = sky["sun_azimuth"].as_f64().unwrap() as f32; }
+
+                        if !&sky["latitude"].is_null() { self.sky.latitude = sky["latitude"].as_f64().unwrap() as f32; self.sky.use_location = true; }
+                        if !&sky["longitude"].is_null() { self.sky.longitude = sky["longitude"].as_f64().unwrap() as f32; self.sky.use_location = true; }
+                        if !&sky["date_time"].is_null()
+                        {
+                            let date_time = sky["date_time"].as_str().unwrap();
+                            if let Ok(date_time) = DateTime::parse_from_rfc3339(date_time)
+                            {
+                                self.sky.date_time = date_time.with_timezone(&Utc);
+                            }
+                            else
+                            {
+                                println!("can not parse sky date_time (RFC 3339 expected): {}", date_time);
+                            }
+                        }
+
+                        self.apply_sky();
+                    }
                 }
 
                 // ********** post processing **********
@@ -202,38 +666,29 @@ impl Scene
                 {
                     if !&post["cavity"].is_null() { self.post_processing.cavity = post["cavity"].as_bool().unwrap(); }
                     if !&post["outline"].is_null() { self.post_processing.outline = post["outline"].as_bool().unwrap(); }
+                    if !&post["denoise"].is_null() { self.post_processing.denoise = post["denoise"].as_bool().unwrap(); }
+                    if !&post["denoise_iterations"].is_null() { self.post_processing.denoise_iterations = post["denoise_iterations"].as_u64().unwrap() as u32; }
                 }
 
                 // ********** camera **********
                 if !camera.is_null()
                 {
-                    let default_cam = Camera::new();
-
-                    let pos = self.get_point_from_json_object("pos", &camera, default_cam.eye_pos);
-                    let up = self.get_vec_from_json_object("up", &camera, default_cam.up);
-                    let dir = self.get_vec_from_json_object("dir", &camera, default_cam.dir);
+                    let mut cam = self.cam.clone();
+                    self.apply_camera_json(camera, &mut cam);
 
-                    self.cam.eye_pos = pos;
-                    self.cam.dir = dir;
-                    self.cam.up = up;
-
-                    let fov =  camera["fov"].as_f64();
-                    let z_near =  camera["z_near"].as_f64();
-                    let z_far =  camera["z_far"].as_f64();
-
-                    if let Some(fov) = fov
-                    {
-                        self.cam.fov = fov.to_radians() as f32;
-                    }
+                    self.replace_active_camera(cam);
+                }
 
-                    if let Some(z_near) = z_near
+                // ********** cameras (multiple named cameras, the first one is active) **********
+                if let Some(cameras) = cameras
+                {
+                    for (i, camera) in cameras.iter().enumerate()
                     {
-                        self.cam.clipping_near = z_near as f32;
-                    }
+                        let mut cam = Camera::new();
+                        cam.name = format!("camera {}", self.cameras.len() + 1);
+                        self.apply_camera_json(camera, &mut cam);
 
-                    if let Some(z_far) = z_far
-                    {
-                        self.cam.clipping_far = z_far as f32;
+                        self.add_camera(cam, i == 0);
                     }
                 }
 
@@ -270,9 +725,36 @@ impl Scene
                             "point" => { light_type = LightType::Point },
                             "directional" => { light_type = LightType::Directional },
                             "spot" => { light_type = LightType::Spot },
+                            "area" => { light_type = LightType::Area },
                             _ => {}
                         }
 
+                        // area light shape and size
+                        let mut area_shape = AreaShape::Rect;
+                        if !light["area_shape"].is_null()
+                        {
+                            let area_shape_str = light["area_shape"].as_str().unwrap();
+                            if let Some(shape) = AreaShape::from_str(area_shape_str)
+                            {
+                                area_shape = shape;
+                            }
+                            else
+                            {
+                                println!("unknown area light shape: {}", area_shape_str);
+                            }
+                        }
+
+                        let mut width = 1.0;
+                        let mut height = 1.0;
+                        let mut radius = 0.5;
+                        if !light["width"].is_null() { width = light["width"].as_f64().unwrap() as f32; }
+                        if !light["height"].is_null() { height = light["height"].as_f64().unwrap() as f32; }
+                        if !light["radius"].is_null() { radius = light["radius"].as_f64().unwrap() as f32; }
+
+                        // light linking
+                        let include = self.get_string_list_from_json_object("include", &light);
+                        let exclude = self.get_string_list_from_json_object("exclude", &light);
+
                         let id = self.get_next_id();
                         self.lights.push(Box::new(Light
                         {
@@ -284,11 +766,38 @@ impl Scene
                             color: color,
                             intensity: intensity,
                             max_angle: max_angle,
-                            light_type: light_type
+                            light_type: light_type,
+
+                            area_shape: area_shape,
+                            width: width,
+                            height: height,
+                            radius: radius,
+
+                            include: include,
+                            exclude: exclude
                         }));
                     }
                 }
 
+                // ********** environment **********
+                if !environment.is_null()
+                {
+                    if !&environment["path"].is_null()
+                    {
+                        self.environment.load(environment["path"].as_str().unwrap());
+
+                        // the sky is kept (baked again) only if it was explicitly enabled
+                        if self.sky.enabled
+                        {
+                            self.sky.enabled = sky_explicitly_enabled;
+                            self.apply_sky();
+                        }
+                    }
+                    if !&environment["enabled"].is_null() { self.environment.enabled = environment["enabled"].as_bool().unwrap(); }
+                    if !&environment["intensity"].is_null() { self.environment.intensity = environment["intensity"].as_f64().unwrap() as f32; }
+                    if !&environment["rotation"].is_null() { self.environment.rotation = (environment["rotation"].as_f64().unwrap() as f32).to_radians(); }
+                }
+
                 // ********** objects **********
                 if let Some(objects) = objects
                 {
@@ -330,6 +839,13 @@ impl Scene
                             {
                                 material.ambient_color = material.base_color * colors["ambient"]["factor"].as_f64().unwrap() as f32;
                             }
+
+                            // emission (emissive objects are used as light sources)
+                            if !colors["emission"].is_null()
+                            {
+                                material.ambient_color = self.get_color_from_json_object("emission", &colors, material.ambient_color);
+                                material.emissive = true;
+                            }
                         }
 
                         // ***** material settings
@@ -338,16 +854,74 @@ impl Scene
                         if !&object["shininess"].is_null() { material.shininess = object["shininess"].as_f64().unwrap() as f32; }
                         if !&object["reflectivity"].is_null() { material.reflectivity = object["reflectivity"].as_f64().unwrap() as f32; }
                         if !&object["refraction_index"].is_null() { material.refraction_index = object["refraction_index"].as_f64().unwrap() as f32; }
+                        if !&object["dispersion_model"].is_null()
+                        {
+                            let dispersion_model = object["dispersion_model"].as_str().unwrap();
+                            if let Some(dispersion_model) = DispersionModel::from_str(dispersion_model)
+                            {
+                                material.dispersion_model = dispersion_model;
+                            }
+                            else
+                            {
+                                println!("unknown dispersion model: {}", dispersion_model);
+                            }
+                        }
+                        if let Some(cauchy) = object["cauchy"].as_array()
+                        {
+                            for (i, coefficient) in cauchy.iter().take(3).enumerate() { material.cauchy_coefficients[i] = coefficient.as_f64().unwrap() as f32; }
+                        }
+                        if let Some(sellmeier_b) = object["sellmeier"]["b"].as_array()
+                        {
+                            for (i, coefficient) in sellmeier_b.iter().take(3).enumerate() { material.sellmeier_b[i] = coefficient.as_f64().unwrap() as f32; }
+                        }
+                        if let Some(sellmeier_c) = object["sellmeier"]["c"].as_array()
+                        {
+                            for (i, coefficient) in sellmeier_c.iter().take(3).enumerate() { material.sellmeier_c[i] = coefficient.as_f64().unwrap() as f32; }
+                        }
+                        material.transmission_color = self.get_color_from_json_object("transmission_color", &object, material.transmission_color);
+                        if !&object["attenuation_distance"].is_null() { material.attenuation_distance = object["attenuation_distance"].as_f64().unwrap() as f32; }
+                        if !&object["subsurface"].is_null() { material.subsurface = object["subsurface"].as_f64().unwrap() as f32; }
+                        material.subsurface_color = self.get_color_from_json_object("subsurface_color", &object, material.subsurface_color);
+                        material.subsurface_radius = self.get_vec_from_json_object("subsurface_radius", &object, material.subsurface_radius);
+                        if !&object["subsurface_scale"].is_null() { material.subsurface_scale = object["subsurface_scale"].as_f64().unwrap() as f32; }
                         if !&object["normal_map_strength"].is_null() { material.normal_map_strength = object["normal_map_strength"].as_f64().unwrap() as f32; }
                         if !&object["cast_shadow"].is_null() { material.cast_shadow = object["cast_shadow"].as_bool().unwrap(); }
                         if !&object["receive_shadow"].is_null() { material.receive_shadow = object["receive_shadow"].as_bool().unwrap(); }
                         if !&object["shadow_softness"].is_null() { material.shadow_softness = object["shadow_softness"].as_f64().unwrap() as f32; }
                         if !&object["roughness"].is_null() { material.roughness = object["roughness"].as_f64().unwrap() as f32; }
+                        if !&object["shading_model"].is_null()
+                        {
+                            let shading_model = object["shading_model"].as_str().unwrap();
+                            if let Some(shading_model) = ShadingModel::from_str(shading_model)
+                            {
+                                material.shading_model = shading_model;
+                            }
+                            else
+                            {
+                                println!("unknown shading model: {}", shading_model);
+                            }
+                        }
+                        if !&object["metallic"].is_null() { material.metallic = object["metallic"].as_f64().unwrap() as f32; }
+                        if !&object["pbr_roughness"].is_null() { material.pbr_roughness = object["pbr_roughness"].as_f64().unwrap() as f32; }
+                        if !&object["clearcoat"].is_null() { material.clearcoat = object["clearcoat"].as_f64().unwrap() as f32; }
+                        if !&object["clearcoat_roughness"].is_null() { material.clearcoat_roughness = object["clearcoat_roughness"].as_f64().unwrap() as f32; }
+                        if !&object["clearcoat_normal_map_strength"].is_null() { material.clearcoat_normal_map_strength = object["clearcoat_normal_map_strength"].as_f64().unwrap() as f32; }
+                        material.sheen_color = self.get_color_from_json_object("sheen_color", &object, material.sheen_color);
+                        if !&object["sheen_roughness"].is_null() { material.sheen_roughness = object["sheen_roughness"].as_f64().unwrap() as f32; }
+                        if !&object["anisotropy"].is_null() { material.anisotropy = object["anisotropy"].as_f64().unwrap() as f32; }
+                        if !&object["anisotropy_rotation"].is_null() { material.anisotropy_rotation = object["anisotropy_rotation"].as_f64().unwrap() as f32; }
+                        if !&object["iridescence"].is_null() { material.iridescence = object["iridescence"].as_f64().unwrap() as f32; }
+                        if !&object["iridescence_ior"].is_null() { material.iridescence_ior = object["iridescence_ior"].as_f64().unwrap() as f32; }
+                        if !&object["iridescence_thickness_min"].is_null() { material.iridescence_thickness_min = object["iridescence_thickness_min"].as_f64().unwrap() as f32; }
+                        if !&object["iridescence_thickness_max"].is_null() { material.iridescence_thickness_max = object["iridescence_thickness_max"].as_f64().unwrap() as f32; }
                         if !&object["monte_carlo"].is_null() { material.monte_carlo = object["monte_carlo"].as_bool().unwrap(); }
                         if !&object["smooth_shading"].is_null() { material.smooth_shading = object["smooth_shading"].as_bool().unwrap(); }
                         if !&object["reflection_only"].is_null() { material.reflection_only = object["reflection_only"].as_bool().unwrap(); }
                         if !&object["backface_cullig"].is_null() { material.backface_cullig = object["backface_cullig"].as_bool().unwrap(); }
 
+                        // ***** interior medium
+                        if !&object["medium"].is_null() { material.medium = self.get_medium_from_json_object(&object["medium"]); }
+
                         // ***** textures
                         let texture = &object["texture"];
 
@@ -394,6 +968,24 @@ impl Scene
                             {
                                 material.load_texture(texture["ambient_occlusion"].as_str().unwrap(), TextureType::AmbientOcclusion);
                             }
+
+                            // clearcoat_normal
+                            if texture["clearcoat_normal"].is_string()
+                            {
+                                material.load_texture(texture["clearcoat_normal"].as_str().unwrap(), TextureType::ClearcoatNormal);
+                            }
+
+                            // iridescence_thickness
+                            if texture["iridescence_thickness"].is_string()
+                            {
+                                material.load_texture(texture["iridescence_thickness"].as_str().unwrap(), TextureType::IridescenceThickness);
+                            }
+
+                            // anisotropy
+                            if texture["anisotropy"].is_string()
+                            {
+                                material.load_texture(texture["anisotropy"].as_str().unwrap(), TextureType::Anisotropy);
+                            }
                         }
 
                         // ***** other (basic item) settings
@@ -404,6 +996,21 @@ impl Scene
                         if !&object["visible"].is_null() { visible = object["visible"].as_bool().unwrap(); }
                         if !&object["flip_normals"].is_null() { flip_normals = object["flip_normals"].as_bool().unwrap(); }
 
+                        let mut group = default_basic_settings.group.clone();
+                        if !&object["group"].is_null() { group = object["group"].as_str().unwrap().to_string(); }
+
+                        // per ray type visibility
+                        let mut ray_visibility = default_basic_settings.ray_visibility;
+                        let ray_visibility_json = &object["ray_visibility"];
+                        if !ray_visibility_json.is_null()
+                        {
+                            if !&ray_visibility_json["camera"].is_null() { ray_visibility.camera = ray_visibility_json["camera"].as_bool().unwrap(); }
+                            if !&ray_visibility_json["shadow"].is_null() { ray_visibility.shadow = ray_visibility_json["shadow"].as_bool().unwrap(); }
+                            if !&ray_visibility_json["reflection"].is_null() { ray_visibility.reflection = ray_visibility_json["reflection"].as_bool().unwrap(); }
+                            if !&ray_visibility_json["refraction"].is_null() { ray_visibility.refraction = ray_visibility_json["refraction"].as_bool().unwrap(); }
+                            if !&ray_visibility_json["indirect"].is_null() { ray_visibility.indirect = ray_visibility_json["indirect"].as_bool().unwrap(); }
+                        }
+
                         // ***** transformation
                         let mut rotation = Vector3::<f32>::new(0.0, 0.0, 0.0);
                         let mut scale = Vector3::<f32>::new(1.0, 1.0, 1.0);
@@ -519,6 +1126,16 @@ impl Scene
                                             item.get_basic_mut().name = name.to_string();
                                         }
 
+                                        if !object["group"].is_null()
+                                        {
+                                            item.get_basic_mut().group = group.clone();
+                                        }
+
+                                        if !ray_visibility_json.is_null()
+                                        {
+                                            item.get_basic_mut().ray_visibility = ray_visibility;
+                                        }
+
                                         item.get_basic_mut().material.write().unwrap().apply_diff(&material_arc.read().unwrap());
                                         item.get_basic_mut().visible = visible;
                                         item.get_basic_mut().flip_normals = flip_normals;
@@ -535,6 +1152,8 @@ impl Scene
                         {
                             //shape.get_basic_mut().material = material;
                             shape.get_basic_mut().visible = visible;
+                            shape.get_basic_mut().ray_visibility = ray_visibility;
+                            shape.get_basic_mut().group = group;
                             shape.get_basic_mut().flip_normals = flip_normals;
                             shape.get_basic_mut().apply_transformation(translation, scale, rotation);
 
@@ -609,54 +1228,232 @@ impl Scene
                                             rotation_rad.y = r.y.to_radians();
                                             rotation_rad.z = r.z.to_radians();
 
-                                            rotation = Some(rotation_rad);
-                                        }
-                                    }
+                                            rotation = Some(rotation_rad);
+                                        }
+                                    }
+
+                                    let frame = Frame::new(object_name.to_string(), translation, rotation, scale);
+                                    keyframe_data.push(frame);
+                                }
+                            }
+
+                            let keyframe = Keyframe::new(time, keyframe_data);
+                            keyframes_data.push(keyframe);
+                        }
+
+                        // apply animation data
+                        self.animation.keyframes = keyframes_data;
+                    }
+                }
+            }
+            else
+            {
+                println!("error can not parse json file {}", path);
+            }
+        }
+        else
+        {
+            println!("error can not load file {}", path);
+        }
+
+        loaded_ids
+    }
+
+    pub fn get_color_from_json_object(&self, key: &str, json_obj: &Value, default_data: Vector3::<f32>) -> Vector3::<f32>
+    {
+        let mut vec = default_data;
+
+        if json_obj.is_null()
+        {
+            return vec;
+        }
+
+        if !json_obj[key].is_null() && !json_obj[key]["r"].is_null() && !json_obj[key]["g"].is_null() && !json_obj[key]["b"].is_null()
+        {
+            vec.x = json_obj[key]["r"].as_f64().unwrap() as f32;
+            vec.y = json_obj[key]["g"].as_f64().unwrap() as f32;
+            vec.z = json_obj[key]["b"].as_f64().unwrap() as f32;
+        }
+
+        vec
+    }
+
+    pub fn get_medium_from_json_object(&self, json_obj: &Value) -> Medium
+    {
+        let mut medium = Medium::new();
+        medium.enabled = true;
+
+        if !&json_obj["enabled"].is_null() { medium.enabled = json_obj["enabled"].as_bool().unwrap(); }
+
+        medium.absorption = self.get_color_from_json_object("absorption", json_obj, medium.absorption);
+        medium.scattering = self.get_color_from_json_object("scattering", json_obj, medium.scattering);
+
+        if !&json_obj["anisotropy"].is_null() { medium.anisotropy = json_obj["anisotropy"].as_f64().unwrap() as f32; }
+        if !&json_obj["density"].is_null() { medium.density = json_obj["density"].as_f64().unwrap() as f32; }
+        if !&json_obj["density_type"].is_null()
+        {
+            let density_type = json_obj["density_type"].as_str().unwrap();
+            if let Some(density_type) = DensityType::from_str(density_type)
+            {
+                medium.density_type = density_type;
+            }
+            else
+            {
+                println!("unknown density type: {}", density_type);
+            }
+        }
+        if !&json_obj["noise_scale"].is_null() { medium.noise_scale = json_obj["noise_scale"].as_f64().unwrap() as f32; }
+        if !&json_obj["noise_octaves"].is_null() { medium.noise_octaves = json_obj["noise_octaves"].as_u64().unwrap() as u32; }
+        if !&json_obj["step_size"].is_null() { medium.step_size = json_obj["step_size"].as_f64().unwrap() as f32; }
+
+        // density grid (inline data or raw f32 file)
+        let grid = &json_obj["grid"];
+        if !grid.is_null()
+        {
+            let width = grid["width"].as_u64().unwrap_or(0) as usize;
+            let height = grid["height"].as_u64().unwrap_or(0) as usize;
+            let depth = grid["depth"].as_u64().unwrap_or(0) as usize;
+
+            if grid["path"].is_string()
+            {
+                medium.grid = DensityGrid::load(grid["path"].as_str().unwrap(), width, height, depth);
+            }
+            else if let Some(data) = grid["data"].as_array()
+            {
+                let data = data.iter().map(|value| value.as_f64().unwrap_or(0.0) as f32).collect();
+                medium.grid = DensityGrid::new(width, height, depth, data);
+            }
+        }
+
+        medium
+    }
 
-                                    let frame = Frame::new(object_name.to_string(), translation, rotation, scale);
-                                    keyframe_data.push(frame);
-                                }
-                            }
+    fn get_camera_from_gltf(&self, cam: &easy_gltf::Camera, gltf_extensions: &GltfExtensions) -> Camera
+    {
+        let mut camera = Camera::new();
 
-                            let keyframe = Keyframe::new(time, keyframe_data);
-                            keyframes_data.push(keyframe);
-                        }
+        if let Some(name) = &cam.name
+        {
+            camera.name = name.clone();
+        }
 
-                        // apply animation data
-                        self.animation.keyframes = keyframes_data;
+        let pos = cam.position();
+        let up = cam.up();
+        let forward = cam.forward();
+
+        camera.eye_pos = Point3::<f32>::new(pos.x, pos.y, pos.z);
+        camera.dir = Vector3::<f32>::new(-forward.x, -forward.y, -forward.z).normalize();
+        camera.up = Vector3::<f32>::new(up.x, up.y, up.z).normalize();
+
+        match cam.projection
+        {
+            Projection::Perspective { yfov, aspect_ratio } =>
+            {
+                // the sensor matches the aspect ratio of the camera (the focal length is derived from the fov)
+                if let Some(aspect_ratio) = aspect_ratio
+                {
+                    if aspect_ratio > 0.0
+                    {
+                        camera.sensor_height = camera.sensor_width / aspect_ratio;
                     }
                 }
-            }
-            else
+
+                camera.projection_type = CameraProjection::Perspective;
+                camera.fov = yfov.0;
+            },
+            Projection::Orthographic { scale } =>
             {
-                println!("error can not parse json file {}", path);
-            }
+                camera.projection_type = CameraProjection::Orthographic;
+                camera.ortho_scale = scale.y;
+            },
         }
-        else
+
+        camera.clipping_near = cam.znear;
+        camera.clipping_far = cam.zfar;
+
+        // physical camera parameters (custom properties of the camera, e.g. exported from blender)
+        if let Some(extras) = gltf_extensions.get_camera_extras(&cam.name)
         {
-            println!("error can not load file {}", path);
+            if let Some(sensor_width) = extras["sensor_width"].as_f64()
+            {
+                camera.sensor_height *= sensor_width as f32 / camera.sensor_width;
+                camera.sensor_width = sensor_width as f32;
+            }
+            if let Some(f_number) = extras["f_number"].as_f64() { camera.f_number = f_number as f32; camera.dof = true; }
+            if let Some(focus_distance) = extras["focus_distance"].as_f64() { camera.focus_distance = focus_distance as f32; }
+            if let Some(shutter_speed) = extras["shutter_speed"].as_f64() { camera.shutter_speed = shutter_speed as f32; }
+            if let Some(iso) = extras["iso"].as_f64() { camera.iso = iso as f32; camera.exposure = true; }
         }
 
-        loaded_ids
+        camera
     }
 
-    pub fn get_color_from_json_object(&self, key: &str, json_obj: &Value, default_data: Vector3::<f32>) -> Vector3::<f32>
+    pub fn apply_camera_json(&self, camera: &Value, cam: &mut Camera)
     {
-        let mut vec = default_data;
+        let default_cam = Camera::new();
 
-        if json_obj.is_null()
+        if let Some(name) = camera["name"].as_str()
         {
-            return vec;
+            cam.name = name.to_string();
         }
 
-        if !json_obj[key].is_null() && !json_obj[key]["r"].is_null() && !json_obj[key]["g"].is_null() && !json_obj[key]["b"].is_null()
+        let pos = self.get_point_from_json_object("pos", &camera, default_cam.eye_pos);
+        let up = self.get_vec_from_json_object("up", &camera, default_cam.up);
+        let dir = self.get_vec_from_json_object("dir", &camera, default_cam.dir);
+
+        cam.eye_pos = pos;
+        cam.dir = dir;
+        cam.up = up;
+
+        let fov =  camera["fov"].as_f64();
+        let z_near =  camera["z_near"].as_f64();
+        let z_far =  camera["z_far"].as_f64();
+
+        if let Some(fov) = fov
         {
-            vec.x = json_obj[key]["r"].as_f64().unwrap() as f32;
-            vec.y = json_obj[key]["g"].as_f64().unwrap() as f32;
-            vec.z = json_obj[key]["b"].as_f64().unwrap() as f32;
+            cam.fov = fov.to_radians() as f32;
         }
 
-        vec
+        if let Some(z_near) = z_near
+        {
+            cam.clipping_near = z_near as f32;
+        }
+
+        if let Some(z_far) = z_far
+        {
+            cam.clipping_far = z_far as f32;
+        }
+
+        if !&camera["projection"].is_null()
+        {
+            let projection = camera["projection"].as_str().unwrap();
+            if let Some(projection) = CameraProjection::from_str(projection)
+            {
+                cam.projection_type = projection;
+            }
+            else
+            {
+                println!("unknown camera projection: {}", projection);
+            }
+        }
+        if !&camera["ortho_scale"].is_null() { cam.ortho_scale = camera["ortho_scale"].as_f64().unwrap() as f32; }
+        if !&camera["fisheye_fov"].is_null() { cam.fisheye_fov = (camera["fisheye_fov"].as_f64().unwrap() as f32).to_radians(); }
+        if !&camera["stereo_eye_distance"].is_null() { cam.stereo_eye_distance = camera["stereo_eye_distance"].as_f64().unwrap() as f32; }
+
+        // physical camera
+        if !&camera["sensor_width"].is_null() { cam.sensor_width = camera["sensor_width"].as_f64().unwrap() as f32; }
+        if !&camera["sensor_height"].is_null() { cam.sensor_height = camera["sensor_height"].as_f64().unwrap() as f32; }
+        if !&camera["focal_length"].is_null() { cam.set_focal_length(camera["focal_length"].as_f64().unwrap() as f32); }
+        if !&camera["dof"].is_null() { cam.dof = camera["dof"].as_bool().unwrap(); }
+        if !&camera["f_number"].is_null() { cam.f_number = camera["f_number"].as_f64().unwrap() as f32; }
+        if !&camera["focus_distance"].is_null() { cam.focus_distance = camera["focus_distance"].as_f64().unwrap() as f32; }
+        if !&camera["shutter_speed"].is_null() { cam.shutter_speed = camera["shutter_speed"].as_f64().unwrap() as f32; }
+        if !&camera["iso"].is_null() { cam.iso = camera["iso"].as_f64().unwrap() as f32; }
+        if !&camera["exposure"].is_null() { cam.exposure = camera["exposure"].as_bool().unwrap(); }
+
+        // motion blur
+        if !&camera["shutter_open"].is_null() { cam.shutter_open = camera["shutter_open"].as_f64().unwrap() as f32; }
+        if !&camera["shutter_close"].is_null() { cam.shutter_close = camera["shutter_close"].as_f64().unwrap() as f32; }
     }
 
     pub fn get_point_from_json_object(&self, key: &str, json_obj: &Value, default_data: Point3::<f32>) -> Point3::<f32>
@@ -719,6 +1516,29 @@ impl Scene
         }
     }
 
+    // accepts a single string or an array of strings
+    pub fn get_string_list_from_json_object(&self, key: &str, json_obj: &Value) -> Vec<String>
+    {
+        let mut list = vec![];
+
+        if let Some(str) = json_obj[key].as_str()
+        {
+            list.push(str.to_string());
+        }
+        else if let Some(array) = json_obj[key].as_array()
+        {
+            for item in array
+            {
+                if let Some(str) = item.as_str()
+                {
+                    list.push(str.to_string());
+                }
+            }
+        }
+
+        list
+    }
+
     pub fn load_gltf(&mut self, path: &str) -> Vec<u32>
     {
         let mut loaded_ids: Vec<u32> = vec![];
@@ -726,6 +1546,7 @@ impl Scene
         let mut double_check_materials: Vec<(Arc<easy_gltf::Material>, u32)> = vec![];
 
         let scenes = easy_gltf::load(path).unwrap();
+        let gltf_extensions = GltfExtensions::load(path);
         for scene in scenes
         {
             // ********** light **********
@@ -746,7 +1567,15 @@ impl Scene
                             color: Vector3::<f32>::new(color.x, color.y, color.z),
                             intensity: intensity / 10.0,
                             max_angle: PI / 2.0, // just some default value - if type is changing
-                            light_type: LightType::Point
+                            light_type: LightType::Point,
+
+                            area_shape: AreaShape::Rect,
+                            width: 1.0,
+                            height: 1.0,
+                            radius: 0.5,
+
+                            include: vec![],
+                            exclude: vec![]
                         }));
                     },
                     Directional { direction, color, intensity, name } =>
@@ -762,7 +1591,15 @@ impl Scene
                             color: Vector3::<f32>::new(color.x, color.y, color.z),
                             intensity: intensity,
                             max_angle: PI / 2.0, // just some default value - if type is changing
-                            light_type: LightType::Directional
+                            light_type: LightType::Directional,
+
+                            area_shape: AreaShape::Rect,
+                            width: 1.0,
+                            height: 1.0,
+                            radius: 0.5,
+
+                            include: vec![],
+                            exclude: vec![]
                         }));
                     },
                     Spot { position, direction, color, intensity, inner_cone_angle, outer_cone_angle, name } =>
@@ -780,44 +1617,30 @@ impl Scene
                             color: Vector3::<f32>::new(color.x, color.y, color.z),
                             intensity: intensity,
                             max_angle: outer_cone_angle,
-                            light_type: LightType::Spot
+                            light_type: LightType::Spot,
+
+                            area_shape: AreaShape::Rect,
+                            width: 1.0,
+                            height: 1.0,
+                            radius: 0.5,
+
+                            include: vec![],
+                            exclude: vec![]
                         }));
                     }
                 };
             }
 
-            // ********** camera **********
-            if scene.cameras.len() > 2
-            {
-                println!("only one camera is supported (using first one)");
-            }
-
-            if scene.cameras.len() > 0
+            // ********** cameras (the first one is active) **********
+            for (i, cam) in scene.cameras.iter().enumerate()
             {
-                let cam = &scene.cameras[0];
-
-                let pos = cam.position();
-                let up = cam.up();
-                let forward = cam.forward();
-
-                self.cam.eye_pos = Point3::<f32>::new(pos.x, pos.y, pos.z);
-                self.cam.dir = Vector3::<f32>::new(-forward.x, -forward.y, -forward.z).normalize();
-                self.cam.up = Vector3::<f32>::new(up.x, up.y, up.z).normalize();
-
-                match cam.projection
+                let mut camera = self.get_camera_from_gltf(cam, &gltf_extensions);
+                if cam.name.is_none()
                 {
-                    Projection::Perspective { yfov, .. } =>
-                    {
-                        self.cam.fov = yfov.0;
-                    },
-                    Projection::Orthographic { .. } =>
-                    {
-                        !todo!()
-                    },
+                    camera.name = format!("camera {}", self.cameras.len() + 1);
                 }
 
-                self.cam.clipping_near = cam.znear;
-                self.cam.clipping_far = cam.zfar;
+                self.add_camera(camera, i == 0);
             }
 
             // ********** objects **********
@@ -911,10 +1734,136 @@ impl Scene
                     material.specular_color = material.base_color * 0.8; // TODO 🤔
 
                     material.alpha = base_color.w;
-                    material.reflectivity = gltf_material.pbr.metallic_factor * 0.5; // do not use full metallic_factor as reflectivity --> otherwise the object will be just complete mirror if metallic is set to 1.0
-                    material.roughness = (1.0 / PI / 2.0) * gltf_material.pbr.roughness_factor;
-                    //materialroughness = gltf_material.pbr.roughness_factor;
-                    //material.reflectivity = gltf_material.pbr.metallic_factor * (1.0 - material.roughness);
+
+                    // metallic-roughness maps directly onto the microfacet model (metallic/roughness maps are scaled by the factors)
+                    material.shading_model = ShadingModel::Pbr;
+                    material.metallic = gltf_material.pbr.metallic_factor;
+                    material.pbr_roughness = gltf_material.pbr.roughness_factor;
+
+                    // transmission (refraction is driven by alpha)
+                    if let Some(extension) = gltf_extensions.get_material_extension(&gltf_material.name, "KHR_materials_transmission")
+                    {
+                        if !extension["transmissionFactor"].is_null()
+                        {
+                            material.alpha *= 1.0 - extension["transmissionFactor"].as_f64().unwrap() as f32;
+                        }
+
+                        // default ior of gltf
+                        material.refraction_index = GLTF_DEFAULT_IOR;
+                    }
+
+                    // index of refraction
+                    if let Some(extension) = gltf_extensions.get_material_extension(&gltf_material.name, "KHR_materials_ior")
+                    {
+                        if !extension["ior"].is_null() { material.refraction_index = extension["ior"].as_f64().unwrap() as f32; }
+                    }
+
+                    // volume absorption (thickness = 0 means thin walled --> no absorption)
+                    if let Some(extension) = gltf_extensions.get_material_extension(&gltf_material.name, "KHR_materials_volume")
+                    {
+                        let mut thickness = 0.0;
+                        if !extension["thicknessFactor"].is_null() { thickness = extension["thicknessFactor"].as_f64().unwrap() as f32; }
+
+                        if thickness > 0.0
+                        {
+                            if !extension["attenuationDistance"].is_null() { material.attenuation_distance = extension["attenuationDistance"].as_f64().unwrap() as f32; }
+
+                            if let Some(color) = extension["attenuationColor"].as_array()
+                            {
+                                if color.len() >= 3
+                                {
+                                    material.transmission_color.x = color[0].as_f64().unwrap() as f32;
+                                    material.transmission_color.y = color[1].as_f64().unwrap() as f32;
+                                    material.transmission_color.z = color[2].as_f64().unwrap() as f32;
+                                }
+                            }
+                        }
+                    }
+
+                    // diffuse transmission is approximated by subsurface scattering (the scattering radius stays at the default)
+                    if let Some(extension) = gltf_extensions.get_material_extension(&gltf_material.name, "KHR_materials_diffuse_transmission")
+                    {
+                        if !extension["diffuseTransmissionFactor"].is_null() { material.subsurface = extension["diffuseTransmissionFactor"].as_f64().unwrap() as f32; }
+
+                        if let Some(color) = extension["diffuseTransmissionColorFactor"].as_array()
+                        {
+                            if color.len() >= 3
+                            {
+                                material.subsurface_color.x = color[0].as_f64().unwrap() as f32;
+                                material.subsurface_color.y = color[1].as_f64().unwrap() as f32;
+                                material.subsurface_color.z = color[2].as_f64().unwrap() as f32;
+                            }
+                        }
+                        else
+                        {
+                            material.subsurface_color = Vector3::<f32>::new(1.0, 1.0, 1.0);
+                        }
+                    }
+
+                    // clearcoat (the clearcoat and clearcoat roughness textures are not supported)
+                    if let Some(extension) = gltf_extensions.get_material_extension(&gltf_material.name, "KHR_materials_clearcoat")
+                    {
+                        if !extension["clearcoatFactor"].is_null() { material.clearcoat = extension["clearcoatFactor"].as_f64().unwrap() as f32; }
+                        if !extension["clearcoatRoughnessFactor"].is_null() { material.clearcoat_roughness = extension["clearcoatRoughnessFactor"].as_f64().unwrap() as f32; }
+
+                        if !extension["clearcoatNormalTexture"].is_null()
+                        {
+                            if !extension["clearcoatNormalTexture"]["scale"].is_null() { material.clearcoat_normal_map_strength = extension["clearcoatNormalTexture"]["scale"].as_f64().unwrap() as f32; }
+
+                            if let Some(img) = gltf_extensions.load_texture(&extension["clearcoatNormalTexture"])
+                            {
+                                material.load_texture_buffer(&img, TextureType::ClearcoatNormal);
+                            }
+                        }
+                    }
+
+                    // sheen
+                    if let Some(extension) = gltf_extensions.get_material_extension(&gltf_material.name, "KHR_materials_sheen")
+                    {
+                        if let Some(color) = extension["sheenColorFactor"].as_array()
+                        {
+                            if color.len() >= 3
+                            {
+                                material.sheen_color.x = color[0].as_f64().unwrap() as f32;
+                                material.sheen_color.y = color[1].as_f64().unwrap() as f32;
+                                material.sheen_color.z = color[2].as_f64().unwrap() as f32;
+                            }
+                        }
+
+                        if !extension["sheenRoughnessFactor"].is_null() { material.sheen_roughness = extension["sheenRoughnessFactor"].as_f64().unwrap() as f32; }
+                    }
+
+                    // anisotropy
+                    if let Some(extension) = gltf_extensions.get_material_extension(&gltf_material.name, "KHR_materials_anisotropy")
+                    {
+                        if !extension["anisotropyStrength"].is_null() { material.anisotropy = extension["anisotropyStrength"].as_f64().unwrap() as f32; }
+                        if !extension["anisotropyRotation"].is_null() { material.anisotropy_rotation = extension["anisotropyRotation"].as_f64().unwrap() as f32; }
+
+                        if !extension["anisotropyTexture"].is_null()
+                        {
+                            if let Some(img) = gltf_extensions.load_texture(&extension["anisotropyTexture"])
+                            {
+                                material.load_texture_buffer(&img, TextureType::Anisotropy);
+                            }
+                        }
+                    }
+
+                    // thin-film iridescence (the iridescence factor texture is not supported)
+                    if let Some(extension) = gltf_extensions.get_material_extension(&gltf_material.name, "KHR_materials_iridescence")
+                    {
+                        if !extension["iridescenceFactor"].is_null() { material.iridescence = extension["iridescenceFactor"].as_f64().unwrap() as f32; }
+                        if !extension["iridescenceIor"].is_null() { material.iridescence_ior = extension["iridescenceIor"].as_f64().unwrap() as f32; }
+                        if !extension["iridescenceThicknessMinimum"].is_null() { material.iridescence_thickness_min = extension["iridescenceThicknessMinimum"].as_f64().unwrap() as f32; }
+                        if !extension["iridescenceThicknessMaximum"].is_null() { material.iridescence_thickness_max = extension["iridescenceThicknessMaximum"].as_f64().unwrap() as f32; }
+
+                        if !extension["iridescenceThicknessTexture"].is_null()
+                        {
+                            if let Some(img) = gltf_extensions.load_texture(&extension["iridescenceThicknessTexture"])
+                            {
+                                material.load_texture_buffer(&img, TextureType::IridescenceThickness);
+                            }
+                        }
+                    }
 
                     // base map
                     if gltf_material.pbr.base_color_texture.is_some()
@@ -937,14 +1886,22 @@ impl Scene
                         material.load_texture_buffer(&img, TextureType::Reflectivity);
                     }
 
-                    // emissive
+                    // emissive (emissive materials are used as light sources)
+                    let mut emissive_strength = 1.0;
+                    if let Some(extension) = gltf_extensions.get_material_extension(&gltf_material.name, "KHR_materials_emissive_strength")
+                    {
+                        if !extension["emissiveStrength"].is_null() { emissive_strength = extension["emissiveStrength"].as_f64().unwrap() as f32; }
+                    }
+
+                    material.ambient_color.x = gltf_material.emissive.factor.x * emissive_strength;
+                    material.ambient_color.y = gltf_material.emissive.factor.y * emissive_strength;
+                    material.ambient_color.z = gltf_material.emissive.factor.z * emissive_strength;
+                    material.emissive = max_component(material.ambient_color) > 0.0;
+
                     if gltf_material.emissive.texture.is_some()
                     {
                         let img = self.get_dyn_image_from_gltf_material(&gltf_material, TextureType::AmbientEmissive);
                         material.load_texture_buffer(&img, TextureType::AmbientEmissive);
-                        material.ambient_color.x = gltf_material.emissive.factor.x;
-                        material.ambient_color.y = gltf_material.emissive.factor.y;
-                        material.ambient_color.z = gltf_material.emissive.factor.z;
                     }
 
                     // roughness map
@@ -1379,7 +2336,15 @@ impl Scene
             color: color,
             intensity: intensity,
             max_angle: max_angle,
-            light_type: light_type
+            light_type: light_type,
+
+            area_shape: AreaShape::Rect,
+            width: 1.0,
+            height: 1.0,
+            radius: 0.5,
+
+            include: vec![],
+            exclude: vec![]
         }));
     }
 
@@ -1396,7 +2361,15 @@ impl Scene
             color: Vector3::<f32>::new(1.0, 1.0, 1.0),
             intensity: 200.0,
             max_angle: PI / 2.0,
-            light_type: LightType::Point
+            light_type: LightType::Point,
+
+            area_shape: AreaShape::Rect,
+            width: 1.0,
+            height: 1.0,
+            radius: 0.5,
+
+            include: vec![],
+            exclude: vec![]
         }));
     }
 
@@ -1572,6 +2545,62 @@ impl Scene
         basic.apply_translation(Vector3::<f32>::new(0.0, y_pos, 0.0));
     }
 
+    // bakes the sky into the environment map and updates the sun light
+    pub fn apply_sky(&mut self)
+    {
+        if !self.sky.enabled
+        {
+            if self.environment.path == SKY_ENVIRONMENT_NAME
+            {
+                self.environment.unload();
+            }
+
+            if let Some(sun_light_id) = self.sky.sun_light_id
+            {
+                if let Some(sun) = self.get_light_by_id_mut(sun_light_id)
+                {
+                    sun.enabled = false;
+                }
+            }
+
+            return;
+        }
+
+        // ********** sky **********
+        let (width, height, pixels) = self.sky.bake();
+
+        self.environment.load_from_pixels(SKY_ENVIRONMENT_NAME, width, height, pixels);
+        self.environment.enabled = true;
+        self.environment.intensity = 1.0;
+        self.environment.rotation = 0.0;
+
+        // ********** sun **********
+        let sun_dir = -self.sky.get_sun_direction();
+        let sun_color = self.sky.get_sun_color();
+        let sun_intensity = self.sky.sun_intensity;
+
+        let mut sun_exists = false;
+        if let Some(sun_light_id) = self.sky.sun_light_id
+        {
+            if let Some(sun) = self.get_light_by_id_mut(sun_light_id)
+            {
+                sun.enabled = true;
+                sun.dir = sun_dir;
+                sun.color = sun_color;
+                sun.intensity = sun_intensity;
+                sun.light_type = LightType::Directional;
+
+                sun_exists = true;
+            }
+        }
+
+        if !sun_exists
+        {
+            self.add_light("sun".to_string(), Point3::<f32>::new(0.0, 0.0, 0.0), sun_dir, sun_color, sun_intensity, PI / 2.0, LightType::Directional);
+            self.sky.sun_light_id = Some(self.item_id);
+        }
+    }
+
     pub fn add_environment_sphere(&mut self)
     {
         self.load_json("scene/environment.json");
@@ -1673,6 +2702,8 @@ impl Scene
 
     pub fn update(&mut self)
     {
+        self.update_motion_blur();
+
         for item in & mut self.items
         {
             item.update();
@@ -1685,6 +2716,30 @@ impl Scene
         BVHNode::build(&mut self.items, &indices, &mut nodes, 0, 0);
 
         self.bvh.nodes = nodes;
+
+        //update emissive shapes (used as lights)
+        self.emitters.build(&self.items);
+
+        //update scene bounds (the global medium is limited to them)
+        self.b_box = Aabb::new_invalid();
+        for item in &self.items
+        {
+            let b_box = item.get_basic().b_box;
+
+            if b_box.mins.x > b_box.maxs.x
+            {
+                continue;
+            }
+
+            for trans in &item.get_basic().get_bounds_trans()
+            {
+                for vert in &b_box.vertices()
+                {
+                    let transformed = trans * vert.to_homogeneous();
+                    self.b_box.take_point(Point3::<f32>::from(transformed.xyz()));
+                }
+            }
+        }
     }
 
     pub fn frame_exists(&self, frame: u64) -> bool
@@ -1709,9 +2764,64 @@ impl Scene
             }
         }
 
+        //cameras can be animated by their name as well
+        let cam_trans = self.animation.get_trans_for_frame(frame, self.cam.name.to_string());
+        if let Some(cam_trans) = cam_trans
+        {
+            self.cam.apply_animation_trans(&cam_trans);
+        }
+
+        for cam in &mut self.cameras
+        {
+            let cam_trans = self.animation.get_trans_for_frame(frame, cam.name.to_string());
+            if let Some(cam_trans) = cam_trans
+            {
+                cam.apply_animation_trans(&cam_trans);
+            }
+        }
+
+        self.frame = frame;
+        self.update_motion_blur();
+
         true
     }
 
+    // movement of the items and the camera during the shutter interval of the active camera
+    pub fn update_motion_blur(&mut self)
+    {
+        let has_animation = self.animation.has_animation();
+
+        let frame = self.frame;
+        let shutter_open = self.cam.shutter_open;
+        let shutter_close = self.cam.shutter_close;
+
+        for item in &mut self.items
+        {
+            let mut motion = vec![];
+            if has_animation
+            {
+                motion = self.animation.get_motion_for_frame(frame, shutter_open, shutter_close, &item.get_basic().name);
+            }
+
+            item.get_basic_mut().apply_motion(motion);
+        }
+
+        self.cam.motion = vec![];
+
+        if has_animation
+        {
+            let cam_trans = self.animation.get_trans_for_frame(frame, self.cam.name.to_string());
+            if let Some(cam_trans) = cam_trans
+            {
+                //relative to the pose of the frame
+                let cam_trans_inverse = cam_trans.try_inverse().unwrap_or(Matrix4::<f32>::identity());
+
+                let motion = self.animation.get_motion_for_frame(frame, shutter_open, shutter_close, &self.cam.name);
+                self.cam.motion = motion.iter().map(|trans| trans * cam_trans_inverse).collect();
+            }
+        }
+    }
+
     pub fn get_possible_hits_by_ray(&self, ray: &Ray) -> Vec<&ScemeItem>
     {
         let origin = bvh::Point3::new(ray.origin.x, ray.origin.y, ray.origin.z);
@@ -1721,6 +2831,35 @@ impl Scene
         self.bvh.traverse(&ray, &self.items)
     }
 
+    // number of bvh nodes visited by the traversal (debug render mode)
+    pub fn get_bvh_node_visits(&self, ray: &Ray) -> usize
+    {
+        if self.bvh.nodes.is_empty()
+        {
+            return 0;
+        }
+
+        let origin = bvh::Point3::new(ray.origin.x, ray.origin.y, ray.origin.z);
+        let direction = bvh::Vector3::new(ray.dir.x, ray.dir.y, ray.dir.z);
+        let ray = bvh::ray::Ray::new(origin, direction);
+
+        let mut visits = 0;
+        let mut stack = vec![0];
+
+        while let Some(node_index) = stack.pop()
+        {
+            visits += 1;
+
+            if let BVHNode::Node { child_l_index, ref child_l_aabb, child_r_index, ref child_r_aabb, .. } = self.bvh.nodes[node_index]
+            {
+                if ray.intersects_aabb(child_l_aabb) { stack.push(child_l_index); }
+                if ray.intersects_aabb(child_r_aabb) { stack.push(child_r_index); }
+            }
+        }
+
+        visits
+    }
+
     pub fn get_by_name_mut(&mut self, name: &str) -> Option<&mut ScemeItem>
     {
         for item in & mut self.items
@@ -1825,8 +2964,21 @@ impl Scene
             let light_type = light.light_type;
 
             println!(" - {:?}: pos: {:?}, dir: {:?}, color: {:?}, intensity: {}, max_angle: {}", light_type, pos, dir, color, intensity, max_angle);
+
+            if light.is_linked()
+            {
+                println!("   linking: include: {:?}, exclude: {:?}", light.include, light.exclude);
+            }
         }
 
+        println!("");
+        println!("environment:");
+        println!("==========");
+        println!("path: {}", self.environment.path);
+        println!("enabled: {}", self.environment.enabled);
+        println!("intensity: {}", self.environment.intensity);
+        println!("rotation: {}", self.environment.rotation.to_degrees());
+
         println!("");
         println!("scene:");
         println!("==========");
@@ -1,6 +1,7 @@
 use nalgebra::{Vector3, Point3, Point2, Isometry3};
 
 use parry3d::query::{Ray, RayCast};
+use parry3d::query::visitors::RayIntersectionsVisitor;
 use parry3d::shape::{TriMesh, FeatureId};
 
 use crate::shape::{Shape, ShapeBasics, TextureType};
@@ -80,7 +81,7 @@ impl Shape for Mesh
             {
                 let hit = ray.origin + (ray.dir * res.toi);
                 normal = self.get_normal(hit, face_id);
-                normal = (self.basic.trans * normal.to_homogeneous()).xyz().normalize();
+                normal = (self.basic.get_trans() * normal.to_homogeneous()).xyz().normalize();
 
                 if self.mesh.is_backface(res.feature)
                 {
@@ -89,7 +90,7 @@ impl Shape for Mesh
             }
             else
             {
-                normal = (self.basic.trans * res.normal.to_homogeneous()).xyz().normalize();
+                normal = (self.basic.get_trans() * res.normal.to_homogeneous()).xyz().normalize();
             }
 
             if self.get_basic().flip_normals
@@ -108,7 +109,7 @@ impl Shape for Mesh
         // https://answers.unity.com/questions/383804/calculate-uv-coordinates-of-3d-point-on-plane-of-m.html
 
         //transform hit to local coords
-        let hit_pos_local = self.basic.tran_inverse * hit.to_homogeneous();
+        let hit_pos_local = self.basic.get_inverse_trans() * hit.to_homogeneous();
         let hit_pos_local = Point3::<f32>::from_homogeneous(hit_pos_local).unwrap();
 
         let f_id = (face_id % self.mesh.indices().len() as u32) as usize;
@@ -159,6 +160,129 @@ impl Shape for Mesh
 
         Point2::<f32>::new(uv.x, -uv.y)
     }
+
+    fn get_triangles(&self) -> Vec<[Point3<f32>; 3]>
+    {
+        //transformation of the frame (emissive shapes are not sampled along their motion)
+        let trans = self.basic.trans;
+        let vertices = self.mesh.vertices();
+
+        let mut triangles = vec![];
+
+        for face in self.mesh.indices()
+        {
+            let a = Point3::<f32>::from_homogeneous(trans * vertices[face[0] as usize].to_homogeneous()).unwrap();
+            let b = Point3::<f32>::from_homogeneous(trans * vertices[face[1] as usize].to_homogeneous()).unwrap();
+            let c = Point3::<f32>::from_homogeneous(trans * vertices[face[2] as usize].to_homogeneous()).unwrap();
+
+            triangles.push([a, b, c]);
+        }
+
+        triangles
+    }
+
+    fn get_geometric_normal(&self, _hit: Point3<f32>, face_id: u32) -> Option<Vector3<f32>>
+    {
+        let f_id = (face_id % self.mesh.indices().len() as u32) as usize;
+        let face = self.mesh.indices()[f_id];
+
+        let a = self.mesh.vertices()[face[0] as usize];
+        let b = self.mesh.vertices()[face[1] as usize];
+        let c = self.mesh.vertices()[face[2] as usize];
+
+        let mut normal = (b - a).cross(&(c - a));
+        normal = (self.basic.get_trans() * normal.to_homogeneous()).xyz().normalize();
+
+        if self.get_basic().flip_normals
+        {
+            normal = -normal;
+        }
+
+        Some(normal)
+    }
+
+    fn get_tangent(&self, _hit: Point3<f32>, face_id: u32) -> Option<Vector3<f32>>
+    {
+        let f_id = (face_id % self.mesh.indices().len() as u32) as usize;
+
+        if self.uv_indices.len() <= f_id
+        {
+            return None;
+        }
+
+        let face = self.mesh.indices()[f_id];
+        let uv_face = self.uv_indices[f_id];
+
+        let a = self.mesh.vertices()[face[0] as usize];
+        let b = self.mesh.vertices()[face[1] as usize];
+        let c = self.mesh.vertices()[face[2] as usize];
+
+        let a_t = self.uvs[uv_face[0] as usize];
+        let b_t = self.uvs[uv_face[1] as usize];
+        let c_t = self.uvs[uv_face[2] as usize];
+
+        //dP/du from the uv derivatives of the triangle
+        let e1 = b - a;
+        let e2 = c - a;
+        let duv1 = b_t - a_t;
+        let duv2 = c_t - a_t;
+
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        if det.abs() <= 0.00000001
+        {
+            return None;
+        }
+
+        let tangent = (e1 * duv2.y - e2 * duv1.y) / det;
+        let tangent = (self.basic.get_trans() * tangent.to_homogeneous()).xyz();
+
+        if tangent.magnitude_squared() <= 0.00000001
+        {
+            return None;
+        }
+
+        Some(tangent.normalize())
+    }
+
+    fn get_primitive_tests(&self, ray: &Ray) -> usize
+    {
+        let ray_inverse = self.basic.get_inverse_ray(ray);
+
+        //triangles with a bounding box hit in the mesh bvh
+        let mut tests = 0;
+        {
+            let mut count = |_: &u32| { tests += 1; true };
+            let mut visitor = RayIntersectionsVisitor::new(&ray_inverse, std::f32::MAX, &mut count);
+            self.mesh.qbvh().traverse_depth_first(&mut visitor);
+        }
+
+        tests
+    }
+
+    fn get_barycentrics(&self, hit: Point3<f32>, face_id: u32) -> Option<Vector3<f32>>
+    {
+        //transform hit to local coords
+        let hit_pos_local = self.basic.get_inverse_trans() * hit.to_homogeneous();
+        let hit_pos_local = Point3::<f32>::from_homogeneous(hit_pos_local).unwrap();
+
+        let f_id = (face_id % self.mesh.indices().len() as u32) as usize;
+        let face = self.mesh.indices()[f_id];
+
+        let a = self.mesh.vertices()[face[0] as usize];
+        let b = self.mesh.vertices()[face[1] as usize];
+        let c = self.mesh.vertices()[face[2] as usize];
+
+        let f1 = a - hit_pos_local;
+        let f2 = b - hit_pos_local;
+        let f3 = c - hit_pos_local;
+
+        let area = (a-b).cross(&(a-c)).magnitude();
+        let a1 = f2.cross(&f3).magnitude() / area;
+        let a2 = f3.cross(&f1).magnitude() / area;
+        let a3 = f1.cross(&f2).magnitude() / area;
+
+        Some(Vector3::<f32>::new(a1, a2, a3))
+    }
 }
 
 impl Mesh
@@ -207,7 +331,7 @@ impl Mesh
         // https://answers.unity.com/questions/383804/calculate-uv-coordinates-of-3d-point-on-plane-of-m.html
 
         //transform hit to local coords
-        let hit_pos_local = self.basic.tran_inverse * hit.to_homogeneous();
+        let hit_pos_local = self.basic.get_inverse_trans() * hit.to_homogeneous();
         let hit_pos_local = Point3::<f32>::from_homogeneous(hit_pos_local).unwrap();
 
         let f_id = (face_id % self.mesh.indices().len() as u32) as usize;
@@ -9,10 +9,16 @@ use parry3d::bounding_volume::Aabb;
 
 use image::{DynamicImage, GenericImageView, Pixel};
 
+use crate::animation::{get_sample_time, interpolate_motion};
 use crate::helper::{approx_equal, interpolate_vec4};
+use crate::medium::Medium;
+use crate::raytracing::RayType;
+use crate::spectrum::{DispersionModel, SpectralSample, cauchy_refraction_index, sellmeier_refraction_index};
 
 pub type MaterialItem = Arc<RwLock<Box<Material>>>;
 
+const MIN_TRANSMISSION_COLOR: f32 = 0.0001;
+
 pub mod sphere;
 pub mod mesh;
 
@@ -30,6 +36,42 @@ pub trait Shape
 
     fn get_uv(&self, hit: Point3<f32>, face_id: u32) -> Point2<f32>;
 
+    // world space triangles (index = face id) -- used to sample emissive shapes as lights
+    fn get_triangles(&self) -> Vec<[Point3<f32>; 3]>
+    {
+        vec![]
+    }
+
+    // world space center and radius of analytic spheres -- used to sample emissive spheres as lights
+    fn get_sphere(&self) -> Option<(Point3<f32>, f32)>
+    {
+        None
+    }
+
+    // face normal by winding order without smooth shading or normal mapping (debug render mode)
+    fn get_geometric_normal(&self, _hit: Point3<f32>, _face_id: u32) -> Option<Vector3<f32>>
+    {
+        None
+    }
+
+    // world space direction of increasing u (tangent frame of anisotropic materials)
+    fn get_tangent(&self, _hit: Point3<f32>, _face_id: u32) -> Option<Vector3<f32>>
+    {
+        None
+    }
+
+    // exact primitive (triangle) tests of an intersection (debug render mode)
+    fn get_primitive_tests(&self, _ray: &Ray) -> usize
+    {
+        1
+    }
+
+    // barycentric coordinates of the hit on the face (debug render mode)
+    fn get_barycentrics(&self, _hit: Point3<f32>, _face_id: u32) -> Option<Vector3<f32>>
+    {
+        None
+    }
+
     fn update(&mut self)
     {
         self.calc_bbox();
@@ -52,22 +94,24 @@ impl Bounded for Box<(dyn Shape + Send + Sync + 'static)>
         let aabb = self.get_basic().b_box;
         let verts = aabb.vertices();
 
-        let trans = self.get_basic().trans;
+        let mut min = Point3::<f32>::new(std::f32::MAX, std::f32::MAX, std::f32::MAX);
+        let mut max = Point3::<f32>::new(-std::f32::MAX, -std::f32::MAX, -std::f32::MAX);
 
-        let mut min = verts[0];
-        let mut max = verts[0];
-
-        for vert in &verts
+        //the bounds are covering the whole motion (motion blur)
+        for trans in &self.get_basic().get_bounds_trans()
         {
-            let transformed = trans * vert.to_homogeneous();
+            for vert in &verts
+            {
+                let transformed = trans * vert.to_homogeneous();
 
-            min.x = min.x.min(transformed.x);
-            min.y = min.y.min(transformed.y);
-            min.z = min.z.min(transformed.z);
+                min.x = min.x.min(transformed.x);
+                min.y = min.y.min(transformed.y);
+                min.z = min.z.min(transformed.z);
 
-            max.x = max.x.max(transformed.x);
-            max.y = max.y.max(transformed.y);
-            max.z = max.z.max(transformed.z);
+                max.x = max.x.max(transformed.x);
+                max.y = max.y.max(transformed.y);
+                max.z = max.z.max(transformed.z);
+            }
         }
 
         let min = bvh::Point3::new(min.x, min.y, min.z);
@@ -100,6 +144,7 @@ pub struct Material
     pub ambient_color: Vector3<f32>,
     pub base_color: Vector3<f32>,
     pub specular_color: Vector3<f32>,
+    pub emissive: bool, //the ambient color is emitted light (sampled as light source)
 
     pub texture_ambient: DynamicImage,
     pub texture_base: DynamicImage,
@@ -109,6 +154,9 @@ pub struct Material
     pub texture_roughness: DynamicImage,
     pub texture_ambient_occlusion: DynamicImage,
     pub texture_reflectivity: DynamicImage,
+    pub texture_clearcoat_normal: DynamicImage,
+    pub texture_iridescence_thickness: DynamicImage,
+    pub texture_anisotropy: DynamicImage,
 
     pub texture_filtering_nearest: bool,
 
@@ -117,6 +165,21 @@ pub struct Material
     pub reflectivity: f32,
     pub refraction_index: f32,
 
+    //wavelength dependent refraction index (spectral mode only)
+    pub dispersion_model: DispersionModel,
+    pub cauchy_coefficients: Vector3<f32>, //A, B, C (λ in micrometer)
+    pub sellmeier_b: Vector3<f32>,
+    pub sellmeier_c: Vector3<f32>, //in micrometer²
+
+    pub transmission_color: Vector3<f32>, //color after travelling the attenuation distance inside of the object (beer-lambert)
+    pub attenuation_distance: f32, //0 = no absorption
+
+    //subsurface scattering (random walk)
+    pub subsurface: f32, //0 = off, 1 = full subsurface (blends with the surface bsdf)
+    pub subsurface_color: Vector3<f32>, //multiple scattering albedo
+    pub subsurface_radius: Vector3<f32>, //mean free path per color channel
+    pub subsurface_scale: f32,
+
     pub normal_map_strength: f32,
 
     pub cast_shadow: bool,
@@ -127,6 +190,27 @@ pub struct Material
 
     pub roughness: f32, //degree in rad (max PI/2)
 
+    pub shading_model: ShadingModel,
+    pub metallic: f32,
+    pub pbr_roughness: f32, //perceptual roughness (0..1) of the microfacet model
+
+    //layers of the pbr shading model
+    pub clearcoat: f32, //0 = off
+    pub clearcoat_roughness: f32,
+    pub clearcoat_normal_map_strength: f32,
+    pub sheen_color: Vector3<f32>, //black = off
+    pub sheen_roughness: f32,
+    pub anisotropy: f32, //0 = isotropic, 1 = fully stretched along the tangent
+    pub anisotropy_rotation: f32, //rotation of the tangent in rad
+
+    //thin-film interference (modulates the specular fresnel term)
+    pub iridescence: f32, //0 = off
+    pub iridescence_ior: f32,
+    pub iridescence_thickness_min: f32, //nm (used with the thickness texture)
+    pub iridescence_thickness_max: f32, //nm
+
+    pub medium: Medium, //interior medium (volumetric)
+
     pub smooth_shading: bool,
 
     pub reflection_only: bool,
@@ -145,6 +229,7 @@ impl Material
             ambient_color: Vector3::<f32>::new(0.0, 0.0, 0.0),
             base_color: Vector3::<f32>::new(1.0, 1.0, 1.0),
             specular_color: Vector3::<f32>::new(0.8, 0.8, 0.8),
+            emissive: false,
 
             texture_ambient: DynamicImage::new_rgb8(0,0),
             texture_base: DynamicImage::new_rgb8(0,0),
@@ -154,6 +239,9 @@ impl Material
             texture_roughness: DynamicImage::new_rgb8(0,0),
             texture_ambient_occlusion: DynamicImage::new_rgb8(0,0),
             texture_reflectivity: DynamicImage::new_rgb8(0,0),
+            texture_clearcoat_normal: DynamicImage::new_rgb8(0,0),
+            texture_iridescence_thickness: DynamicImage::new_rgb8(0,0),
+            texture_anisotropy: DynamicImage::new_rgb8(0,0),
 
             texture_filtering_nearest: false,
 
@@ -162,6 +250,21 @@ impl Material
             reflectivity: 0.0,
             refraction_index: 1.0,
 
+            //borosilicate crown glass (BK7)
+            dispersion_model: DispersionModel::None,
+            cauchy_coefficients: Vector3::<f32>::new(1.5046, 0.0042, 0.0),
+            sellmeier_b: Vector3::<f32>::new(1.03961212, 0.231792344, 1.01046945),
+            sellmeier_c: Vector3::<f32>::new(0.00600069867, 0.0200179144, 103.560653),
+
+            transmission_color: Vector3::<f32>::new(1.0, 1.0, 1.0),
+            attenuation_distance: 0.0,
+
+            //skin like scattering radius
+            subsurface: 0.0,
+            subsurface_color: Vector3::<f32>::new(0.9, 0.6, 0.5),
+            subsurface_radius: Vector3::<f32>::new(1.0, 0.2, 0.1),
+            subsurface_scale: 0.05,
+
             normal_map_strength: 1.0,
 
             cast_shadow: true,
@@ -170,6 +273,25 @@ impl Material
 
             roughness: 0.0,
 
+            shading_model: ShadingModel::Phong,
+            metallic: 0.0,
+            pbr_roughness: 0.5,
+
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            clearcoat_normal_map_strength: 1.0,
+            sheen_color: Vector3::<f32>::new(0.0, 0.0, 0.0),
+            sheen_roughness: 0.3,
+            anisotropy: 0.0,
+            anisotropy_rotation: 0.0,
+
+            iridescence: 0.0,
+            iridescence_ior: 1.3,
+            iridescence_thickness_min: 100.0,
+            iridescence_thickness_max: 400.0,
+
+            medium: Medium::new(),
+
             monte_carlo: true,
 
             smooth_shading: true,
@@ -225,14 +347,55 @@ impl Material
         if !approx_equal(default_material.reflectivity, new_mat.reflectivity) { self.reflectivity = new_mat.reflectivity; }
         if !approx_equal(default_material.refraction_index, new_mat.refraction_index) { self.refraction_index = new_mat.refraction_index; }
 
+        if default_material.dispersion_model != new_mat.dispersion_model { self.dispersion_model = new_mat.dispersion_model; }
+        if default_material.cauchy_coefficients != new_mat.cauchy_coefficients { self.cauchy_coefficients = new_mat.cauchy_coefficients; }
+        if default_material.sellmeier_b != new_mat.sellmeier_b { self.sellmeier_b = new_mat.sellmeier_b; }
+        if default_material.sellmeier_c != new_mat.sellmeier_c { self.sellmeier_c = new_mat.sellmeier_c; }
+
+        if
+            !approx_equal(default_material.transmission_color.x, new_mat.transmission_color.x)
+            ||
+            !approx_equal(default_material.transmission_color.y, new_mat.transmission_color.y)
+            ||
+            !approx_equal(default_material.transmission_color.z, new_mat.transmission_color.z)
+        {
+            self.transmission_color = new_mat.transmission_color;
+        }
+        if !approx_equal(default_material.attenuation_distance, new_mat.attenuation_distance) { self.attenuation_distance = new_mat.attenuation_distance; }
+
+        if !approx_equal(default_material.subsurface, new_mat.subsurface) { self.subsurface = new_mat.subsurface; }
+        if default_material.subsurface_color != new_mat.subsurface_color { self.subsurface_color = new_mat.subsurface_color; }
+        if default_material.subsurface_radius != new_mat.subsurface_radius { self.subsurface_radius = new_mat.subsurface_radius; }
+        if !approx_equal(default_material.subsurface_scale, new_mat.subsurface_scale) { self.subsurface_scale = new_mat.subsurface_scale; }
+
         if !approx_equal(default_material.normal_map_strength, new_mat.normal_map_strength) { self.normal_map_strength = new_mat.normal_map_strength; }
 
+        if default_material.emissive != new_mat.emissive { self.emissive = new_mat.emissive; }
         if default_material.cast_shadow != new_mat.cast_shadow { self.cast_shadow = new_mat.cast_shadow; }
         if default_material.receive_shadow != new_mat.receive_shadow { self.receive_shadow = new_mat.receive_shadow; }
         if !approx_equal(default_material.shadow_softness, new_mat.shadow_softness) { self.shadow_softness = new_mat.shadow_softness; }
 
         if !approx_equal(default_material.roughness, new_mat.roughness) { self.roughness = new_mat.roughness; }
 
+        if default_material.shading_model != new_mat.shading_model { self.shading_model = new_mat.shading_model; }
+        if !approx_equal(default_material.metallic, new_mat.metallic) { self.metallic = new_mat.metallic; }
+        if !approx_equal(default_material.pbr_roughness, new_mat.pbr_roughness) { self.pbr_roughness = new_mat.pbr_roughness; }
+
+        if !approx_equal(default_material.clearcoat, new_mat.clearcoat) { self.clearcoat = new_mat.clearcoat; }
+        if !approx_equal(default_material.clearcoat_roughness, new_mat.clearcoat_roughness) { self.clearcoat_roughness = new_mat.clearcoat_roughness; }
+        if !approx_equal(default_material.clearcoat_normal_map_strength, new_mat.clearcoat_normal_map_strength) { self.clearcoat_normal_map_strength = new_mat.clearcoat_normal_map_strength; }
+        if default_material.sheen_color != new_mat.sheen_color { self.sheen_color = new_mat.sheen_color; }
+        if !approx_equal(default_material.sheen_roughness, new_mat.sheen_roughness) { self.sheen_roughness = new_mat.sheen_roughness; }
+        if !approx_equal(default_material.anisotropy, new_mat.anisotropy) { self.anisotropy = new_mat.anisotropy; }
+        if !approx_equal(default_material.anisotropy_rotation, new_mat.anisotropy_rotation) { self.anisotropy_rotation = new_mat.anisotropy_rotation; }
+
+        if !approx_equal(default_material.iridescence, new_mat.iridescence) { self.iridescence = new_mat.iridescence; }
+        if !approx_equal(default_material.iridescence_ior, new_mat.iridescence_ior) { self.iridescence_ior = new_mat.iridescence_ior; }
+        if !approx_equal(default_material.iridescence_thickness_min, new_mat.iridescence_thickness_min) { self.iridescence_thickness_min = new_mat.iridescence_thickness_min; }
+        if !approx_equal(default_material.iridescence_thickness_max, new_mat.iridescence_thickness_max) { self.iridescence_thickness_max = new_mat.iridescence_thickness_max; }
+
+        if default_material.medium != new_mat.medium { self.medium = new_mat.medium.clone(); }
+
         if default_material.monte_carlo != new_mat.monte_carlo { self.monte_carlo = new_mat.monte_carlo; }
 
         if default_material.smooth_shading != new_mat.smooth_shading { self.smooth_shading = new_mat.smooth_shading; }
@@ -241,6 +404,62 @@ impl Material
         if default_material.backface_cullig != new_mat.backface_cullig { self.backface_cullig = new_mat.backface_cullig; }
     }
 
+    // bending the light on transmission (an ior of 1 is just alpha based transparency)
+    pub fn is_refractive(&self) -> bool
+    {
+        !approx_equal(self.refraction_index, 1.0) || self.dispersion_model != DispersionModel::None
+    }
+
+    // refraction index for the wavelength of the sample (the wavelength is sampled on the first dispersive hit)
+    pub fn get_refraction_index(&self, spectral: &SpectralSample) -> f32
+    {
+        if self.dispersion_model == DispersionModel::None
+        {
+            return self.refraction_index;
+        }
+
+        if let Some(wavelength) = spectral.get_wavelength()
+        {
+            match self.dispersion_model
+            {
+                DispersionModel::Cauchy => return cauchy_refraction_index(wavelength, &self.cauchy_coefficients),
+                DispersionModel::Sellmeier => return sellmeier_refraction_index(wavelength, &self.sellmeier_b, &self.sellmeier_c),
+                DispersionModel::None => {}
+            }
+        }
+
+        self.refraction_index
+    }
+
+    // absorption coefficient based on the transmission color and the attenuation distance
+    pub fn get_absorption(&self) -> Vector3<f32>
+    {
+        if self.attenuation_distance <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        self.transmission_color.map(|c| -c.clamp(MIN_TRANSMISSION_COLOR, 1.0).ln() / self.attenuation_distance)
+    }
+
+    // the interior medium overrules the beer-lambert absorption
+    pub fn get_interior_medium(&self) -> Option<Medium>
+    {
+        if self.medium.is_active()
+        {
+            return Some(self.medium.clone());
+        }
+
+        let absorption = self.get_absorption();
+
+        if absorption.max() <= 0.0
+        {
+            return None;
+        }
+
+        Some(Medium::new_homogeneous(absorption, Vector3::<f32>::zeros(), 0.0))
+    }
+
     pub fn apply_diff(&mut self, new_mat: &Material)
     {
         // ********** default settings **********
@@ -296,6 +515,24 @@ impl Material
         {
             self.texture_reflectivity = new_mat.texture_reflectivity.clone();
         }
+
+        // clearcoat normal
+        if default_material.texture_clearcoat_normal != new_mat.texture_clearcoat_normal
+        {
+            self.texture_clearcoat_normal = new_mat.texture_clearcoat_normal.clone();
+        }
+
+        // iridescence thickness
+        if default_material.texture_iridescence_thickness != new_mat.texture_iridescence_thickness
+        {
+            self.texture_iridescence_thickness = new_mat.texture_iridescence_thickness.clone();
+        }
+
+        // anisotropy
+        if default_material.texture_anisotropy != new_mat.texture_anisotropy
+        {
+            self.texture_anisotropy = new_mat.texture_anisotropy.clone();
+        }
     }
 
     pub fn print(&self)
@@ -303,6 +540,7 @@ impl Material
         println!("ambient_color: {:?}", self.ambient_color);
         println!("base_color: {:?}", self.base_color);
         println!("specular_color: {:?}", self.specular_color);
+        println!("emissive: {:?}", self.emissive);
 
         println!("texture_ambient: {:?}", self.texture_ambient.width() > 0);
         println!("texture_base: {:?}", self.texture_base.width() > 0);
@@ -312,6 +550,9 @@ impl Material
         println!("texture_roughness: {:?}", self.texture_roughness.width() > 0);
         println!("texture_ambient_occlusion: {:?}", self.texture_ambient_occlusion.width() > 0);
         println!("texture_reflectivity: {:?}", self.texture_reflectivity.width() > 0);
+        println!("texture_clearcoat_normal: {:?}", self.texture_clearcoat_normal.width() > 0);
+        println!("texture_iridescence_thickness: {:?}", self.texture_iridescence_thickness.width() > 0);
+        println!("texture_anisotropy: {:?}", self.texture_anisotropy.width() > 0);
 
         println!("texture_filtering_nearest: {:?}", self.texture_filtering_nearest);
 
@@ -320,6 +561,19 @@ impl Material
         println!("reflectivity: {:?}", self.reflectivity);
         println!("refraction_index: {:?}", self.refraction_index);
 
+        println!("dispersion_model: {:?}", self.dispersion_model);
+        println!("cauchy_coefficients: {:?}", self.cauchy_coefficients);
+        println!("sellmeier_b: {:?}", self.sellmeier_b);
+        println!("sellmeier_c: {:?}", self.sellmeier_c);
+
+        println!("transmission_color: {:?}", self.transmission_color);
+        println!("attenuation_distance: {:?}", self.attenuation_distance);
+
+        println!("subsurface: {:?}", self.subsurface);
+        println!("subsurface_color: {:?}", self.subsurface_color);
+        println!("subsurface_radius: {:?}", self.subsurface_radius);
+        println!("subsurface_scale: {:?}", self.subsurface_scale);
+
         println!("normal_map_strength: {:?}", self.normal_map_strength);
 
         println!("cast_shadow: {:?}", self.cast_shadow);
@@ -328,6 +582,25 @@ impl Material
 
         println!("roughness: {:?}", self.roughness);
 
+        println!("shading_model: {:?}", self.shading_model);
+        println!("metallic: {:?}", self.metallic);
+        println!("pbr_roughness: {:?}", self.pbr_roughness);
+
+        println!("clearcoat: {:?}", self.clearcoat);
+        println!("clearcoat_roughness: {:?}", self.clearcoat_roughness);
+        println!("clearcoat_normal_map_strength: {:?}", self.clearcoat_normal_map_strength);
+        println!("sheen_color: {:?}", self.sheen_color);
+        println!("sheen_roughness: {:?}", self.sheen_roughness);
+        println!("anisotropy: {:?}", self.anisotropy);
+        println!("anisotropy_rotation: {:?}", self.anisotropy_rotation);
+
+        println!("iridescence: {:?}", self.iridescence);
+        println!("iridescence_ior: {:?}", self.iridescence_ior);
+        println!("iridescence_thickness_min: {:?}", self.iridescence_thickness_min);
+        println!("iridescence_thickness_max: {:?}", self.iridescence_thickness_max);
+
+        self.medium.print();
+
         println!("monte_carlo: {:?}", self.monte_carlo);
 
         println!("smooth_shading: {:?}", self.smooth_shading);
@@ -372,6 +645,18 @@ impl Material
             {
                 self.texture_reflectivity = DynamicImage::new_rgb8(0,0);
             },
+            TextureType::ClearcoatNormal =>
+            {
+                self.texture_clearcoat_normal = DynamicImage::new_rgb8(0,0);
+            },
+            TextureType::IridescenceThickness =>
+            {
+                self.texture_iridescence_thickness = DynamicImage::new_rgb8(0,0);
+            },
+            TextureType::Anisotropy =>
+            {
+                self.texture_anisotropy = DynamicImage::new_rgb8(0,0);
+            },
         }
     }
 
@@ -414,6 +699,18 @@ impl Material
             {
                 self.texture_reflectivity = tex;
             },
+            TextureType::ClearcoatNormal =>
+            {
+                self.texture_clearcoat_normal = tex;
+            },
+            TextureType::IridescenceThickness =>
+            {
+                self.texture_iridescence_thickness = tex;
+            },
+            TextureType::Anisotropy =>
+            {
+                self.texture_anisotropy = tex;
+            },
         }
     }
 
@@ -455,6 +752,18 @@ impl Material
             {
                 self.texture_reflectivity = image.clone();
             },
+            TextureType::ClearcoatNormal =>
+            {
+                self.texture_clearcoat_normal = image.clone();
+            },
+            TextureType::IridescenceThickness =>
+            {
+                self.texture_iridescence_thickness = image.clone();
+            },
+            TextureType::Anisotropy =>
+            {
+                self.texture_anisotropy = image.clone();
+            },
         }
     }
 
@@ -475,6 +784,12 @@ impl Material
         self.texture_ambient_occlusion.width() > 0
         ||
         self.texture_reflectivity.width() > 0
+        ||
+        self.texture_clearcoat_normal.width() > 0
+        ||
+        self.texture_iridescence_thickness.width() > 0
+        ||
+        self.texture_anisotropy.width() > 0
     }
 
     pub fn has_texture(&self, tex_type: TextureType) -> bool
@@ -488,7 +803,10 @@ impl Material
             TextureType::Alpha => self.texture_alpha.width() > 0,
             TextureType::Roughness => self.texture_roughness.width() > 0,
             TextureType::AmbientOcclusion => self.texture_ambient_occlusion.width() > 0,
-            TextureType::Reflectivity => self.texture_reflectivity.width() > 0
+            TextureType::Reflectivity => self.texture_reflectivity.width() > 0,
+            TextureType::ClearcoatNormal => self.texture_clearcoat_normal.width() > 0,
+            TextureType::IridescenceThickness => self.texture_iridescence_thickness.width() > 0,
+            TextureType::Anisotropy => self.texture_anisotropy.width() > 0
         }
     }
 
@@ -503,7 +821,10 @@ impl Material
             TextureType::Alpha => self.texture_alpha.dimensions(),
             TextureType::Roughness => self.texture_roughness.dimensions(),
             TextureType::AmbientOcclusion => self.texture_ambient_occlusion.dimensions(),
-            TextureType::Reflectivity => self.texture_reflectivity.dimensions()
+            TextureType::Reflectivity => self.texture_reflectivity.dimensions(),
+            TextureType::ClearcoatNormal => self.texture_clearcoat_normal.dimensions(),
+            TextureType::IridescenceThickness => self.texture_iridescence_thickness.dimensions(),
+            TextureType::Anisotropy => self.texture_anisotropy.dimensions()
         }
     }
 
@@ -525,7 +846,10 @@ impl Material
             TextureType::Alpha => { pixel = self.texture_alpha.get_pixel(x, y); },
             TextureType::Roughness => { pixel = self.texture_roughness.get_pixel(x, y); },
             TextureType::AmbientOcclusion => { pixel = self.texture_ambient_occlusion.get_pixel(x, y); },
-            TextureType::Reflectivity => { pixel = self.texture_reflectivity.get_pixel(x, y); }
+            TextureType::Reflectivity => { pixel = self.texture_reflectivity.get_pixel(x, y); },
+            TextureType::ClearcoatNormal => { pixel = self.texture_clearcoat_normal.get_pixel(x, y); },
+            TextureType::IridescenceThickness => { pixel = self.texture_iridescence_thickness.get_pixel(x, y); },
+            TextureType::Anisotropy => { pixel = self.texture_anisotropy.get_pixel(x, y); }
         }
 
         let rgba = pixel.to_rgba();
@@ -557,7 +881,10 @@ impl Material
             TextureType::Alpha => { tex = &self.texture_alpha; },
             TextureType::Roughness => { tex = &self.texture_roughness; },
             TextureType::AmbientOcclusion => { tex = &self.texture_ambient_occlusion; },
-            TextureType::Reflectivity => { tex = &self.texture_reflectivity; }
+            TextureType::Reflectivity => { tex = &self.texture_reflectivity; },
+            TextureType::ClearcoatNormal => { tex = &self.texture_clearcoat_normal; },
+            TextureType::IridescenceThickness => { tex = &self.texture_iridescence_thickness; },
+            TextureType::Anisotropy => { tex = &self.texture_anisotropy; }
         }
 
         let width = tex.width();
@@ -629,6 +956,26 @@ impl Material
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadingModel
+{
+    Phong,
+    Pbr
+}
+
+impl ShadingModel
+{
+    pub fn from_str(name: &str) -> Option<ShadingModel>
+    {
+        match name
+        {
+            "phong" => Some(ShadingModel::Phong),
+            "pbr" | "ggx" | "metallic_roughness" => Some(ShadingModel::Pbr),
+            _ => None
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TextureType
 {
@@ -640,11 +987,15 @@ pub enum TextureType
     Roughness,
     AmbientOcclusion,
     Reflectivity,
+    ClearcoatNormal,
+    IridescenceThickness,
+    Anisotropy,
 }
 
 pub struct AnimationData
 {
     pub trans_initial: Matrix4<f32>,
+    pub motion: Vec<Matrix4<f32>>, //transformations during the shutter interval (empty = no motion blur)
 }
 
 impl AnimationData
@@ -653,7 +1004,46 @@ impl AnimationData
     {
         AnimationData
         {
-            trans_initial: Matrix4::<f32>::identity()
+            trans_initial: Matrix4::<f32>::identity(),
+            motion: vec![]
+        }
+    }
+}
+
+// visibility of an object per ray type (a hidden object is still part of the scene for all other rays)
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct RayVisibility
+{
+    pub camera: bool,
+    pub shadow: bool,
+    pub reflection: bool,
+    pub refraction: bool,
+    pub indirect: bool
+}
+
+impl RayVisibility
+{
+    pub fn new() -> RayVisibility
+    {
+        RayVisibility
+        {
+            camera: true,
+            shadow: true,
+            reflection: true,
+            refraction: true,
+            indirect: true
+        }
+    }
+
+    pub fn is_visible(&self, ray_type: RayType) -> bool
+    {
+        match ray_type
+        {
+            RayType::Camera => self.camera,
+            RayType::Shadow => self.shadow,
+            RayType::Reflection => self.reflection,
+            RayType::Refraction => self.refraction,
+            RayType::Indirect => self.indirect
         }
     }
 }
@@ -662,8 +1052,10 @@ pub struct ShapeBasics
 {
     pub id: u32,
     pub name: String,
+    pub group: String, //used for light linking (next to the name)
 
     pub visible: bool,
+    pub ray_visibility: RayVisibility,
     pub flip_normals: bool,
 
     pub trans: Matrix4<f32>,
@@ -687,7 +1079,9 @@ impl ShapeBasics
         {
             id: 0,
             name: name.to_string(),
+            group: String::new(),
             visible: true,
+            ray_visibility: RayVisibility::new(),
             flip_normals: false,
             trans: Matrix4::<f32>::identity(),
             tran_inverse: Matrix4::<f32>::identity(),
@@ -754,12 +1148,51 @@ impl ShapeBasics
 
     pub fn get_inverse_ray(&self, ray: &Ray) -> Ray
     {
-        let ray_inverse_start = self.tran_inverse * ray.origin.to_homogeneous();
-        let ray_inverse_dir = self.tran_inverse * ray.dir.to_homogeneous();
+        let tran_inverse = self.get_inverse_trans();
+
+        let ray_inverse_start = tran_inverse * ray.origin.to_homogeneous();
+        let ray_inverse_dir = tran_inverse * ray.dir.to_homogeneous();
 
         Ray::new(Point3::from_homogeneous(ray_inverse_start).unwrap(), Vector3::from_homogeneous(ray_inverse_dir).unwrap())
     }
 
+    // transformation at the time of the current sample (motion blur)
+    pub fn get_trans(&self) -> Matrix4<f32>
+    {
+        if self.animation_data.motion.is_empty()
+        {
+            return self.trans;
+        }
+
+        interpolate_motion(&self.animation_data.motion, get_sample_time())
+    }
+
+    pub fn get_inverse_trans(&self) -> Matrix4<f32>
+    {
+        if self.animation_data.motion.is_empty()
+        {
+            return self.tran_inverse;
+        }
+
+        self.get_trans().try_inverse().unwrap_or(self.tran_inverse)
+    }
+
+    // all transformations the shape can have while the shutter is open
+    pub fn get_bounds_trans(&self) -> Vec<Matrix4<f32>>
+    {
+        if self.animation_data.motion.is_empty()
+        {
+            return vec![self.trans];
+        }
+
+        self.animation_data.motion.clone()
+    }
+
+    pub fn apply_motion(&mut self, motion: Vec<Matrix4<f32>>)
+    {
+        self.animation_data.motion = motion;
+    }
+
     pub fn calc_inverse(&mut self)
     {
         //because we are dealing with 4x4 matrices: unwrap should be fine
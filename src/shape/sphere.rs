@@ -60,7 +60,7 @@ impl Shape for Sphere
         let res = self.ball.cast_local_ray_and_get_normal(&ray_inverse, std::f32::MAX, solid);
         if let Some(res) = res
         {
-            let normal = self.basic.trans * res.normal.to_homogeneous();
+            let normal = self.basic.get_trans() * res.normal.to_homogeneous();
             return Some((res.toi, normal.xyz().normalize(), 0))
         }
         None
@@ -68,7 +68,7 @@ impl Shape for Sphere
 
     fn get_uv(&self, hit: Point3<f32>, _face_id: u32) -> Point2<f32>
     {
-        let hit_pos_local = self.basic.tran_inverse * hit.to_homogeneous();
+        let hit_pos_local = self.basic.get_inverse_trans() * hit.to_homogeneous();
         let hit_pos_local = Point3::<f32>::from_homogeneous(hit_pos_local).unwrap();
 
         // https://gamedev.stackexchange.com/questions/114412/how-to-get-uv-coordinates-for-sphere-cylindrical-projection
@@ -97,6 +97,34 @@ impl Shape for Sphere
 
         Point2::<f32>::new(u, -v)
     }
+
+    fn get_sphere(&self) -> Option<(Point3<f32>, f32)>
+    {
+        //transformation of the frame (emissive shapes are not sampled along their motion)
+        let trans = self.basic.trans;
+
+        let center = Point3::<f32>::from_homogeneous(trans * Point3::<f32>::origin().to_homogeneous()).unwrap();
+        let radius = (trans * Vector3::<f32>::new(self.ball.radius, 0.0, 0.0).to_homogeneous()).xyz().norm();
+
+        Some((center, radius))
+    }
+
+    fn get_tangent(&self, hit: Point3<f32>, _face_id: u32) -> Option<Vector3<f32>>
+    {
+        let hit_pos_local = self.basic.get_inverse_trans() * hit.to_homogeneous();
+        let hit_pos_local = Point3::<f32>::from_homogeneous(hit_pos_local).unwrap();
+
+        //derivative of the longitude used in get_uv (undefined at the poles)
+        let tangent = Vector3::<f32>::new(hit_pos_local.z, 0.0, -hit_pos_local.x);
+        let tangent = (self.basic.get_trans() * tangent.to_homogeneous()).xyz();
+
+        if tangent.magnitude_squared() <= 0.00000001
+        {
+            return None;
+        }
+
+        Some(tangent.normalize())
+    }
 }
 
 impl Sphere
@@ -0,0 +1,280 @@
+use std::f32::consts::PI;
+
+use chrono::{DateTime, Datelike, Timelike, TimeZone, Utc};
+use nalgebra::Vector3;
+
+pub const SKY_ENVIRONMENT_NAME: &str = "sky";
+
+const SKY_MAP_WIDTH: usize = 512;
+const SKY_MAP_HEIGHT: usize = 256;
+
+// the preetham model returns the luminance in kcd/m² --> scale to the unit range of the renderer
+const SKY_RADIANCE_SCALE: f32 = 0.01;
+
+// the preetham model is only valid for the sun above the horizon
+const MAX_SUN_ZENITH: f32 = PI / 2.0 - 0.01;
+
+/*
+some resources:
+
+preetham sky model:
+https://courses.cs.duke.edu/cps124/spring08/assign/07_papers/p91-preetham.pdf
+https://github.com/mrdoob/three.js/blob/dev/examples/jsm/objects/Sky.js
+
+sun position (NOAA):
+https://gml.noaa.gov/grad/solcalc/solareqns.PDF
+
+coordinate system: y is up, -z is north and +x is east
+*/
+
+// ******************** Sky ********************
+pub struct Sky
+{
+    pub enabled: bool,
+
+    pub turbidity: f32,
+    pub ground_albedo: f32,
+    pub intensity: f32,
+    pub sun_intensity: f32,
+
+    // sun direction
+    pub sun_elevation: f32, //in degree
+    pub sun_azimuth: f32, //in degree (0 = north, 90 = east)
+
+    // sun direction based on the location and time
+    pub use_location: bool,
+    pub latitude: f32,
+    pub longitude: f32,
+    pub date_time: DateTime<Utc>,
+
+    pub sun_light_id: Option<u32>
+}
+
+impl Sky
+{
+    pub fn new() -> Sky
+    {
+        Sky
+        {
+            enabled: false,
+
+            turbidity: 3.0,
+            ground_albedo: 0.3,
+            intensity: 1.0,
+            sun_intensity: 3.0,
+
+            sun_elevation: 45.0,
+            sun_azimuth: 135.0,
+
+            use_location: false,
+            latitude: 48.14,
+            longitude: 11.58,
+            date_time: Utc.with_ymd_and_hms(2024, 6, 21, 12, 0, 0).unwrap(), //fixed default for reproducible renders
+
+            sun_light_id: None
+        }
+    }
+
+    pub fn set_sun_direction(&mut self, dir_to_sun: Vector3<f32>)
+    {
+        let dir = dir_to_sun.normalize();
+
+        self.sun_elevation = dir.y.clamp(-1.0, 1.0).asin().to_degrees();
+        self.sun_azimuth = dir.x.atan2(-dir.z).to_degrees().rem_euclid(360.0);
+    }
+
+    // direction towards the sun
+    pub fn get_sun_direction(&self) -> Vector3<f32>
+    {
+        let mut elevation = self.sun_elevation;
+        let mut azimuth = self.sun_azimuth;
+
+        if self.use_location
+        {
+            (elevation, azimuth) = self.get_sun_position_from_location();
+        }
+
+        let elevation = elevation.to_radians();
+        let azimuth = azimuth.to_radians();
+
+        Vector3::<f32>::new(elevation.cos() * azimuth.sin(), elevation.sin(), -elevation.cos() * azimuth.cos()).normalize()
+    }
+
+    // returns elevation and azimuth (in degree)
+    pub fn get_sun_position_from_location(&self) -> (f32, f32)
+    {
+        let day_of_year = self.date_time.ordinal() as f32;
+        let hour = self.date_time.hour() as f32 + self.date_time.minute() as f32 / 60.0 + self.date_time.second() as f32 / 3600.0;
+
+        // fractional year
+        let gamma = 2.0 * PI / 365.0 * (day_of_year - 1.0 + (hour - 12.0) / 24.0);
+
+        // equation of time (in minutes) and declination
+        let eqtime = 229.18 * (0.000075 + 0.001868 * gamma.cos() - 0.032077 * gamma.sin() - 0.014615 * (2.0 * gamma).cos() - 0.040849 * (2.0 * gamma).sin());
+        let decl = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin() - 0.006758 * (2.0 * gamma).cos() + 0.000907 * (2.0 * gamma).sin() - 0.002697 * (3.0 * gamma).cos() + 0.00148 * (3.0 * gamma).sin();
+
+        // true solar time and hour angle
+        let time_offset = eqtime + 4.0 * self.longitude;
+        let true_solar_time = hour * 60.0 + time_offset;
+        let hour_angle = (true_solar_time / 4.0 - 180.0).to_radians();
+
+        let lat = self.latitude.to_radians();
+
+        // east, north, up
+        let east = -decl.cos() * hour_angle.sin();
+        let north = decl.sin() * lat.cos() - decl.cos() * hour_angle.cos() * lat.sin();
+        let up = decl.sin() * lat.sin() + decl.cos() * hour_angle.cos() * lat.cos();
+
+        let elevation = up.clamp(-1.0, 1.0).asin().to_degrees();
+        let azimuth = east.atan2(north).to_degrees().rem_euclid(360.0);
+
+        (elevation, azimuth)
+    }
+
+    // atmospheric transmittance of the direct sun light (rayleigh and aerosol scattering -- preetham appendix)
+    pub fn get_sun_color(&self) -> Vector3<f32>
+    {
+        let sun_dir = self.get_sun_direction();
+
+        if sun_dir.y <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        let zenith = sun_dir.y.acos();
+        let zenith_deg = zenith.to_degrees();
+
+        // relative optical mass (kasten and young)
+        let m = 1.0 / (zenith.cos() + 0.15 * (93.885 - zenith_deg).powf(-1.253));
+
+        // angstrom turbidity
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        // wavelengths (in micrometer) for r, g, b
+        let lambdas = [0.680, 0.550, 0.440];
+
+        let mut color = Vector3::<f32>::zeros();
+        for i in 0..3
+        {
+            let lambda: f32 = lambdas[i];
+
+            let rayleigh = (-0.008735 * lambda.powf(-4.08) * m).exp();
+            let aerosol = (-beta * lambda.powf(-1.3) * m).exp();
+
+            color[i] = rayleigh * aerosol;
+        }
+
+        color
+    }
+
+    fn perez(theta: f32, gamma: f32, coeffs: &[f32; 5]) -> f32
+    {
+        let cos_theta = theta.cos().max(0.01);
+
+        (1.0 + coeffs[0] * (coeffs[1] / cos_theta).exp()) * (1.0 + coeffs[2] * (coeffs[3] * gamma).exp() + coeffs[4] * gamma.cos() * gamma.cos())
+    }
+
+    // sky radiance (upper hemisphere) based on the preetham model
+    pub fn eval(&self, dir: &Vector3<f32>, sun_dir: &Vector3<f32>) -> Vector3<f32>
+    {
+        let t = self.turbidity;
+
+        let theta_s = sun_dir.y.clamp(-1.0, 1.0).acos().min(MAX_SUN_ZENITH);
+        let theta = dir.y.clamp(0.0, 1.0).acos();
+        let gamma = dir.dot(sun_dir).clamp(-1.0, 1.0).acos();
+
+        // distribution coefficients
+        let coeffs_y = [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703];
+        let coeffs_x = [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452];
+        let coeffs_yc = [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529];
+
+        // zenith values
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+
+        let ts = theta_s;
+        let ts2 = ts * ts;
+        let ts3 = ts2 * ts;
+        let t2 = t * t;
+
+        let zenith_x =
+            t2 * (0.00166 * ts3 - 0.00375 * ts2 + 0.00209 * ts) +
+            t * (-0.02903 * ts3 + 0.06377 * ts2 - 0.03202 * ts + 0.00394) +
+            (0.11693 * ts3 - 0.21196 * ts2 + 0.06052 * ts + 0.25886);
+
+        let zenith_yc =
+            t2 * (0.00275 * ts3 - 0.00610 * ts2 + 0.00317 * ts) +
+            t * (-0.04214 * ts3 + 0.08970 * ts2 - 0.04153 * ts + 0.00516) +
+            (0.15346 * ts3 - 0.26756 * ts2 + 0.06670 * ts + 0.26688);
+
+        // Yxy
+        let lum = zenith_y * Sky::perez(theta, gamma, &coeffs_y) / Sky::perez(0.0, theta_s, &coeffs_y);
+        let x = zenith_x * Sky::perez(theta, gamma, &coeffs_x) / Sky::perez(0.0, theta_s, &coeffs_x);
+        let y = zenith_yc * Sky::perez(theta, gamma, &coeffs_yc) / Sky::perez(0.0, theta_s, &coeffs_yc);
+
+        if y <= 0.0
+        {
+            return Vector3::<f32>::zeros();
+        }
+
+        // Yxy --> XYZ --> linear sRGB
+        let cx = x / y * lum;
+        let cy = lum;
+        let cz = (1.0 - x - y) / y * lum;
+
+        let r = 3.2406 * cx - 1.5372 * cy - 0.4986 * cz;
+        let g = -0.9689 * cx + 1.8758 * cy + 0.0415 * cz;
+        let b = 0.0557 * cx - 0.2040 * cy + 1.0570 * cz;
+
+        Vector3::<f32>::new(r.max(0.0), g.max(0.0), b.max(0.0)) * (SKY_RADIANCE_SCALE * self.intensity)
+    }
+
+    // equirectangular map (same layout as the environment map)
+    pub fn bake(&self) -> (usize, usize, Vec<Vector3<f32>>)
+    {
+        let sun_dir = self.get_sun_direction();
+
+        let mut pixels = vec![Vector3::<f32>::zeros(); SKY_MAP_WIDTH * SKY_MAP_HEIGHT];
+
+        // fade out the sky when the sun goes down
+        let mut sky_fade = 1.0;
+        if sun_dir.y < 0.0
+        {
+            sky_fade = (1.0 + sun_dir.y * 10.0).max(0.0);
+        }
+
+        // upper hemisphere
+        let mut irradiance = Vector3::<f32>::zeros();
+        let pixel_solid_angle_base = (2.0 * PI / SKY_MAP_WIDTH as f32) * (PI / SKY_MAP_HEIGHT as f32);
+
+        for y in 0..SKY_MAP_HEIGHT / 2
+        {
+            let theta = PI * (y as f32 + 0.5) / SKY_MAP_HEIGHT as f32;
+
+            for x in 0..SKY_MAP_WIDTH
+            {
+                let phi = (((x as f32 + 0.5) / SKY_MAP_WIDTH as f32) - 0.5) * 2.0 * PI;
+                let dir = Vector3::<f32>::new(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos());
+
+                let radiance = self.eval(&dir, &sun_dir) * sky_fade;
+                pixels[y * SKY_MAP_WIDTH + x] = radiance;
+
+                irradiance += radiance * (theta.cos() * theta.sin() * pixel_solid_angle_base);
+            }
+        }
+
+        // ground: diffuse reflection of the sky and sun
+        irradiance += self.get_sun_color() * (self.sun_intensity * PI * sun_dir.y.max(0.0));
+        let ground = irradiance * (self.ground_albedo / PI);
+
+        for y in SKY_MAP_HEIGHT / 2..SKY_MAP_HEIGHT
+        {
+            for x in 0..SKY_MAP_WIDTH
+            {
+                pixels[y * SKY_MAP_WIDTH + x] = ground;
+            }
+        }
+
+        (SKY_MAP_WIDTH, SKY_MAP_HEIGHT, pixels)
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::OnceLock;
+use std::cell::Cell;
+
+use nalgebra::Vector3;
+
+pub const MIN_WAVELENGTH: f32 = 380.0; //nm
+pub const MAX_WAVELENGTH: f32 = 780.0; //nm
+
+/*
+some resources:
+
+analytic approximation of the CIE 1931 color matching functions:
+https://jcgt.org/published/0002/02/01/
+
+dispersion (cauchy and sellmeier equation):
+https://en.wikipedia.org/wiki/Cauchy%27s_equation
+https://en.wikipedia.org/wiki/Sellmeier_equation
+https://refractiveindex.info
+
+each sample carries one wavelength: the rgb result of the sample is weighted by the (linear sRGB) response of the wavelength
+the wavelength is only chosen once a dispersive material is hit (all other samples are staying rgb)
+*/
+
+// ******************** DispersionModel ********************
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DispersionModel
+{
+    None,
+    Cauchy,
+    Sellmeier
+}
+
+impl DispersionModel
+{
+    pub fn from_str(name: &str) -> Option<DispersionModel>
+    {
+        match name.to_lowercase().as_str()
+        {
+            "none" => Some(DispersionModel::None),
+            "cauchy" => Some(DispersionModel::Cauchy),
+            "sellmeier" => Some(DispersionModel::Sellmeier),
+            _ => None
+        }
+    }
+}
+
+// n = A + B / λ² + C / λ⁴ (λ in micrometer)
+pub fn cauchy_refraction_index(wavelength: f32, coefficients: &Vector3<f32>) -> f32
+{
+    let lambda = wavelength / 1000.0;
+    let lambda_sq = lambda * lambda;
+
+    coefficients.x + coefficients.y / lambda_sq + coefficients.z / (lambda_sq * lambda_sq)
+}
+
+// n² = 1 + Σ Bi λ² / (λ² - Ci) (λ in micrometer, Ci in micrometer²)
+pub fn sellmeier_refraction_index(wavelength: f32, b: &Vector3<f32>, c: &Vector3<f32>) -> f32
+{
+    let lambda = wavelength / 1000.0;
+    let lambda_sq = lambda * lambda;
+
+    let mut n_sq = 1.0;
+    for i in 0..3
+    {
+        n_sq += b[i] * lambda_sq / (lambda_sq - c[i]);
+    }
+
+    n_sq.max(1.0).sqrt()
+}
+
+// ******************** color matching ********************
+
+fn piecewise_gaussian(x: f32, mu: f32, sigma_low: f32, sigma_high: f32) -> f32
+{
+    let sigma = if x < mu { sigma_low } else { sigma_high };
+    let t = (x - mu) / sigma;
+
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 2° observer (multi-lobe fit)
+pub fn wavelength_to_xyz(wavelength: f32) -> Vector3<f32>
+{
+    let l = wavelength;
+
+    let x = 1.056 * piecewise_gaussian(l, 599.8, 37.9, 31.0) + 0.362 * piecewise_gaussian(l, 442.0, 16.0, 26.7) - 0.065 * piecewise_gaussian(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * piecewise_gaussian(l, 568.8, 46.9, 40.5) + 0.286 * piecewise_gaussian(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * piecewise_gaussian(l, 437.0, 11.8, 36.0) + 0.681 * piecewise_gaussian(l, 459.0, 26.0, 13.8);
+
+    Vector3::<f32>::new(x, y, z)
+}
+
+pub fn xyz_to_rgb(xyz: &Vector3<f32>) -> Vector3<f32>
+{
+    let r = 3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z;
+    let g = -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z;
+    let b = 0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z;
+
+    Vector3::<f32>::new(r, g, b)
+}
+
+// integral of the rgb response over the visible range (used to map a flat spectrum to white)
+fn get_rgb_response_integral() -> &'static Vector3<f32>
+{
+    static INTEGRAL: OnceLock<Vector3<f32>> = OnceLock::new();
+
+    INTEGRAL.get_or_init(||
+    {
+        let mut integral = Vector3::<f32>::zeros();
+
+        let mut wavelength = MIN_WAVELENGTH + 0.5;
+        while wavelength < MAX_WAVELENGTH
+        {
+            integral += xyz_to_rgb(&wavelength_to_xyz(wavelength));
+            wavelength += 1.0;
+        }
+
+        integral
+    })
+}
+
+// uniform wavelength sampling (pdf = 1 / (MAX_WAVELENGTH - MIN_WAVELENGTH))
+pub fn sample_wavelength(u: f32) -> f32
+{
+    MIN_WAVELENGTH + (MAX_WAVELENGTH - MIN_WAVELENGTH) * u.clamp(0.0, 1.0)
+}
+
+// rgb weight of a sampled wavelength -- the average over all wavelengths is exactly (1, 1, 1)
+pub fn get_wavelength_rgb_weight(wavelength: f32) -> Vector3<f32>
+{
+    let rgb = xyz_to_rgb(&wavelength_to_xyz(wavelength));
+    let pdf = 1.0 / (MAX_WAVELENGTH - MIN_WAVELENGTH);
+
+    rgb.component_div(get_rgb_response_integral()) / pdf
+}
+
+// ******************** SpectralSample ********************
+pub struct SpectralSample
+{
+    u: Option<f32>, //random number of the wavelength (None = spectral mode is off)
+    wavelength: Cell<Option<f32>>
+}
+
+impl SpectralSample
+{
+    pub fn new(u: Option<f32>) -> SpectralSample
+    {
+        SpectralSample
+        {
+            u: u,
+            wavelength: Cell::new(None)
+        }
+    }
+
+    // the wavelength is sampled on the first request (dispersive materials only)
+    pub fn get_wavelength(&self) -> Option<f32>
+    {
+        let u = self.u?;
+
+        if self.wavelength.get().is_none()
+        {
+            self.wavelength.set(Some(sample_wavelength(u)));
+        }
+
+        self.wavelength.get()
+    }
+
+    // (1, 1, 1) if no wavelength was sampled
+    pub fn get_rgb_weight(&self) -> Vector3<f32>
+    {
+        match self.wavelength.get()
+        {
+            Some(wavelength) => get_wavelength_rgb_weight(wavelength),
+            None => Vector3::<f32>::new(1.0, 1.0, 1.0)
+        }
+    }
+}
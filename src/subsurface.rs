@@ -0,0 +1,156 @@
+use nalgebra::{Point3, Vector3};
+use parry3d::query::Ray;
+
+use crate::bsdf::Bsdf;
+use crate::helper::cosine_sample_hemisphere;
+use crate::medium::sample_henyey_greenstein;
+use crate::sampler::{sample_1d, sample_2d};
+use crate::raytracing::{Raytracing, SHADOW_BIAS};
+use crate::shape::{Shape, Material, ShadingModel};
+
+const MAX_RANDOM_WALK_STEPS: u32 = 256;
+const MIN_SCATTERING_DISTANCE: f32 = 0.00001;
+
+/*
+some resources:
+
+random walk subsurface scattering:
+https://graphics.pixar.com/library/PathTracedSubsurface/paper.pdf
+https://blog.selfshadow.com/publications/s2017-shading-course/imageworks/s2017_pbs_imageworks_sss.pdf
+
+albedo inversion (multiple scattering albedo --> single scattering albedo):
+Chiang et al. 2016, "Practical and Controllable Subsurface Scattering for Production Path Tracing"
+
+the walk only considers the object itself (other objects inside of it are ignored), the entry and the exit are diffuse transmissions
+whitted is falling back to path tracing for scenes with subsurface materials, bidirectional and photon tracing are shading them with the surface bsdf only
+*/
+
+// ******************** SubsurfaceExit ********************
+pub struct SubsurfaceExit
+{
+    pub point: Point3<f32>,
+    pub normal: Vector3<f32>, //outside facing
+    pub weight: Vector3<f32>
+}
+
+// extinction and single scattering albedo based on the (multiple scattering) subsurface color and the mean free path
+pub fn get_subsurface_coefficients(material: &Material) -> (Vector3<f32>, Vector3<f32>)
+{
+    let color = material.subsurface_color.map(|c| c.clamp(0.0, 0.999));
+    let radius = (material.subsurface_radius * material.subsurface_scale).map(|r| r.max(MIN_SCATTERING_DISTANCE));
+
+    let albedo = color.map(|a| 1.0 - (a * (-5.09406 + a * (2.61188 - a * 4.31805))).exp());
+    let s = color.map(|a| 1.9 - a + 3.5 * (a - 0.8) * (a - 0.8));
+
+    let extinction = Vector3::<f32>::new(1.0 / (radius.x * s.x), 1.0 / (radius.y * s.y), 1.0 / (radius.z * s.z));
+
+    (extinction, albedo)
+}
+
+// lambertian exit (white --> the color is the result of the walk)
+pub fn get_subsurface_exit_bsdf() -> Bsdf
+{
+    Bsdf::new(ShadingModel::Phong, Vector3::<f32>::new(1.0, 1.0, 1.0), 0.0, 1.0, 1.0)
+}
+
+impl Raytracing
+{
+    // normal: surface normal on the side of the incoming ray
+    pub fn subsurface_random_walk(&self, item: &dyn Shape, material: &Material, hit_point: &Point3<f32>, normal: Vector3<f32>) -> Option<SubsurfaceExit>
+    {
+        let (extinction, albedo) = get_subsurface_coefficients(material);
+
+        let mut weight = Vector3::<f32>::new(1.0, 1.0, 1.0);
+        let mut point = hit_point - (normal * SHADOW_BIAS);
+        let (u1, u2) = sample_2d();
+        let mut dir = cosine_sample_hemisphere(-normal, u1, u2);
+
+        for _ in 0..MAX_RANDOM_WALK_STEPS
+        {
+            let ray = Ray::new(point, dir);
+            let hit = item.intersect(&ray, true);
+
+            let mut max_dist = std::f32::MAX;
+            if let Some(hit) = &hit
+            {
+                max_dist = hit.0;
+            }
+
+            // choose the color channel based on the current weight (the sample weight is the balance heuristic over all channels)
+            let weight_sum = weight.sum();
+            if weight_sum <= 0.0
+            {
+                return None;
+            }
+
+            let channel_probability = weight / weight_sum;
+
+            let u = sample_1d();
+            let mut channel = 2;
+            if u < channel_probability.x
+            {
+                channel = 0;
+            }
+            else if u < channel_probability.x + channel_probability.y
+            {
+                channel = 1;
+            }
+
+            let u_dist: f32 = sample_1d();
+            let dist = -(1.0 - u_dist).ln() / extinction[channel];
+
+            if dist < max_dist
+            {
+                // scattering inside of the object
+                let transmittance = (-extinction * dist).map(|t| t.exp());
+                let pdf = channel_probability.dot(&extinction.component_mul(&transmittance));
+
+                if pdf <= 0.0
+                {
+                    return None;
+                }
+
+                weight = weight.component_mul(&albedo.component_mul(&extinction).component_mul(&transmittance)) / pdf;
+
+                point = point + (dir * dist);
+                let (u1, u2) = sample_2d();
+                dir = sample_henyey_greenstein(dir, 0.0, u1, u2);
+            }
+            else
+            {
+                // not closed geometry
+                if hit.is_none()
+                {
+                    return None;
+                }
+
+                let hit = hit.unwrap();
+
+                let transmittance = (-extinction * max_dist).map(|t| t.exp());
+                let pdf = channel_probability.dot(&transmittance);
+
+                if pdf <= 0.0
+                {
+                    return None;
+                }
+
+                weight = weight.component_mul(&transmittance) / pdf;
+
+                let mut exit_normal = hit.1;
+                if exit_normal.dot(&dir) < 0.0
+                {
+                    exit_normal = -exit_normal;
+                }
+
+                return Some(SubsurfaceExit
+                {
+                    point: point + (dir * max_dist),
+                    normal: exit_normal,
+                    weight: weight
+                });
+            }
+        }
+
+        None
+    }
+}